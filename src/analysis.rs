@@ -0,0 +1,386 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::io;
+
+use chrono::NaiveDate;
+use serde::Serialize;
+
+use super::config::{Config, MetricKind};
+use super::journal::Journal;
+use super::month_log::{Entry, MetricValue};
+
+/// Results based on fewer days than this are marked, they don't tell much.
+const FEW_DAYS: u32 = 5;
+
+/// What the habits, tags and custom metrics have to do with the rating of the
+/// days over a range of days.
+#[derive(Serialize)]
+pub struct Analysis {
+    /// First day of the range.
+    pub from: NaiveDate,
+    /// Last day of the range (inclusive).
+    pub to: NaiveDate,
+    /// Number of rated days in the range, only these are analyzed.
+    pub days: u32,
+    /// Average rating of those days.
+    pub average: Option<f64>,
+    /// Average rating with and without each habit, yes/no metric and option
+    /// of a choice metric, the strongest positive difference first.
+    pub differences: Vec<Difference>,
+    /// Average rating with and without each tag found in the entries, the
+    /// strongest positive difference first.
+    pub tags: Vec<Difference>,
+    /// Correlation of each numeric metric with the rating, the strongest
+    /// positive correlation first.
+    pub correlations: Vec<Correlation>,
+}
+
+/// Average rating of the days that have something compared to the days
+/// that don't (eg a habit that was done or not).
+#[derive(Serialize)]
+pub struct Difference {
+    /// Name of the habit, tag or metric (eg `meditate`, `#work` or
+    /// `caffeine = a cup`).
+    pub factor: String,
+    pub average_with: Option<f64>,
+    pub days_with: u32,
+    pub average_without: Option<f64>,
+    pub days_without: u32,
+}
+
+/// Pearson correlation of a numeric metric with the rating.
+#[derive(Serialize)]
+pub struct Correlation {
+    pub metric: String,
+    /// Between `-1` and `1`, `None` if there is not enough data.
+    pub coefficient: Option<f64>,
+    /// Number of rated days that have a value for the metric.
+    pub days: u32,
+}
+
+impl Difference {
+    /// Return how much higher the rating is on days that have the factor.
+    pub fn difference(&self) -> Option<f64> {
+        Some(self.average_with? - self.average_without?)
+    }
+}
+
+impl Analysis {
+    /// Analyze the rated days in the range `from..=to`.
+    ///
+    /// Habits are taken from `config` and from the entries (so habits that
+    /// were removed from the config are still analyzed), metrics only from
+    /// `config` since their kind is needed.
+    pub fn for_range(
+        journal: &mut Journal,
+        from: NaiveDate,
+        to: NaiveDate,
+        config: &Config,
+    ) -> io::Result<Self> {
+        let entries: Vec<&Entry> = journal
+            .days(from, to)?
+            .map(|(_, entry)| entry)
+            .filter(|entry| !entry.is_default() && (-2..=2).contains(&entry.get_rating()))
+            .collect();
+
+        let mut habits = config.habits.clone();
+        for entry in &entries {
+            for habit in entry.get_habits() {
+                if !habits.contains(habit) {
+                    habits.push(habit.clone());
+                }
+            }
+        }
+
+        let mut differences: Vec<Difference> = habits
+            .iter()
+            .map(|habit| difference(habit, &entries, |entry| Some(entry.has_done(habit))))
+            .collect();
+
+        let entry_tags: Vec<Vec<String>> = entries.iter().map(|entry| entry.tags()).collect();
+        let mut tags: Vec<&String> = Vec::new();
+        for tag in entry_tags.iter().flatten() {
+            if !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+        let mut tags: Vec<Difference> = tags
+            .into_iter()
+            .map(|tag| {
+                let days = entries.iter().zip(&entry_tags).map(|(entry, entry_tags)| {
+                    (entry.get_rating(), Some(entry_tags.contains(tag)))
+                });
+                difference_of_days(&format!("#{}", tag), days)
+            })
+            .collect();
+
+        let mut correlations = Vec::new();
+
+        for metric in &config.metrics {
+            let value = |entry: &Entry| entry.get_metrics().get(&metric.name).cloned();
+
+            match &metric.kind {
+                MetricKind::Boolean => {
+                    differences.push(difference(&metric.name, &entries, |entry| {
+                        match value(entry) {
+                            Some(MetricValue::Boolean(value)) => Some(value),
+                            _ => None,
+                        }
+                    }));
+                }
+                MetricKind::Choice { options } => {
+                    for option in options {
+                        let factor = format!("{} = {}", metric.name, option);
+                        differences.push(difference(&factor, &entries, |entry| {
+                            match value(entry) {
+                                Some(MetricValue::Choice(choice)) => Some(&choice == option),
+                                _ => None,
+                            }
+                        }));
+                    }
+                }
+                MetricKind::Number { .. } => {
+                    let pairs: Vec<(f64, f64)> = entries
+                        .iter()
+                        .filter_map(|entry| match value(entry) {
+                            Some(MetricValue::Number(number)) => {
+                                Some((number, f64::from(entry.get_rating())))
+                            }
+                            _ => None,
+                        })
+                        .collect();
+                    correlations.push(Correlation {
+                        metric: metric.name.clone(),
+                        coefficient: pearson(&pairs),
+                        days: pairs.len() as u32,
+                    });
+                }
+            }
+        }
+
+        differences.sort_by(|a, b| descending(a.difference(), b.difference()));
+        tags.sort_by(|a, b| descending(a.difference(), b.difference()));
+        correlations.sort_by(|a, b| descending(a.coefficient, b.coefficient));
+
+        Ok(Analysis {
+            from,
+            to,
+            days: entries.len() as u32,
+            average: average(entries.iter().map(|entry| entry.get_rating())),
+            differences,
+            tags,
+            correlations,
+        })
+    }
+}
+
+impl fmt::Display for Analysis {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} rated days from {} to {}, average rating {}",
+            self.days,
+            self.from,
+            self.to,
+            format_rating(self.average)
+        )?;
+
+        write_differences(
+            f,
+            "habits and yes/no fields (average rating with / without):",
+            &self.differences,
+        )?;
+        write_differences(f, "tags (average rating with / without):", &self.tags)?;
+
+        if !self.correlations.is_empty() {
+            write!(f, "\n\nnumeric fields (correlation with the rating):")?;
+            for correlation in &self.correlations {
+                write!(
+                    f,
+                    "\n{:>6}  {} ({} days)",
+                    format_rating(correlation.coefficient),
+                    correlation.metric,
+                    correlation.days
+                )?;
+                if correlation.days < FEW_DAYS {
+                    write!(f, " - few days")?;
+                }
+            }
+        }
+
+        if self.differences.is_empty() && self.tags.is_empty() && self.correlations.is_empty() {
+            write!(
+                f,
+                "\n\nthere is nothing to analyze yet, add habits or metrics to the config file \
+                 or #tags to the entries."
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Write a list of differences under `title`, nothing if it's empty.
+fn write_differences(
+    f: &mut fmt::Formatter<'_>,
+    title: &str,
+    differences: &[Difference],
+) -> fmt::Result {
+    if differences.is_empty() {
+        return Ok(());
+    }
+
+    write!(f, "\n\n{}", title)?;
+    for difference in differences {
+        write!(
+            f,
+            "\n{:>6}  {}: {} ({} days) / {} ({} days)",
+            format_rating(difference.difference()),
+            difference.factor,
+            format_rating(difference.average_with),
+            difference.days_with,
+            format_rating(difference.average_without),
+            difference.days_without
+        )?;
+        if difference.days_with.min(difference.days_without) < FEW_DAYS {
+            write!(f, " - few days")?;
+        }
+    }
+    Ok(())
+}
+
+/// Compare the average rating of the days on which `has` is true and false.
+///
+/// Days for which `has` returns `None` (eg a metric without a value) are
+/// left out of both.
+fn difference<F>(factor: &str, entries: &[&Entry], has: F) -> Difference
+where
+    F: Fn(&Entry) -> Option<bool>,
+{
+    let days = entries.iter().map(|entry| (entry.get_rating(), has(entry)));
+    difference_of_days(factor, days)
+}
+
+/// Compare the average rating of the days that have a factor and the days
+/// that don't, given the rating of each day and whether it has the factor.
+fn difference_of_days<I>(factor: &str, days: I) -> Difference
+where
+    I: Iterator<Item = (i8, Option<bool>)>,
+{
+    let (mut with, mut without) = (Vec::new(), Vec::new());
+    for (rating, has) in days {
+        match has {
+            Some(true) => with.push(rating),
+            Some(false) => without.push(rating),
+            None => {}
+        }
+    }
+
+    Difference {
+        factor: factor.to_string(),
+        average_with: average(with.iter().copied()),
+        days_with: with.len() as u32,
+        average_without: average(without.iter().copied()),
+        days_without: without.len() as u32,
+    }
+}
+
+fn average<I: Iterator<Item = i8>>(ratings: I) -> Option<f64> {
+    let (sum, count) = ratings.fold((0.0, 0), |(sum, count), rating| {
+        (sum + f64::from(rating), count + 1)
+    });
+    if count == 0 {
+        None
+    } else {
+        Some(sum / f64::from(count))
+    }
+}
+
+/// Return the Pearson correlation coefficient of the pairs.
+///
+/// `None` if there are less than 3 pairs or one side is constant.
+fn pearson(pairs: &[(f64, f64)]) -> Option<f64> {
+    if pairs.len() < 3 {
+        return None;
+    }
+
+    let n = pairs.len() as f64;
+    let mean_x = pairs.iter().map(|(x, _)| x).sum::<f64>() / n;
+    let mean_y = pairs.iter().map(|(_, y)| y).sum::<f64>() / n;
+
+    let (mut covariance, mut variance_x, mut variance_y) = (0.0, 0.0, 0.0);
+    for (x, y) in pairs {
+        covariance += (x - mean_x) * (y - mean_y);
+        variance_x += (x - mean_x).powi(2);
+        variance_y += (y - mean_y).powi(2);
+    }
+
+    if variance_x == 0.0 || variance_y == 0.0 {
+        None
+    } else {
+        Some(covariance / (variance_x * variance_y).sqrt())
+    }
+}
+
+/// Order by value, highest first, with the missing values last.
+fn descending(a: Option<f64>, b: Option<f64>) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => b.partial_cmp(&a).unwrap_or(Ordering::Equal),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+fn format_rating(value: Option<f64>) -> String {
+    match value {
+        Some(value) => format!("{:+.2}", value),
+        None => "-".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::month_log::tests::TempDir;
+
+    #[test]
+    fn tags_are_compared_like_habits() {
+        let _temp = TempDir::create();
+        let mut journal = Journal::new();
+        let days = [
+            (2, "a day off with #Anna", &["walk"][..]),
+            (1, "#anna came over", &["walk"]),
+            (-1, "long day at #work", &[]),
+            (0, "#work, then #Anna", &["walk"]),
+        ];
+        journal
+            .update("June/2022", |log| {
+                for (day, (rating, text, habits)) in (1..).zip(days) {
+                    log.set_entry(day, Entry::new(rating, text).with_habits(habits));
+                }
+            })
+            .unwrap();
+
+        let from = NaiveDate::from_ymd(2022, 6, 1);
+        let to = NaiveDate::from_ymd(2022, 6, 30);
+        let analysis = Analysis::for_range(&mut journal, from, to, &Config::default()).unwrap();
+        assert_eq!(analysis.days, 4);
+
+        let factors: Vec<&str> = analysis
+            .tags
+            .iter()
+            .map(|tag| tag.factor.as_str())
+            .collect();
+        assert_eq!(factors, ["#anna", "#work"]);
+        let anna = &analysis.tags[0];
+        assert_eq!((anna.average_with, anna.days_with), (Some(1.0), 3));
+        assert_eq!((anna.average_without, anna.days_without), (Some(-1.0), 1));
+        assert_eq!(analysis.tags[1].difference(), Some(-2.0));
+
+        assert_eq!(analysis.differences[0].factor, "walk");
+        assert_eq!(analysis.differences[0].difference(), Some(2.0));
+        let text = analysis.to_string();
+        assert!(text.contains("tags (average rating with / without):"));
+        assert!(text.contains("#anna"));
+    }
+}
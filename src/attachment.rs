@@ -0,0 +1,201 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+
+use super::month_log;
+
+/// Return the folder in which attachments of the given day are kept.
+///
+/// `month_year` is in the same format as for `MonthLog` (eg August/2022).
+/// Attachments live next to the month files, in a separate tree:
+///
+/// ```text
+/// ./lifelog/data
+/// ├── 2022
+/// │   └── August.json
+/// └── attachments
+///     └── 2022
+///         └── August
+///             └── 05
+///                 ├── ticket.pdf
+///                 └── sunset.jpg
+/// ```
+pub fn day_dir(month_year: &str, day: u32) -> PathBuf {
+    month_dir(month_year).join(format!("{:02}", day))
+}
+
+/// Return the folder with the attachment folders of every day of a month.
+pub fn month_dir(month_year: &str) -> PathBuf {
+    let (month, year) = month_year.split_once('/').expect("invalid month_year");
+    month_log::data_dir()
+        .join("attachments")
+        .join(year)
+        .join(month)
+}
+
+/// Copy the attachments called `names` of the given day into `target`.
+///
+/// Attachments missing from disk are skipped. Return the names of the files
+/// that were copied.
+pub fn copy_day(
+    month_year: &str,
+    day: u32,
+    names: &[String],
+    target: &Path,
+) -> io::Result<Vec<String>> {
+    let source = day_dir(month_year, day);
+    let mut copied = Vec::new();
+    for name in names {
+        if source.join(name).is_file() {
+            fs::create_dir_all(target)?;
+            fs::copy(source.join(name), target.join(name))?;
+            copied.push(name.clone());
+        }
+    }
+    Ok(copied)
+}
+
+/// Recursively copy the files in `from` that don't exist in `to`.
+pub fn copy_missing(from: &Path, to: &Path) -> io::Result<()> {
+    fs::create_dir_all(to)?;
+
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+
+        if entry.file_type()?.is_dir() {
+            copy_missing(&entry.path(), &target)?;
+        } else if !target.exists() {
+            fs::copy(entry.path(), target)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Check if an attachment is an image that browsers and e-readers can show.
+pub fn is_image(name: &str) -> bool {
+    media_type(name).is_some_and(|media_type| media_type.starts_with("image/"))
+}
+
+/// Return the media type of an attachment from its extension, if it is a
+/// known one.
+pub fn media_type(name: &str) -> Option<&'static str> {
+    let extension = Path::new(name).extension()?.to_str()?.to_lowercase();
+    Some(match extension.as_str() {
+        "jpg" | "jpeg" => "image/jpeg",
+        "png" => "image/png",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "webp" => "image/webp",
+        "pdf" => "application/pdf",
+        "txt" => "text/plain",
+        _ => return None,
+    })
+}
+
+/// Copy the file at `source` into the attachment folder of the given day.
+///
+/// Return the name under which the file was saved. If the day already has an
+/// attachment with the same name, a number is appended to the file stem.
+pub fn add(month_year: &str, day: u32, source: &Path) -> io::Result<String> {
+    if !source.is_file() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("'{}' is not a file", source.display()),
+        ));
+    }
+
+    let dir = day_dir(month_year, day);
+    fs::create_dir_all(&dir)?;
+
+    let stem = source
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or("attachment");
+    let extension = source.extension().and_then(|ext| ext.to_str());
+
+    let mut name = file_name(stem, extension);
+    let mut counter = 1;
+    while dir.join(&name).exists() {
+        name = file_name(&format!("{}-{}", stem, counter), extension);
+        counter += 1;
+    }
+
+    fs::copy(source, dir.join(&name))?;
+    Ok(name)
+}
+
+fn file_name(stem: &str, extension: Option<&str>) -> String {
+    match extension {
+        Some(extension) => format!("{}.{}", stem, extension),
+        None => stem.to_string(),
+    }
+}
+
+/// Delete a single attachment of the given day.
+pub fn remove(month_year: &str, day: u32, name: &str) -> io::Result<()> {
+    let path = day_dir(month_year, day).join(name);
+    if path.exists() {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// Delete the attachment folder of the given day with everything inside it.
+pub fn remove_all(month_year: &str, day: u32) -> io::Result<()> {
+    let dir = day_dir(month_year, day);
+    if dir.exists() {
+        fs::remove_dir_all(dir)?;
+    }
+    Ok(())
+}
+
+/// Open an attachment with the default application of the system.
+pub fn open(month_year: &str, day: u32, name: &str) -> io::Result<()> {
+    let path = day_dir(month_year, day).join(name);
+    if !path.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("'{}' does not exist", path.display()),
+        ));
+    }
+
+    let mut child = opener_command(&path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+
+    // reap the opener in the background so the UI is not blocked by it
+    thread::spawn(move || child.wait());
+    Ok(())
+}
+
+/// Build the command that opens `path` with the system opener.
+fn opener_command(path: &Path) -> Command {
+    let mut command;
+    if cfg!(target_os = "windows") {
+        command = Command::new("cmd");
+        command.args(["/C", "start", ""]);
+    } else if cfg!(target_os = "macos") {
+        command = Command::new("open");
+    } else {
+        command = Command::new("xdg-open");
+    }
+    command.arg(path);
+    command
+}
+
+/// Expand a leading `~` in a path typed in by the user.
+pub fn expand_home(path: &str) -> PathBuf {
+    match path.strip_prefix("~/") {
+        Some(rest) => match etcetera::home_dir() {
+            Ok(home) => home.join(rest),
+            Err(_) => PathBuf::from(path),
+        },
+        None => PathBuf::from(path),
+    }
+}
@@ -0,0 +1,776 @@
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+
+use chrono::{Datelike, NaiveDate};
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use super::config::Config;
+use super::journal::Journal;
+use super::month_log::Entry;
+use super::site::{escape, url_encode, RATING_COLORS};
+use super::statistics::{self, Statistics};
+use super::{attachment, markdown, profile};
+
+/// A book of the entries written over a range of days, with one chapter per
+/// month, to read as an EPUB or print as a PDF.
+///
+/// Every chapter starts with the statistics of its month, followed by the
+/// days that have an entry with their date and rating. Months without any
+/// entry are left out.
+pub struct Book {
+    title: String,
+    from: NaiveDate,
+    to: NaiveDate,
+    config: Config,
+    include_private: bool,
+    chapters: Vec<Chapter>,
+}
+
+struct Chapter {
+    /// First day of the month.
+    month: NaiveDate,
+    /// Statistics of the month, cut to the range of the book.
+    statistics: Statistics,
+    days: Vec<(NaiveDate, Entry)>,
+}
+
+const STYLE: &str = "\
+body { font-family: serif; line-height: 1.5; }
+h1 { text-align: center; }
+table.statistics { margin: 1em auto; border-collapse: collapse; }
+table.statistics td { padding: 0.1em 0.8em; }
+section.day { margin-top: 2em; }
+section.day h2 { font-size: 1.1em; margin-bottom: 0; }
+p.rating { margin-top: 0; font-size: 0.9em; }
+p.note, p.habits, ul.metrics, p.attachments { font-size: 0.9em; color: #555; }
+img { max-width: 100%; }
+.title { text-align: center; margin-top: 30%; }
+";
+
+impl Book {
+    /// Gather the entries of the days in `from..=to`.
+    pub fn new(
+        journal: &mut Journal,
+        config: Config,
+        include_private: bool,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> io::Result<Self> {
+        let mut title = if from.ordinal() == 1 && from.year() == to.year() {
+            format!("{}", from.year())
+        } else {
+            format!("{} - {}", from, to)
+        };
+        if profile::current() != profile::DEFAULT {
+            title = format!("{} {}", profile::current(), title);
+        }
+
+        let mut chapters = Vec::new();
+        for (first, last) in statistics::months(from, to) {
+            let days: Vec<(NaiveDate, Entry)> = journal
+                .days(first, last)?
+                .filter(|(_, entry)| !entry.is_default())
+                .map(|(date, entry)| (date, entry.clone()))
+                .collect();
+            if days.is_empty() {
+                continue;
+            }
+            chapters.push(Chapter {
+                month: first.with_day(1).unwrap(),
+                statistics: Statistics::for_range(journal, first, last)?,
+                days,
+            });
+        }
+
+        Ok(Book {
+            title: format!("lifelog {}", title),
+            from,
+            to,
+            config,
+            include_private,
+            chapters,
+        })
+    }
+
+    /// Return the number of days in the book.
+    pub fn days(&self) -> usize {
+        self.chapters.iter().map(|chapter| chapter.days.len()).sum()
+    }
+
+    /// Write the book as an EPUB 3 file to `path`.
+    pub fn write_epub(&self, path: &Path) -> io::Result<()> {
+        let mut zip = ZipWriter::new(File::create(path)?);
+        let stored = FileOptions::default().compression_method(CompressionMethod::Stored);
+        let deflated = FileOptions::default().compression_method(CompressionMethod::Deflated);
+
+        // the mimetype has to come first, uncompressed, for readers to
+        // recognize the file
+        zip.start_file("mimetype", stored)?;
+        zip.write_all(b"application/epub+zip")?;
+
+        let mut files = vec![
+            (
+                "META-INF/container.xml".to_string(),
+                CONTAINER_XML.to_string(),
+            ),
+            ("OEBPS/content.opf".to_string(), self.package()),
+            ("OEBPS/nav.xhtml".to_string(), self.navigation()),
+            ("OEBPS/style.css".to_string(), self.stylesheet()),
+            ("OEBPS/title.xhtml".to_string(), self.title_page()),
+        ];
+        for chapter in &self.chapters {
+            files.push((
+                format!("OEBPS/{}", chapter_file(chapter)),
+                self.chapter_page(chapter),
+            ));
+        }
+
+        for (name, content) in files {
+            zip.start_file(name, deflated)?;
+            zip.write_all(content.as_bytes())?;
+        }
+        // images are already compressed
+        for (date, name) in self.images() {
+            let source = attachment::day_dir(&date.format("%B/%Y").to_string(), date.day());
+            zip.start_file(format!("OEBPS/{}", image_file(date, &name)), stored)?;
+            zip.write_all(&std::fs::read(source.join(&name))?)?;
+        }
+        zip.finish()?;
+        Ok(())
+    }
+
+    /// Write the book as a PDF file to `path`.
+    ///
+    /// The PDF uses the fonts every reader has (Courier), so it doesn't have
+    /// to embed any, but only Latin characters can be shown: others are
+    /// replaced with `?`. Use the EPUB for entries in other scripts.
+    pub fn write_pdf(&self, path: &Path) -> io::Result<()> {
+        let mut pdf = Pdf::new();
+
+        pdf.skip(PAGE_HEIGHT / 4.0);
+        pdf.text(Font::Bold, 20.0, &self.title);
+        pdf.skip(10.0);
+        pdf.text(
+            Font::Regular,
+            11.0,
+            &format!(
+                "{} - {}",
+                self.from.format("%-d %B %Y"),
+                self.to.format("%-d %B %Y")
+            ),
+        );
+
+        for chapter in &self.chapters {
+            pdf.new_page();
+            let title = chapter.month.format("%B %Y").to_string();
+            pdf.bookmark(&title);
+            pdf.text(Font::Bold, 16.0, &title);
+            pdf.skip(8.0);
+            for (label, value) in self.summary(&chapter.statistics) {
+                pdf.text(Font::Regular, 10.0, &format!("{:<20} {}", label, value));
+            }
+
+            for (date, entry) in &chapter.days {
+                pdf.skip(14.0);
+                pdf.text(Font::Bold, 11.0, &date.format("%A, %-d %B").to_string());
+                pdf.text(
+                    Font::Regular,
+                    9.0,
+                    &self.config.rating_label(entry.get_rating()),
+                );
+                pdf.skip(4.0);
+
+                if !self.is_shown(entry) {
+                    pdf.text(Font::Regular, 10.0, "this entry is private.");
+                    continue;
+                }
+                for line in markdown::render(entry.get_text()).source().lines() {
+                    pdf.text(Font::Regular, 10.0, line);
+                }
+                if !entry.get_habits().is_empty() {
+                    pdf.skip(4.0);
+                    pdf.text(
+                        Font::Regular,
+                        9.0,
+                        &format!("habits done: {}", entry.get_habits().join(", ")),
+                    );
+                }
+                for (name, value) in entry.get_metrics() {
+                    pdf.text(Font::Regular, 9.0, &format!("{}: {}", name, value));
+                }
+                if !entry.get_attachments().is_empty() {
+                    pdf.text(
+                        Font::Regular,
+                        9.0,
+                        &format!("attached: {}", entry.get_attachments().join(", ")),
+                    );
+                }
+            }
+        }
+
+        std::fs::write(path, pdf.finish(&self.title))
+    }
+
+    /// Check if the text (and attachments) of `entry` are shown in the book.
+    fn is_shown(&self, entry: &Entry) -> bool {
+        self.include_private || !entry.is_private()
+    }
+
+    /// Return the image attachments shown in the EPUB, with their days.
+    ///
+    /// Images missing from disk are only listed by name, like other files.
+    fn images(&self) -> Vec<(NaiveDate, String)> {
+        let mut images = Vec::new();
+        for (date, entry) in self.chapters.iter().flat_map(|chapter| &chapter.days) {
+            if !self.is_shown(entry) {
+                continue;
+            }
+            let dir = attachment::day_dir(&date.format("%B/%Y").to_string(), date.day());
+            for name in entry.get_attachments() {
+                if attachment::is_image(name) && dir.join(name).is_file() {
+                    images.push((*date, name.clone()));
+                }
+            }
+        }
+        images
+    }
+
+    /// Return the lines of the statistics summary at the start of a chapter.
+    fn summary(&self, statistics: &Statistics) -> Vec<(String, String)> {
+        let mut summary: Vec<(String, String)> = (-2..=2)
+            .rev()
+            .zip([
+                statistics.awesome,
+                statistics.good,
+                statistics.okay,
+                statistics.bad,
+                statistics.horrible,
+            ])
+            .map(|(rating, count)| (self.config.rating_label(rating), count.to_string()))
+            .collect();
+        summary.push(("no entry".to_string(), statistics.missed.to_string()));
+        if let Some(average) = statistics.average {
+            summary.push(("average rating".to_string(), format!("{:+.2}", average)));
+        }
+        summary
+    }
+
+    fn package(&self) -> String {
+        let mut manifest = String::new();
+        let mut spine = String::new();
+        for chapter in &self.chapters {
+            let file = chapter_file(chapter);
+            let id = file.trim_end_matches(".xhtml");
+            writeln!(
+                manifest,
+                "    <item id=\"m{}\" href=\"{}\" media-type=\"application/xhtml+xml\"/>",
+                id, file
+            )
+            .unwrap();
+            writeln!(spine, "    <itemref idref=\"m{}\"/>", id).unwrap();
+        }
+        for (i, (date, name)) in self.images().iter().enumerate() {
+            writeln!(
+                manifest,
+                "    <item id=\"i{}\" href=\"{}\" media-type=\"{}\"/>",
+                i,
+                image_href(*date, name),
+                attachment::media_type(name).unwrap()
+            )
+            .unwrap();
+        }
+
+        format!(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+            <package xmlns=\"http://www.idpf.org/2007/opf\" version=\"3.0\" unique-identifier=\"id\">\n\
+            \x20 <metadata xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n\
+            \x20   <dc:identifier id=\"id\">lifelog-{journal}-{from}-{to}</dc:identifier>\n\
+            \x20   <dc:title>{title}</dc:title>\n\
+            \x20   <dc:language>en</dc:language>\n\
+            \x20   <meta property=\"dcterms:modified\">{modified}</meta>\n\
+            \x20 </metadata>\n\
+            \x20 <manifest>\n\
+            \x20   <item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\"/>\n\
+            \x20   <item id=\"style\" href=\"style.css\" media-type=\"text/css\"/>\n\
+            \x20   <item id=\"title\" href=\"title.xhtml\" media-type=\"application/xhtml+xml\"/>\n\
+            {manifest}\
+            \x20 </manifest>\n\
+            \x20 <spine>\n\
+            \x20   <itemref idref=\"title\"/>\n\
+            \x20   <itemref idref=\"nav\"/>\n\
+            {spine}\
+            \x20 </spine>\n\
+            </package>\n",
+            journal = profile::current(),
+            from = self.from,
+            to = self.to,
+            title = escape(&self.title),
+            modified = chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ"),
+            manifest = manifest,
+            spine = spine
+        )
+    }
+
+    fn navigation(&self) -> String {
+        let mut body = String::from("<nav epub:type=\"toc\">\n<h1>contents</h1>\n<ol>\n");
+        for chapter in &self.chapters {
+            writeln!(
+                body,
+                "<li><a href=\"{}\">{}</a></li>",
+                chapter_file(chapter),
+                chapter.month.format("%B %Y")
+            )
+            .unwrap();
+        }
+        body.push_str("</ol>\n</nav>\n");
+        xhtml_page("contents", &body)
+    }
+
+    fn title_page(&self) -> String {
+        let body = format!(
+            "<div class=\"title\">\n<h1>{}</h1>\n<p>{} - {}</p>\n</div>\n",
+            escape(&self.title),
+            self.from.format("%-d %B %Y"),
+            self.to.format("%-d %B %Y")
+        );
+        xhtml_page(&self.title, &body)
+    }
+
+    fn chapter_page(&self, chapter: &Chapter) -> String {
+        let title = chapter.month.format("%B %Y").to_string();
+        let mut body = format!("<h1>{}</h1>\n<table class=\"statistics\">\n", title);
+        for (label, value) in self.summary(&chapter.statistics) {
+            writeln!(
+                body,
+                "<tr><td>{}</td><td>{}</td></tr>",
+                escape(&label),
+                value
+            )
+            .unwrap();
+        }
+        body.push_str("</table>\n");
+
+        let images = self.images();
+        for (date, entry) in &chapter.days {
+            let rating = entry.get_rating();
+            write!(
+                body,
+                "<section class=\"day\">\n<h2>{}</h2>\n<p class=\"rating rating-{}\">{}</p>\n",
+                date.format("%A, %-d %B"),
+                rating + 2,
+                escape(&self.config.rating_label(rating))
+            )
+            .unwrap();
+
+            if !self.is_shown(entry) {
+                body.push_str("<p><em>this entry is private.</em></p>\n</section>\n");
+                continue;
+            }
+            if let Some(written) = entry.written_note() {
+                writeln!(body, "<p class=\"note\">{}</p>", escape(&written)).unwrap();
+            }
+            body.push_str(&markdown::to_xhtml(entry.get_text()));
+            if !entry.get_habits().is_empty() {
+                writeln!(
+                    body,
+                    "<p class=\"habits\">habits done: {}</p>",
+                    escape(&entry.get_habits().join(", "))
+                )
+                .unwrap();
+            }
+            if !entry.get_metrics().is_empty() {
+                body.push_str("<ul class=\"metrics\">\n");
+                for (name, value) in entry.get_metrics() {
+                    writeln!(
+                        body,
+                        "<li>{}: {}</li>",
+                        escape(name),
+                        escape(&value.to_string())
+                    )
+                    .unwrap();
+                }
+                body.push_str("</ul>\n");
+            }
+
+            let mut others = Vec::new();
+            for name in entry.get_attachments() {
+                if images.contains(&(*date, name.clone())) {
+                    writeln!(
+                        body,
+                        "<p><img src=\"{}\" alt=\"{}\"/></p>",
+                        image_href(*date, name),
+                        escape(name)
+                    )
+                    .unwrap();
+                } else {
+                    others.push(name.as_str());
+                }
+            }
+            if !others.is_empty() {
+                writeln!(
+                    body,
+                    "<p class=\"attachments\">attached: {}</p>",
+                    escape(&others.join(", "))
+                )
+                .unwrap();
+            }
+            body.push_str("</section>\n");
+        }
+
+        xhtml_page(&title, &body)
+    }
+
+    /// Return the style of the book, with the ratings in the colors of the
+    /// static site.
+    fn stylesheet(&self) -> String {
+        let mut css = STYLE.to_string();
+        for (i, color) in RATING_COLORS.iter().enumerate() {
+            writeln!(css, ".rating-{} {{ color: {}; }}", i, color).unwrap();
+        }
+        css
+    }
+}
+
+const CONTAINER_XML: &str = "\
+<?xml version=\"1.0\" encoding=\"utf-8\"?>
+<container version=\"1.0\" xmlns=\"urn:oasis:names:tc:opendocument:xmlns:container\">
+  <rootfiles>
+    <rootfile full-path=\"OEBPS/content.opf\" media-type=\"application/oebps-package+xml\"/>
+  </rootfiles>
+</container>
+";
+
+fn chapter_file(chapter: &Chapter) -> String {
+    chapter.month.format("%Y-%m.xhtml").to_string()
+}
+
+/// Return the path of an image attachment inside the EPUB, relative to the
+/// pages.
+fn image_file(date: NaiveDate, name: &str) -> String {
+    format!("attachments/{}/{}", date, name)
+}
+
+/// Return the link to an image attachment from the pages.
+fn image_href(date: NaiveDate, name: &str) -> String {
+    format!("attachments/{}/{}", date, url_encode(name))
+}
+
+fn xhtml_page(title: &str, body: &str) -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+        <!DOCTYPE html>\n\
+        <html xmlns=\"http://www.w3.org/1999/xhtml\" xmlns:epub=\"http://www.idpf.org/2007/ops\">\n\
+        <head>\n\
+        <meta charset=\"utf-8\"/>\n\
+        <title>{}</title>\n\
+        <link rel=\"stylesheet\" type=\"text/css\" href=\"style.css\"/>\n\
+        </head>\n\
+        <body>\n{}</body>\n\
+        </html>\n",
+        escape(title),
+        body
+    )
+}
+
+// ============================================================================
+// ------------------------------------ PDF -----------------------------------
+// ============================================================================
+
+/// Size of an A5 page, in points.
+const PAGE_WIDTH: f32 = 420.0;
+const PAGE_HEIGHT: f32 = 595.0;
+const MARGIN: f32 = 45.0;
+
+#[derive(Clone, Copy)]
+enum Font {
+    Regular,
+    Bold,
+}
+
+impl Font {
+    fn resource(self) -> &'static str {
+        match self {
+            Font::Regular => "/F1",
+            Font::Bold => "/F2",
+        }
+    }
+}
+
+/// A minimal PDF writer: lines of text that flow from page to page.
+struct Pdf {
+    /// Content streams of the pages.
+    pages: Vec<Vec<u8>>,
+    /// Position of the next line on the current page.
+    y: f32,
+    /// Title, page index and position of the chapters, for the outline.
+    bookmarks: Vec<(String, usize, f32)>,
+}
+
+impl Pdf {
+    fn new() -> Self {
+        Pdf {
+            pages: vec![Vec::new()],
+            y: PAGE_HEIGHT - MARGIN,
+            bookmarks: Vec::new(),
+        }
+    }
+
+    fn new_page(&mut self) {
+        self.pages.push(Vec::new());
+        self.y = PAGE_HEIGHT - MARGIN;
+    }
+
+    /// Leave `height` points of blank space.
+    fn skip(&mut self, height: f32) {
+        self.y -= height;
+    }
+
+    /// Add a bookmark to the current position in the outline of the document.
+    fn bookmark(&mut self, title: &str) {
+        self.bookmarks
+            .push((title.to_string(), self.pages.len() - 1, self.y));
+    }
+
+    /// Write `text` in `font`, wrapped to the width of the page.
+    fn text(&mut self, font: Font, size: f32, text: &str) {
+        // Courier glyphs are all 0.6 em wide
+        let columns = ((PAGE_WIDTH - 2.0 * MARGIN) / (size * 0.6)) as usize;
+        for line in wrap(text, columns) {
+            if self.y - size * 1.3 < MARGIN {
+                self.new_page();
+            }
+            self.y -= size * 1.3;
+            let page = self.pages.last_mut().unwrap();
+            write!(
+                page,
+                "BT {} {} Tf {} {:.1} Td ",
+                font.resource(),
+                size,
+                MARGIN,
+                self.y
+            )
+            .unwrap();
+            page.extend(pdf_string(&line));
+            page.extend(b" Tj ET\n");
+        }
+    }
+
+    /// Return the bytes of the document, with the page numbers added.
+    fn finish(mut self, title: &str) -> Vec<u8> {
+        let count = self.pages.len();
+        for (i, page) in self.pages.iter_mut().enumerate().skip(1) {
+            let number = (i + 1).to_string();
+            let x = (PAGE_WIDTH - number.len() as f32 * 5.4) / 2.0;
+            writeln!(
+                page,
+                "BT /F1 9 Tf {} {} Td ({}) Tj ET",
+                x,
+                MARGIN / 2.0,
+                number
+            )
+            .unwrap();
+        }
+
+        // objects: catalog, page tree, two fonts, outline, outline items,
+        // every page followed by its content, then the document information
+        let outline = 5;
+        let first_page = outline + 1 + self.bookmarks.len();
+        let page_id = |i: usize| first_page + 2 * i;
+
+        let mut objects: Vec<Vec<u8>> =
+            vec![
+            format!("<< /Type /Catalog /Pages 2 0 R /Outlines {} 0 R >>", outline).into_bytes(),
+            format!(
+                "<< /Type /Pages /Count {} /Kids [{}] >>",
+                count,
+                (0..count)
+                    .map(|i| format!("{} 0 R", page_id(i)))
+                    .collect::<Vec<String>>()
+                    .join(" ")
+            )
+            .into_bytes(),
+            b"<< /Type /Font /Subtype /Type1 /BaseFont /Courier /Encoding /WinAnsiEncoding >>"
+                .to_vec(),
+            b"<< /Type /Font /Subtype /Type1 /BaseFont /Courier-Bold /Encoding /WinAnsiEncoding >>"
+                .to_vec(),
+        ];
+
+        let bookmarks = self.bookmarks.len();
+        objects.push(if bookmarks == 0 {
+            b"<< /Type /Outlines /Count 0 >>".to_vec()
+        } else {
+            format!(
+                "<< /Type /Outlines /First {} 0 R /Last {} 0 R /Count {} >>",
+                outline + 1,
+                outline + bookmarks,
+                bookmarks
+            )
+            .into_bytes()
+        });
+        for (i, (title, page, y)) in self.bookmarks.iter().enumerate() {
+            let mut item = b"<< /Title ".to_vec();
+            item.extend(pdf_string(title));
+            write!(
+                item,
+                " /Parent {} 0 R /Dest [{} 0 R /XYZ 0 {:.1} null]",
+                outline,
+                page_id(*page),
+                y
+            )
+            .unwrap();
+            if i > 0 {
+                write!(item, " /Prev {} 0 R", outline + i).unwrap();
+            }
+            if i + 1 < bookmarks {
+                write!(item, " /Next {} 0 R", outline + i + 2).unwrap();
+            }
+            item.extend(b" >>");
+            objects.push(item);
+        }
+
+        for (i, content) in self.pages.iter().enumerate() {
+            objects.push(
+                format!(
+                    "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] \
+                    /Resources << /Font << /F1 3 0 R /F2 4 0 R >> >> /Contents {} 0 R >>",
+                    PAGE_WIDTH,
+                    PAGE_HEIGHT,
+                    page_id(i) + 1
+                )
+                .into_bytes(),
+            );
+            let mut stream = format!("<< /Length {} >>\nstream\n", content.len()).into_bytes();
+            stream.extend(content);
+            stream.extend(b"endstream");
+            objects.push(stream);
+        }
+
+        let mut info = b"<< /Title ".to_vec();
+        info.extend(pdf_string(title));
+        info.extend(b" /Producer (lifelog) >>");
+        objects.push(info);
+
+        let mut document = b"%PDF-1.4\n".to_vec();
+        let mut offsets = Vec::new();
+        for (i, object) in objects.iter().enumerate() {
+            offsets.push(document.len());
+            writeln!(document, "{} 0 obj", i + 1).unwrap();
+            document.extend(object);
+            document.extend(b"\nendobj\n");
+        }
+
+        let xref = document.len();
+        write!(
+            document,
+            "xref\n0 {}\n0000000000 65535 f \n",
+            objects.len() + 1
+        )
+        .unwrap();
+        for offset in offsets {
+            writeln!(document, "{:010} 00000 n ", offset).unwrap();
+        }
+        write!(
+            document,
+            "trailer\n<< /Size {} /Root 1 0 R /Info {} 0 R >>\nstartxref\n{}\n%%EOF\n",
+            objects.len() + 1,
+            objects.len(),
+            xref
+        )
+        .unwrap();
+        document
+    }
+}
+
+/// Split `text` into lines of at most `columns` characters, between words
+/// when possible.
+fn wrap(text: &str, columns: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split(' ') {
+        let mut word = word.to_string();
+        loop {
+            let length = line.chars().count();
+            let needed = word.chars().count() + usize::from(length > 0);
+            if length + needed <= columns {
+                if length > 0 {
+                    line.push(' ');
+                }
+                line.push_str(&word);
+                break;
+            }
+            if length > 0 {
+                lines.push(std::mem::take(&mut line));
+                continue;
+            }
+            // a word longer than a line is cut
+            let rest = word.chars().skip(columns).collect();
+            lines.push(word.chars().take(columns).collect());
+            word = rest;
+        }
+    }
+    lines.push(line);
+    lines
+}
+
+/// Return `text` as a PDF string in the Windows-1252 encoding of the fonts,
+/// with the characters it doesn't have replaced with `?`.
+fn pdf_string(text: &str) -> Vec<u8> {
+    let mut string = vec![b'('];
+    for c in text.chars() {
+        let byte = match c {
+            '(' | ')' | '\\' => {
+                string.push(b'\\');
+                c as u8
+            }
+            ' '..='~' | '\u{a0}'..='\u{ff}' => c as u8,
+            '€' => 0x80,
+            '…' => 0x85,
+            '‘' => 0x91,
+            '’' => 0x92,
+            '“' => 0x93,
+            '”' => 0x94,
+            '•' => 0x95,
+            '–' => 0x96,
+            '—' => 0x97,
+            _ => b'?',
+        };
+        string.push(byte);
+    }
+    string.push(b')');
+    string
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wrap_breaks_between_words() {
+        assert_eq!(wrap("the quick brown fox", 9), ["the quick", "brown fox"]);
+        assert_eq!(wrap("the quick brown fox", 10), ["the quick", "brown fox"]);
+        assert_eq!(wrap("", 10), [""]);
+    }
+
+    #[test]
+    fn wrap_cuts_long_words() {
+        assert_eq!(wrap("a abcdefghij b", 4), ["a", "abcd", "efgh", "ij b"]);
+        assert_eq!(wrap("été à la mer", 5), ["été à", "la", "mer"]);
+    }
+
+    #[test]
+    fn pdf_string_escapes_delimiters() {
+        assert_eq!(pdf_string("a (b) \\"), b"(a \\(b\\) \\\\)");
+    }
+
+    #[test]
+    fn pdf_string_uses_windows_1252() {
+        assert_eq!(
+            pdf_string("café €5 – “ok”…"),
+            b"(caf\xe9 \x805 \x96 \x93ok\x94\x85)"
+        );
+        assert_eq!(pdf_string("日本 🙂"), b"(?? ?)");
+    }
+}
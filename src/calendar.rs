@@ -1,7 +1,7 @@
 use std::fs;
 use std::path::PathBuf;
 
-use chrono::{Date, Datelike, Local, TimeZone};
+use chrono::{Date, Datelike, Local, NaiveDate, TimeZone};
 use cursive::reexports::time::{util::days_in_year_month, Month as tMonth};
 
 use super::month_log;
@@ -38,47 +38,76 @@ pub fn earliest_latest() -> (Date<Local>, Date<Local>) {
 
     // construct earliest date
     let data_dir = month_log::data_dir();
-    let iter = fs::read_dir(&data_dir).expect("failed to read data directory");
+    let years = years();
+
+    // years without month files (eg emptied by hand) are skipped
+    let earliest_date = years
+        .into_iter()
+        .find_map(|year| {
+            let month = *get_month_numbers(data_dir.join(year.to_string())).first()?;
+            Some(Local.ymd(year, month.into(), 1))
+        })
+        .unwrap_or_else(|| Local.ymd(current_year, current_month.into(), 1));
+
+    (earliest_date, latest_date)
+}
+
+/// Return the range from `from` to `to`, from the first month of the journal
+/// to today by default.
+///
+/// Used by the commands that work on a range of days. Whether the range is
+/// empty is left to them, so they can report it in their own words.
+pub fn date_range(from: Option<NaiveDate>, to: Option<NaiveDate>) -> (NaiveDate, NaiveDate) {
+    (
+        from.unwrap_or_else(|| earliest_latest().0.naive_local()),
+        to.unwrap_or_else(|| Local::today().naive_local()),
+    )
+}
+
+/// Parse a date written as `YYYY-MM-DD`, return a description of the problem
+/// if it's invalid.
+pub fn parse_date(date: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|_| format!("invalid date '{}', expected YYYY-MM-DD", date))
+}
+
+/// Get a sorted vector of the years that have a directory in the data dir.
+///
+/// Anything that is not a year (eg the `attachments` folder) is skipped.
+pub fn years() -> Vec<i32> {
+    let iter = fs::read_dir(month_log::data_dir()).expect("failed to read data directory");
 
     let mut years: Vec<i32> = iter
-        .map(|entry| {
+        .filter_map(|entry| {
             entry
                 .expect("failed to get a directory entry")
                 .file_name()
                 .to_str()
                 .expect("failed to convert OsStr to &str, invalid Unicode")
                 .parse()
-                .expect("failed to parse a year &str to i32")
+                .ok()
         })
         .collect();
     years.sort();
-
-    let earliest_date = match years.first() {
-        Some(earliest_year) => {
-            let earliest_month = *get_month_numbers(data_dir.join(earliest_year.to_string()))
-                .first()
-                .expect("earliest year directory is empty");
-            Local.ymd(*earliest_year, earliest_month.into(), 1)
-        }
-        None => Local.ymd(current_year, current_month.into(), 1),
-    };
-
-    (earliest_date, latest_date)
+    years
 }
 
 /// Get a vector of sorted numbers (representing months in the specified path).
 ///
 /// First read contents of path, remove the `.json` filename extension, convert
-/// month to number and finally sort and return.
+/// month to number and finally sort and return. Files that are not named after
+/// a month (eg conflict copies made by sync tools) are skipped, and so is a
+/// path that is not a directory.
 fn get_month_numbers(path: PathBuf) -> Vec<u8> {
-    let iter = fs::read_dir(path).expect("failed to read directory");
+    let iter = match fs::read_dir(path) {
+        Ok(iter) => iter,
+        Err(_) => return Vec::new(),
+    };
 
     let mut months: Vec<u8> = iter
-        .map(|entry| {
+        .filter_map(|entry| {
             let month = entry.expect("failed to get a directory entry").file_name();
-
-            // month at this point also has the file extension ('.json')
-            let month = &month.to_str().unwrap()[..(month.len() - 5)];
+            let month = month.to_str().unwrap().strip_suffix(".json")?;
             month_number(month)
         })
         .collect();
@@ -88,8 +117,10 @@ fn get_month_numbers(path: PathBuf) -> Vec<u8> {
 }
 
 /// Given a month name, return the month number.
-fn month_number(month: &str) -> u8 {
-    match month {
+///
+/// Return `None` if `month` is not the name of a month.
+fn month_number(month: &str) -> Option<u8> {
+    let number = match month {
         "January" => 1,
         "February" => 2,
         "March" => 3,
@@ -102,6 +133,7 @@ fn month_number(month: &str) -> u8 {
         "October" => 10,
         "November" => 11,
         "December" => 12,
-        _ => panic!("unexpcted input: {}", month),
-    }
+        _ => return None,
+    };
+    Some(number)
 }
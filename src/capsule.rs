@@ -0,0 +1,144 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chrono::{DateTime, FixedOffset, Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+
+use super::profile;
+
+/// A letter to the future: an entry that can't be read before a given day.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Capsule {
+    /// Local time at which the capsule was written.
+    written: DateTime<FixedOffset>,
+    /// First day on which the capsule can be read.
+    opens: NaiveDate,
+    /// Text of the capsule, base64 encoded so it isn't read by accident when
+    /// looking at the file. This is not encryption.
+    sealed_text: String,
+    /// Whether the capsule was read since it opened.
+    #[serde(default)]
+    read: bool,
+}
+
+impl Capsule {
+    /// Seal `text` until `opens`.
+    pub fn new(opens: NaiveDate, text: &str) -> Self {
+        let now = Local::now();
+        Capsule {
+            written: now.with_timezone(now.offset()),
+            opens,
+            sealed_text: STANDARD.encode(text),
+            read: false,
+        }
+    }
+
+    pub fn written(&self) -> DateTime<FixedOffset> {
+        self.written
+    }
+
+    pub fn opens(&self) -> NaiveDate {
+        self.opens
+    }
+
+    /// Check if the capsule can be read on `today`.
+    pub fn is_open(&self, today: NaiveDate) -> bool {
+        today >= self.opens
+    }
+
+    /// Return the text of the capsule, `None` if it's still sealed on `today`.
+    ///
+    /// A text that isn't valid base64 (eg edited by hand) is an error.
+    pub fn text(&self, today: NaiveDate) -> io::Result<Option<String>> {
+        if !self.is_open(today) {
+            return Ok(None);
+        }
+        let bytes = STANDARD.decode(&self.sealed_text).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid time capsule text: {}", e),
+            )
+        })?;
+        Ok(Some(String::from_utf8_lossy(&bytes).into_owned()))
+    }
+}
+
+/// Return all the time capsules of the journal in use, the first to open
+/// first.
+///
+/// A file that can't be read is an error, so it's never overwritten by
+/// `add` or `mark_read`.
+pub fn load() -> io::Result<Vec<Capsule>> {
+    let path = path();
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let data = fs::read_to_string(&path)?;
+    let mut capsules: Vec<Capsule> = serde_json::from_str(&data).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid {}: {}", path.display(), e),
+        )
+    })?;
+    capsules.sort_by_key(|capsule| (capsule.opens, capsule.written));
+    Ok(capsules)
+}
+
+/// Add a new time capsule to the journal in use.
+///
+/// Return all the capsules, with the new one.
+pub fn add(capsule: Capsule) -> io::Result<Vec<Capsule>> {
+    let mut capsules = load()?;
+    capsules.push(capsule);
+    capsules.sort_by_key(|capsule| (capsule.opens, capsule.written));
+    save(&capsules)?;
+    Ok(capsules)
+}
+
+/// Mark the capsules that are open on `today` as read.
+///
+/// Return all the capsules, as they were saved.
+pub fn mark_read(today: NaiveDate) -> io::Result<Vec<Capsule>> {
+    let mut capsules = load()?;
+    for capsule in capsules.iter_mut() {
+        if capsule.is_open(today) {
+            capsule.read = true;
+        }
+    }
+    save(&capsules)?;
+    Ok(capsules)
+}
+
+/// Return the capsules of `capsules` that are open on `today` but were not
+/// read yet.
+pub fn unread(capsules: &[Capsule], today: NaiveDate) -> Vec<Capsule> {
+    capsules
+        .iter()
+        .filter(|capsule| capsule.is_open(today) && !capsule.read)
+        .cloned()
+        .collect()
+}
+
+/// Return the capsules of `capsules` that open on `date`.
+pub fn opening_on(capsules: &[Capsule], date: NaiveDate) -> Vec<Capsule> {
+    capsules
+        .iter()
+        .filter(|capsule| capsule.opens == date)
+        .cloned()
+        .collect()
+}
+
+fn save(capsules: &[Capsule]) -> io::Result<()> {
+    fs::create_dir_all(profile::dir())?;
+    fs::write(path(), serde_json::to_string(capsules)?)
+}
+
+/// Return the location of the file with the time capsules, next to the data
+/// directory of the journal in use.
+fn path() -> PathBuf {
+    profile::dir().join("capsules.json")
+}
@@ -0,0 +1,202 @@
+use super::config::{Metric, MetricKind};
+use super::month_log::{MetricValue, MonthLog};
+use super::sentiment;
+
+/// Bars of increasing height, used to draw one value per day.
+const LEVELS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Return a chart of the rating and the custom `metrics` of every day of the
+/// month, one column per day.
+///
+/// ```text
+///          1   5    10   15   20   25   30
+/// rating   ▅█ ▁▃▅▅█▃          ▁ -2 .. █ +2
+/// text     ▅▅ ▃▃▅▅█▅          rating suggested by the text
+/// sleep    ▃▅ ▄▅▂▄▆▇          ▁ 5 .. █ 9 hours
+/// workout  █· ·█··█           █ yes · no
+/// ```
+///
+/// Days without a value are left blank.
+pub fn month_chart(log: &MonthLog, metrics: &[Metric]) -> String {
+    let width = metrics
+        .iter()
+        .map(|metric| metric.name.chars().count())
+        .max()
+        .unwrap_or(0)
+        .max("rating".len())
+        + 2;
+
+    let mut chart = format!("{:width$}{}\n", "", day_numbers(log.days()));
+
+    let ratings: String = log
+        .entries()
+        .iter()
+        .map(|entry| match entry.get_rating() {
+            rating @ -2..=2 => level(f64::from(rating + 2) / 4.0),
+            _ => ' ',
+        })
+        .collect();
+    chart.push_str(&format!(
+        "{:width$}{}  {} -2 .. {} +2\n",
+        "rating", ratings, LEVELS[0], LEVELS[7]
+    ));
+
+    let suggestions: String = log
+        .entries()
+        .iter()
+        .map(|entry| match sentiment::suggest_rating(entry.get_text()) {
+            Some(rating) if !entry.is_default() => level(f64::from(rating + 2) / 4.0),
+            _ => ' ',
+        })
+        .collect();
+    chart.push_str(&format!(
+        "{:width$}{}  rating suggested by the text\n",
+        "text", suggestions
+    ));
+
+    for metric in metrics {
+        let values: Vec<Option<&MetricValue>> = log
+            .entries()
+            .iter()
+            .map(|entry| entry.get_metrics().get(&metric.name))
+            .collect();
+        let (row, legend) = metric_row(&metric.kind, &values);
+        chart.push_str(&format!("{:width$}{}  {}\n", metric.name, row, legend));
+    }
+
+    chart
+}
+
+/// Return a grid of the `habits` done on every day of the month, one column
+/// per day.
+///
+/// ```text
+///          1   5    10   15   20   25   30
+/// meditate ██·███ ·██
+/// read     ·█··██ ··█
+/// ```
+///
+/// A habit that was done is a full block, days with an entry on which it
+/// was not done are a dot and days without an entry are left blank.
+pub fn habit_grid(log: &MonthLog, habits: &[String]) -> String {
+    let width = habits
+        .iter()
+        .map(|habit| habit.chars().count())
+        .max()
+        .unwrap_or(0)
+        + 2;
+
+    let mut grid = format!("{:width$}{}", "", day_numbers(log.days()));
+    for habit in habits {
+        let row: String = log
+            .entries()
+            .iter()
+            .map(|entry| {
+                if entry.has_done(habit) {
+                    '█'
+                } else if entry.is_default() {
+                    ' '
+                } else {
+                    '·'
+                }
+            })
+            .collect();
+        grid.push_str(&format!("\n{:width$}{}", habit, row.trim_end()));
+    }
+
+    grid
+}
+
+/// Return the row and the legend of a metric.
+fn metric_row(kind: &MetricKind, values: &[Option<&MetricValue>]) -> (String, String) {
+    match kind {
+        MetricKind::Number { unit, min, max } => {
+            let numbers = values.iter().filter_map(|value| match value {
+                Some(MetricValue::Number(number)) => Some(*number),
+                _ => None,
+            });
+            let min = min.unwrap_or_else(|| numbers.clone().fold(f64::INFINITY, f64::min));
+            let max = max.unwrap_or_else(|| numbers.fold(f64::NEG_INFINITY, f64::max));
+
+            let row = values
+                .iter()
+                .map(|value| match value {
+                    Some(MetricValue::Number(_)) if max <= min => LEVELS[7],
+                    Some(MetricValue::Number(number)) => level((number - min) / (max - min)),
+                    _ => ' ',
+                })
+                .collect();
+            let legend = if min.is_finite() {
+                format!(
+                    "{} {} .. {} {} {}",
+                    LEVELS[0],
+                    min,
+                    LEVELS[7],
+                    max,
+                    unit.as_deref().unwrap_or("")
+                )
+            } else {
+                String::new()
+            };
+            (row, legend.trim_end().to_string())
+        }
+        MetricKind::Boolean => {
+            let row = values
+                .iter()
+                .map(|value| match value {
+                    Some(MetricValue::Boolean(true)) => '█',
+                    Some(MetricValue::Boolean(false)) => '·',
+                    _ => ' ',
+                })
+                .collect();
+            (row, "█ yes · no".to_string())
+        }
+        MetricKind::Choice { options } => {
+            let choice_level = |i: usize| {
+                if options.len() > 1 {
+                    level(i as f64 / (options.len() - 1) as f64)
+                } else {
+                    LEVELS[7]
+                }
+            };
+
+            let row = values
+                .iter()
+                .map(|value| match value {
+                    Some(MetricValue::Choice(choice)) => {
+                        match options.iter().position(|option| option == choice) {
+                            Some(i) => choice_level(i),
+                            None => '?',
+                        }
+                    }
+                    _ => ' ',
+                })
+                .collect();
+            let legend = options
+                .iter()
+                .enumerate()
+                .map(|(i, option)| format!("{} {}", choice_level(i), option))
+                .collect::<Vec<String>>()
+                .join(" ");
+            (row, legend)
+        }
+    }
+}
+
+/// Return the bar for `fraction` (between 0 and 1) of the highest value.
+fn level(fraction: f64) -> char {
+    let index = (fraction * 7.0).round().clamp(0.0, 7.0) as usize;
+    LEVELS[index]
+}
+
+/// Return a line with the numbers of the first day and every fifth day, each
+/// above the column of its day.
+fn day_numbers(days: u32) -> String {
+    let mut line = vec![' '; days as usize + 1];
+    for day in (0..=days).step_by(5).map(|day| day.max(1)) {
+        for (i, digit) in day.to_string().chars().enumerate() {
+            line[(day - 1) as usize + i] = digit;
+        }
+    }
+    line.into_iter().collect::<String>().trim_end().to_string()
+}
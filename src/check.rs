@@ -0,0 +1,393 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use chrono::Local;
+use cursive::reexports::time::{util::days_in_year_month, Month as tMonth};
+
+use super::month_log::{self, MonthLog};
+use super::{attachment, merge};
+
+/// A problem with a file in the data directory.
+pub struct Problem {
+    /// Path of the file (or directory) with the problem.
+    pub path: PathBuf,
+    pub kind: ProblemKind,
+}
+
+/// The different kinds of problems `check` can find.
+pub enum ProblemKind {
+    /// The file is not valid JSON or does not have the structure of a `MonthLog`.
+    InvalidJson(String),
+    /// The number of entries does not match the number of days in the month.
+    WrongEntryCount { expected: u8, found: usize },
+    /// The month and year inside the file do not match its path.
+    MismatchedName { month_year: String },
+    /// A day has a rating outside of the `-2..=2` scale.
+    InvalidRating { day: usize, rating: i8 },
+    /// A conflict copy made by a sync tool, see `merge`.
+    ConflictCopy,
+    /// A month file that could not be read, renamed by `repair`.
+    SetAside,
+    /// A file or directory that lifelog does not know about.
+    UnexpectedFile,
+}
+
+impl Problem {
+    /// Check if `repair` knows how to fix the problem.
+    pub fn is_repairable(&self) -> bool {
+        !matches!(
+            self.kind,
+            ProblemKind::ConflictCopy | ProblemKind::SetAside | ProblemKind::UnexpectedFile
+        )
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = self
+            .path
+            .strip_prefix(month_log::data_dir())
+            .unwrap_or(&self.path)
+            .display();
+
+        match &self.kind {
+            ProblemKind::InvalidJson(e) => write!(f, "{}: invalid month file ({})", path, e),
+            ProblemKind::WrongEntryCount { expected, found } => write!(
+                f,
+                "{}: has {} entries, but the month has {} days",
+                path, found, expected
+            ),
+            ProblemKind::MismatchedName { month_year } => {
+                write!(f, "{}: contains the entries of {}", path, month_year)
+            }
+            ProblemKind::InvalidRating { day, rating } => {
+                write!(
+                    f,
+                    "{}: day {} has an invalid rating ({})",
+                    path, day, rating
+                )
+            }
+            ProblemKind::ConflictCopy => write!(
+                f,
+                "{}: conflict copy made by a sync tool (run `lifelog merge`)",
+                path
+            ),
+            ProblemKind::SetAside => write!(
+                f,
+                "{}: unreadable month file set aside by a repair (fix it and rename it to {})",
+                path,
+                self.path.file_stem().unwrap().to_string_lossy()
+            ),
+            ProblemKind::UnexpectedFile => write!(f, "{}: unexpected file", path),
+        }
+    }
+}
+
+/// Validate every file in the data directory and return the problems found.
+///
+/// The data directory should only contain year directories (and the
+/// `attachments` directory), which should only contain month files named
+/// after their month (eg `2022/August.json`).
+pub fn check() -> Vec<Problem> {
+    let mut problems = Vec::new();
+
+    for entry in read_dir_sorted(&month_log::data_dir()) {
+        let is_year = entry
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| name.parse::<u32>().is_ok());
+
+        if entry.is_dir() && is_year {
+            check_year(&entry, &mut problems);
+        } else if entry.file_name() != Some("attachments".as_ref()) {
+            problems.push(Problem {
+                path: entry,
+                kind: ProblemKind::UnexpectedFile,
+            });
+        }
+    }
+
+    problems
+}
+
+fn check_year(dir: &Path, problems: &mut Vec<Problem>) {
+    let year: u32 = dir.file_name().unwrap().to_str().unwrap().parse().unwrap();
+
+    for path in read_dir_sorted(dir) {
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or("");
+
+        let month = match name.strip_suffix(".json") {
+            Some(month) if month.parse::<tMonth>().is_ok() => month.to_string(),
+            _ if is_set_aside(name) => {
+                problems.push(Problem {
+                    path,
+                    kind: ProblemKind::SetAside,
+                });
+                continue;
+            }
+            _ if merge::is_conflict_file(name) => {
+                problems.push(Problem {
+                    path,
+                    kind: ProblemKind::ConflictCopy,
+                });
+                continue;
+            }
+            _ => {
+                problems.push(Problem {
+                    path,
+                    kind: ProblemKind::UnexpectedFile,
+                });
+                continue;
+            }
+        };
+
+        check_month_file(path, &month, year, problems);
+    }
+}
+
+fn check_month_file(path: PathBuf, month: &str, year: u32, problems: &mut Vec<Problem>) {
+    let log = match MonthLog::read_file(&path) {
+        Ok(log) => log,
+        Err(e) => {
+            problems.push(Problem {
+                path,
+                kind: ProblemKind::InvalidJson(e.to_string()),
+            });
+            return;
+        }
+    };
+
+    if log.month() != month || log.year() != year {
+        problems.push(Problem {
+            path: path.clone(),
+            kind: ProblemKind::MismatchedName {
+                month_year: log.month_year(),
+            },
+        });
+    }
+
+    let expected = days_in_year_month(year as i32, month.parse::<tMonth>().unwrap());
+    if log.entries().len() != expected as usize {
+        problems.push(Problem {
+            path: path.clone(),
+            kind: ProblemKind::WrongEntryCount {
+                expected,
+                found: log.entries().len(),
+            },
+        });
+    }
+
+    for (i, entry) in log.entries().iter().enumerate() {
+        if !entry.has_valid_rating() {
+            problems.push(Problem {
+                path: path.clone(),
+                kind: ProblemKind::InvalidRating {
+                    day: i + 1,
+                    rating: entry.get_rating(),
+                },
+            });
+        }
+    }
+}
+
+/// Fix the repairable `problems`, backing up every file before changing it.
+///
+/// Month files that can be read are repaired in place (see
+/// `MonthLog::repair`), unreadable ones are renamed to `<month>.json.corrupt`
+/// so they can be fixed by hand.
+/// The attachments of a month are backed up with its file, so the backup of
+/// a month is complete. Return the backup directory.
+pub fn repair(problems: &[Problem]) -> io::Result<PathBuf> {
+    let data_dir = month_log::data_dir();
+    let backup_dir = data_dir
+        .parent()
+        .unwrap()
+        .join("backups")
+        .join(Local::now().format("check-%Y-%m-%d-%H%M%S").to_string());
+
+    let mut paths: Vec<&PathBuf> = problems
+        .iter()
+        .filter(|problem| problem.is_repairable())
+        .map(|problem| &problem.path)
+        .collect();
+    paths.dedup();
+
+    for path in paths {
+        let backup = backup_dir.join(path.strip_prefix(&data_dir).unwrap());
+        fs::create_dir_all(backup.parent().unwrap())?;
+        fs::copy(path, &backup)?;
+        let attachments = attachments_dir(path);
+        if attachments.is_dir() {
+            attachment::copy_missing(
+                &attachments,
+                &backup_dir.join(attachments.strip_prefix(&data_dir).unwrap()),
+            )?;
+        }
+
+        match MonthLog::read_file(path) {
+            Ok(mut log) => {
+                let month = path.file_stem().unwrap().to_str().unwrap();
+                let year = path
+                    .parent()
+                    .unwrap()
+                    .file_name()
+                    .unwrap()
+                    .to_str()
+                    .unwrap();
+                log.repair(month, year.parse().unwrap());
+                log.save_to_disk()?;
+            }
+            Err(_) => fs::rename(path, set_aside_path(path))?,
+        }
+    }
+
+    Ok(backup_dir)
+}
+
+/// Return the path an unreadable month file is renamed to by `repair`.
+fn set_aside_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap().to_os_string();
+    name.push(".corrupt");
+    path.with_file_name(name)
+}
+
+/// Check if `name` is the file name of a month file renamed by `repair`.
+fn is_set_aside(name: &str) -> bool {
+    name.strip_suffix(".json.corrupt")
+        .is_some_and(|month| month.parse::<tMonth>().is_ok())
+}
+
+/// Return the attachments folder of the month file at `path`.
+fn attachments_dir(path: &Path) -> PathBuf {
+    let month = path.file_stem().unwrap();
+    let year = path.parent().unwrap().file_name().unwrap();
+    month_log::data_dir()
+        .join("attachments")
+        .join(year)
+        .join(month)
+}
+
+/// Return the paths inside `dir` in alphabetical order.
+fn read_dir_sorted(dir: &Path) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+        .expect("failed to read directory")
+        .map(|entry| entry.expect("failed to get a directory entry").path())
+        .collect();
+    paths.sort();
+    paths
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Datelike;
+
+    use super::*;
+    use crate::calendar;
+    use crate::month_log::tests::TempDir;
+    use crate::month_log::Entry;
+
+    fn write(name: &str, data: &str) -> PathBuf {
+        let path = month_log::data_dir().join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, data).unwrap();
+        path
+    }
+
+    /// Return the JSON of a month file with `count` entries rated `rating`.
+    fn month_file(month: &str, year: u32, count: usize, rating: i8) -> String {
+        let entries = vec![Entry::new(rating, "text"); count];
+        serde_json::json!({"month": month, "year": year, "entries": entries}).to_string()
+    }
+
+    fn descriptions() -> Vec<String> {
+        check().iter().map(|problem| problem.to_string()).collect()
+    }
+
+    #[test]
+    fn valid_data_has_no_problems() {
+        let _temp = TempDir::create();
+        write("2022/June.json", &month_file("June", 2022, 30, 1));
+        write("attachments/2022/June/01/ticket.pdf", "");
+        assert!(check().is_empty());
+    }
+
+    #[test]
+    fn finds_every_kind_of_problem() {
+        let _temp = TempDir::create();
+        write("2021/March.json", "{bad");
+        write("2022/June.json", &month_file("June", 2022, 10, 1));
+        write("2022/July.json", &month_file("August", 2022, 31, 1));
+        // only the first day has an invalid rating
+        let may = month_file("May", 2022, 31, 1).replacen("\"rating\":1", "\"rating\":7", 1);
+        write("2022/May.json", &may);
+        write("2022/June (conflicted copy).json", "{}");
+        write("2022/notes.txt", "");
+
+        let problems = check();
+        let descriptions = descriptions();
+        assert!(descriptions[0].starts_with("2021/March.json: invalid month file"));
+        assert_eq!(
+            descriptions[1..],
+            [
+                "2022/July.json: contains the entries of August/2022",
+                "2022/June (conflicted copy).json: conflict copy made by a sync tool (run `lifelog merge`)",
+                "2022/June.json: has 10 entries, but the month has 30 days",
+                "2022/May.json: day 1 has an invalid rating (7)",
+                "2022/notes.txt: unexpected file",
+            ]
+        );
+        let repairable = problems.iter().filter(|problem| problem.is_repairable());
+        assert_eq!(repairable.count(), 4);
+    }
+
+    #[test]
+    fn repair_fixes_readable_files() {
+        let _temp = TempDir::create();
+        write("2022/June.json", &month_file("June", 2022, 10, 1));
+        write("2022/July.json", &month_file("August", 2022, 33, 5));
+        write("attachments/2022/June/01/ticket.pdf", "pdf");
+
+        let backup = repair(&check()).unwrap();
+        assert!(check().is_empty());
+
+        let june = MonthLog::get_month_log("June/2022").unwrap();
+        assert_eq!(june.days(), 30);
+        let july = MonthLog::get_month_log("July/2022").unwrap();
+        assert_eq!(july.days(), 31);
+        assert_eq!(july.get_entry(1).get_rating(), 2);
+
+        assert!(backup.join("2022/June.json").is_file());
+        assert!(backup.join("attachments/2022/June/01/ticket.pdf").is_file());
+    }
+
+    #[test]
+    fn repair_sets_unreadable_files_aside() {
+        let _temp = TempDir::create();
+        let path = write("2021/March.json", "{bad");
+
+        let backup = repair(&check()).unwrap();
+        assert!(!path.exists());
+        assert_eq!(
+            fs::read_to_string(path.with_file_name("March.json.corrupt")).unwrap(),
+            "{bad"
+        );
+        assert!(backup.join("2021/March.json").is_file());
+        assert_eq!(
+            descriptions(),
+            [
+                "2021/March.json.corrupt: unreadable month file set aside by a repair \
+              (fix it and rename it to March.json)"
+            ]
+        );
+        assert!(!check()[0].is_repairable());
+
+        // the year without month files is skipped
+        let today = Local::today();
+        assert_eq!(calendar::earliest_latest().0, today.with_day(1).unwrap());
+    }
+}
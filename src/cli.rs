@@ -0,0 +1,617 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use chrono::{Datelike, Local, NaiveDate};
+
+use super::analysis::Analysis;
+use super::book::Book;
+use super::config::Config;
+use super::journal::Journal;
+use super::merge::{Merge, Resolution};
+use super::month_log::{DatedEntry, Entry};
+use super::query::Query;
+use super::rpc::Rpc;
+use super::sentiment::{self, Comparison};
+use super::server::Server;
+use super::site::Site;
+use super::statistics::{self, Statistics};
+use super::{attachment, calendar, check, profile};
+
+const USAGE: &str = "\
+usage: lifelog [--journal NAME] [command]
+
+Without a command, the interactive diary is started.
+With --journal, the diary or the command uses the journal called NAME
+instead of the default one.
+
+commands:
+    stats [--from DATE] [--to DATE] [--format json|csv] [--monthly]
+        print statistics of the entries between two dates (YYYY-MM-DD).
+        defaults to all the entries up to today, formatted as JSON.
+        with --monthly, the statistics are split per month.
+    analyze [--from DATE] [--to DATE] [--format text|json]
+        compare the rating of the days with and without each habit (and
+        yes/no or choice field), and correlate numeric fields with the
+        rating. uses the same range as stats by default.
+    sentiment [--from DATE] [--to DATE] [--format text|json]
+        compare the ratings given to days with the rating suggested by the
+        tone of their text, month by month. same range as stats by default.
+    list [QUERY] [--from DATE] [--to DATE] [--include-private]
+        print the days whose entry matches QUERY, with their rating and the
+        first line of their text. a query is a list of terms that all have
+        to match, eg 'rating>=1 tag:work after:2022-06-01 text:\"deploy\"':
+            rating>=1           rating compared with = : != < <= > >=
+            tag:NAME            the text contains #NAME
+            habit:NAME          the habit was done
+            after:DATE          the day is on or after DATE (before: too)
+            text:WORDS, WORDS   the text contains WORDS (any case)
+            NAME>=VALUE         a custom metric, eg sleep>=7 or workout:yes
+        put values with spaces in quotes and start a term with - to negate
+        it (eg -tag:work). uses the same range as stats by default. private
+        entries are left out unless --include-private is given.
+    export [QUERY] [--from DATE] [--to DATE] [--include-private]
+           [--attachments DIR]
+        print the entries that match QUERY (see list) as JSON. with
+        --attachments, their attachments are copied to DIR, in a folder per
+        day (eg DIR/2022-08-05/ticket.pdf).
+    merge [DIR] [--prefer ours|theirs|both]
+        merge the entries of another data directory into this one. without
+        DIR, the conflict copies of month files made by sync tools are merged.
+        days with a different entry on both sides are shown side by side to
+        choose from, unless --prefer says which one to keep.
+    check [--repair]
+        validate every file in the data directory and list the problems.
+        with --repair, fix what can be fixed after backing up the files.
+    site DIR [--from DATE] [--to DATE] [--include-private]
+        write a static website of the entries to DIR, to browse them offline:
+        a calendar per month colored by rating, a page per day, pages for
+        the #tags used in the text and a search page. the colors come from
+        the selected theme. private entries are left out unless
+        --include-private is given.
+    book FILE [--year YEAR] [--from DATE] [--to DATE] [--include-private]
+        write the entries of a year (or between two dates) as a book, with a
+        chapter per month that starts with its statistics. FILE ends with
+        .epub or .pdf. the PDF can only show Latin characters. private
+        entries are left out unless --include-private is given.
+    serve [--port PORT] [--token TOKEN]
+        serve a web interface to the journal on 127.0.0.1 (port 8080 by
+        default), with a calendar, the entries, an editor and statistics.
+        it can be used while the interactive diary is open. a random token
+        is generated unless --token is given, and the link to log in with
+        it is printed. a journal with a passphrase also asks for it.
+    rpc
+        answer JSON-RPC 2.0 requests on stdin, one per line, for editor
+        plugins. the methods are get_entry, put_entry, delete_entry,
+        list_days, search and stats (see the README for their params).
+    journals [--new NAME]
+        list the journals, or create a new one called NAME.
+    help
+        print this message.";
+
+/// Run the command given on the command line (without the program name).
+pub fn run(args: &[String]) -> Result<(), String> {
+    let (command, args) = args.split_first().expect("no command given");
+
+    match command.as_str() {
+        "stats" => stats(args),
+        "analyze" => analyze(args),
+        "sentiment" => sentiment(args),
+        "list" => list(args),
+        "export" => export(args),
+        "merge" => merge(args),
+        "check" => check(args),
+        "site" => site(args),
+        "book" => book(args),
+        "serve" => serve(args),
+        "rpc" => rpc(args),
+        "journals" => journals(args),
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        _ => Err(format!("unknown command '{}'\n\n{}", command, USAGE)),
+    }
+}
+
+/// Remove the `--journal NAME` (or `--journal=NAME`) option from `args` and
+/// use that journal, if it was given.
+///
+/// The option comes before the command but is accepted anywhere, so it
+/// works the same for the interactive diary and all the commands.
+pub fn select_journal(args: &mut Vec<String>) -> Result<(), String> {
+    let position = match args
+        .iter()
+        .position(|arg| arg == "--journal" || arg.starts_with("--journal="))
+    {
+        Some(position) => position,
+        None => return Ok(()),
+    };
+
+    let name = match args.remove(position).strip_prefix("--journal=") {
+        Some(name) => name.to_string(),
+        None if position < args.len() => args.remove(position),
+        None => return Err("missing value for '--journal'".to_string()),
+    };
+    profile::set_current(&name)
+}
+
+/// Options of a command, eg `--from 2022-01-01 --monthly`.
+struct Options {
+    values: HashMap<String, String>,
+    flags: HashSet<String>,
+    /// Arguments that are not options, in the order they were given.
+    positional: Vec<String>,
+}
+
+impl Options {
+    /// Parse `args`, which can contain `--name value` (or `--name=value`) pairs
+    /// for the names in `valued`, lone `--name` flags for the names in `flags`
+    /// and positional arguments.
+    fn parse(args: &[String], valued: &[&str], flags: &[&str]) -> Result<Self, String> {
+        let mut options = Options {
+            values: HashMap::new(),
+            flags: HashSet::new(),
+            positional: Vec::new(),
+        };
+
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            let name = match arg.strip_prefix("--") {
+                Some(name) => name,
+                None => {
+                    options.positional.push(arg.to_string());
+                    continue;
+                }
+            };
+
+            if let Some((name, value)) = name.split_once('=') {
+                if !valued.contains(&name) {
+                    return Err(format!("unknown option '--{}'", name));
+                }
+                options.values.insert(name.to_string(), value.to_string());
+            } else if valued.contains(&name) {
+                let value = iter
+                    .next()
+                    .ok_or_else(|| format!("missing value for '--{}'", name))?;
+                options.values.insert(name.to_string(), value.to_string());
+            } else if flags.contains(&name) {
+                options.flags.insert(name.to_string());
+            } else {
+                return Err(format!("unknown option '--{}'", name));
+            }
+        }
+
+        Ok(options)
+    }
+
+    fn value(&self, name: &str) -> Option<&str> {
+        self.values.get(name).map(String::as_str)
+    }
+
+    fn flag(&self, name: &str) -> bool {
+        self.flags.contains(name)
+    }
+
+    /// Parse the value of the option `name` as a date, if it was given.
+    fn date(&self, name: &str) -> Result<Option<NaiveDate>, String> {
+        self.value(name).map(calendar::parse_date).transpose()
+    }
+}
+
+/// Read the config file, warn on stderr and use the default settings if it
+/// can't be read.
+fn load_config() -> Config {
+    let (config, warning) = Config::load_or_default();
+    if let Some(warning) = warning {
+        eprintln!("lifelog: {}", warning);
+    }
+    config
+}
+
+fn no_positional(options: &Options) -> Result<(), String> {
+    match options.positional.first() {
+        Some(arg) => Err(format!("unexpected argument '{}'", arg)),
+        None => Ok(()),
+    }
+}
+
+/// Return the range given with `--from` and `--to`, which defaults to all
+/// the entries up to today.
+fn date_range(options: &Options) -> Result<(NaiveDate, NaiveDate), String> {
+    let (from, to) = calendar::date_range(options.date("from")?, options.date("to")?);
+    if from > to {
+        return Err(format!("--from ({}) is after --to ({})", from, to));
+    }
+    Ok((from, to))
+}
+
+// ============================================================================
+// ------------------------------- Stats Command ------------------------------
+// ============================================================================
+fn stats(args: &[String]) -> Result<(), String> {
+    let options = Options::parse(args, &["from", "to", "format"], &["monthly"])?;
+    no_positional(&options)?;
+    let (from, to) = date_range(&options)?;
+
+    let mut journal = Journal::new();
+    let monthly = options.flag("monthly");
+    match options.value("format").unwrap_or("json") {
+        "json" => {
+            let json = if monthly {
+                serde_json::to_string_pretty(
+                    &Statistics::monthly(&mut journal, from, to).map_err(|e| e.to_string())?,
+                )
+            } else {
+                serde_json::to_string_pretty(
+                    &Statistics::for_range(&mut journal, from, to).map_err(|e| e.to_string())?,
+                )
+            };
+            println!("{}", json.unwrap());
+        }
+        "csv" => {
+            println!("{}", statistics::CSV_HEADER);
+            if monthly {
+                for month in
+                    Statistics::monthly(&mut journal, from, to).map_err(|e| e.to_string())?
+                {
+                    println!("{}", month.csv_row());
+                }
+            } else {
+                println!(
+                    "{}",
+                    Statistics::for_range(&mut journal, from, to)
+                        .map_err(|e| e.to_string())?
+                        .csv_row()
+                );
+            }
+        }
+        format => return Err(format!("unknown format '{}', expected json or csv", format)),
+    }
+
+    Ok(())
+}
+
+// ============================================================================
+// ------------------------------ Analyze Command -----------------------------
+// ============================================================================
+fn analyze(args: &[String]) -> Result<(), String> {
+    let options = Options::parse(args, &["from", "to", "format"], &[])?;
+    no_positional(&options)?;
+    let (from, to) = date_range(&options)?;
+
+    let analysis = Analysis::for_range(&mut Journal::new(), from, to, &load_config())
+        .map_err(|e| e.to_string())?;
+    match options.value("format").unwrap_or("text") {
+        "text" => println!("{}", analysis),
+        "json" => println!("{}", serde_json::to_string_pretty(&analysis).unwrap()),
+        format => {
+            return Err(format!(
+                "unknown format '{}', expected text or json",
+                format
+            ))
+        }
+    }
+
+    Ok(())
+}
+
+// ============================================================================
+// ----------------------------- Sentiment Command ----------------------------
+// ============================================================================
+fn sentiment(args: &[String]) -> Result<(), String> {
+    let options = Options::parse(args, &["from", "to", "format"], &[])?;
+    no_positional(&options)?;
+    let (from, to) = date_range(&options)?;
+
+    let mut journal = Journal::new();
+    let months = Comparison::monthly(&mut journal, from, to).map_err(|e| e.to_string())?;
+    match options.value("format").unwrap_or("text") {
+        "text" => {
+            println!("{}", sentiment::HEADER);
+            for month in &months {
+                println!("{}", month);
+            }
+            if months.len() > 1 {
+                let total =
+                    Comparison::for_range(&mut journal, from, to).map_err(|e| e.to_string())?;
+                println!("{}", total);
+            }
+        }
+        "json" => println!("{}", serde_json::to_string_pretty(&months).unwrap()),
+        format => {
+            return Err(format!(
+                "unknown format '{}', expected text or json",
+                format
+            ))
+        }
+    }
+
+    Ok(())
+}
+
+// ============================================================================
+// -------------------------- List and Export Commands ------------------------
+// ============================================================================
+fn list(args: &[String]) -> Result<(), String> {
+    let options = Options::parse(args, &["from", "to"], &["include-private"])?;
+    let found = find_entries(&options)?;
+    for (date, entry) in &found {
+        println!(
+            "{}  {:+}  {}",
+            date,
+            entry.get_rating(),
+            entry.get_text().lines().next().unwrap_or("")
+        );
+    }
+    match found.len() {
+        1 => println!("\n1 entry found"),
+        n => println!("\n{} entries found", n),
+    }
+    Ok(())
+}
+
+fn export(args: &[String]) -> Result<(), String> {
+    let options = Options::parse(args, &["from", "to", "attachments"], &["include-private"])?;
+    let found = find_entries(&options)?;
+
+    if let Some(dir) = options.value("attachments") {
+        for (date, entry) in &found {
+            attachment::copy_day(
+                &date.format("%B/%Y").to_string(),
+                date.day(),
+                entry.get_attachments(),
+                &Path::new(dir).join(date.to_string()),
+            )
+            .map_err(|e| format!("failed to copy the attachments of {}: {}", date, e))?;
+        }
+    }
+
+    let entries: Vec<DatedEntry> = found
+        .iter()
+        .map(|(date, entry)| DatedEntry::new(*date, entry))
+        .collect();
+    println!("{}", serde_json::to_string_pretty(&entries).unwrap());
+    Ok(())
+}
+
+/// Return the entries that match the query given to `list` or `export`.
+///
+/// The query can be given as one argument or split in several (the shell
+/// removes the quotes of `text:"deploy"`, so both work).
+fn find_entries(options: &Options) -> Result<Vec<(NaiveDate, Entry)>, String> {
+    let (from, to) = date_range(options)?;
+    let query =
+        Query::parse(&options.positional.join(" ")).map_err(|e| format!("invalid query: {}", e))?;
+
+    let mut found = query
+        .find(&mut Journal::new(), from, to)
+        .map_err(|e| e.to_string())?;
+    if !options.flag("include-private") {
+        found.retain(|(_, entry)| !entry.is_private());
+    }
+    Ok(found)
+}
+
+// ============================================================================
+// ------------------------------- Merge Command ------------------------------
+// ============================================================================
+fn merge(args: &[String]) -> Result<(), String> {
+    let options = Options::parse(args, &["prefer"], &[])?;
+
+    let mut merge = match options.positional.as_slice() {
+        [] => Merge::from_conflict_files(),
+        [dir] => Merge::from_dir(Path::new(dir))
+            .map_err(|e| format!("failed to read '{}': {}", dir, e))?,
+        [_, arg, ..] => return Err(format!("unexpected argument '{}'", arg)),
+    };
+
+    for (path, e) in &merge.errors {
+        eprintln!("skipping '{}': {}", path.display(), e);
+    }
+
+    let conflicts = merge.conflicts().len();
+    match options.value("prefer") {
+        Some("ours") => merge.resolve_all(Resolution::Ours),
+        Some("theirs") => merge.resolve_all(Resolution::Theirs),
+        Some("both") => merge.resolve_all(Resolution::Both),
+        Some(prefer) => {
+            return Err(format!(
+                "unknown value '{}' for --prefer, expected ours, theirs or both",
+                prefer
+            ))
+        }
+        None if conflicts > 0 => {
+            super::resolve_conflicts(merge);
+            return Ok(());
+        }
+        None => (),
+    }
+
+    merge
+        .save()
+        .map_err(|e| format!("failed to save the merge: {}", e))?;
+    println!("merge saved, {} conflicting days resolved.", conflicts);
+
+    Ok(())
+}
+
+// ============================================================================
+// ------------------------------- Check Command ------------------------------
+// ============================================================================
+fn check(args: &[String]) -> Result<(), String> {
+    let options = Options::parse(args, &[], &["repair"])?;
+    no_positional(&options)?;
+
+    let problems = check::check();
+    if problems.is_empty() {
+        println!("no problems found.");
+        return Ok(());
+    }
+
+    for problem in &problems {
+        println!("{}", problem);
+    }
+
+    let repairable = problems.iter().filter(|p| p.is_repairable()).count();
+    if !options.flag("repair") {
+        return Err(format!(
+            "found {} problems, {} can be fixed with --repair",
+            problems.len(),
+            repairable
+        ));
+    }
+
+    let backup_dir =
+        check::repair(&problems).map_err(|e| format!("failed to repair the data: {}", e))?;
+    println!(
+        "\nrepaired {} problems, the original files are in '{}'",
+        repairable,
+        backup_dir.display()
+    );
+
+    if repairable < problems.len() {
+        return Err(format!(
+            "{} problems have to be fixed by hand",
+            problems.len() - repairable
+        ));
+    }
+    Ok(())
+}
+
+// ============================================================================
+// ------------------------------- Site Command -------------------------------
+// ============================================================================
+fn site(args: &[String]) -> Result<(), String> {
+    let options = Options::parse(args, &["from", "to"], &["include-private"])?;
+    let dir = match options.positional.as_slice() {
+        [dir] => Path::new(dir),
+        [] => return Err("missing the directory to write the site to".to_string()),
+        [_, arg, ..] => return Err(format!("unexpected argument '{}'", arg)),
+    };
+    let (from, to) = date_range(&options)?;
+
+    let site = Site::new(load_config(), options.flag("include-private"));
+    let days = site
+        .export(&mut Journal::new(), dir, from, to)
+        .map_err(|e| format!("failed to write the site: {}", e))?;
+    println!(
+        "wrote {} days to '{}', open '{}' in a browser.",
+        days,
+        dir.display(),
+        dir.join("index.html").display()
+    );
+    Ok(())
+}
+
+// ============================================================================
+// ------------------------------- Book Command -------------------------------
+// ============================================================================
+fn book(args: &[String]) -> Result<(), String> {
+    let options = Options::parse(args, &["year", "from", "to"], &["include-private"])?;
+    let path = match options.positional.as_slice() {
+        [path] => Path::new(path),
+        [] => return Err("missing the file to write the book to".to_string()),
+        [_, arg, ..] => return Err(format!("unexpected argument '{}'", arg)),
+    };
+
+    let (from, to) = match options.value("year") {
+        Some(_) if options.value("from").is_some() || options.value("to").is_some() => {
+            return Err("--year can't be used with --from or --to".to_string())
+        }
+        Some(year) => {
+            let year: i32 = year
+                .parse()
+                .map_err(|_| format!("invalid year '{}'", year))?;
+            let today = Local::today().naive_local();
+            if year > today.year() {
+                return Err(format!("{} has not started yet", year));
+            }
+            (
+                NaiveDate::from_ymd(year, 1, 1),
+                today.min(NaiveDate::from_ymd(year, 12, 31)),
+            )
+        }
+        None => date_range(&options)?,
+    };
+
+    let book = Book::new(
+        &mut Journal::new(),
+        load_config(),
+        options.flag("include-private"),
+        from,
+        to,
+    )
+    .map_err(|e| e.to_string())?;
+    let written = match path.extension().and_then(|extension| extension.to_str()) {
+        Some("epub") => book.write_epub(path),
+        Some("pdf") => book.write_pdf(path),
+        _ => return Err("the book has to be an .epub or a .pdf file".to_string()),
+    };
+    written.map_err(|e| format!("failed to write the book: {}", e))?;
+    println!("wrote {} days to '{}'", book.days(), path.display());
+    Ok(())
+}
+
+// ============================================================================
+// ------------------------------- Serve Command ------------------------------
+// ============================================================================
+fn serve(args: &[String]) -> Result<(), String> {
+    let options = Options::parse(args, &["port", "token"], &[])?;
+    no_positional(&options)?;
+    let port = match options.value("port") {
+        Some(port) => port
+            .parse()
+            .map_err(|_| format!("invalid port '{}'", port))?,
+        None => 8080,
+    };
+    let token = options.value("token").map(str::to_string);
+    if token.as_deref().is_some_and(str::is_empty) {
+        return Err("the token can't be empty".to_string());
+    }
+
+    let server = Server::bind(port, token, load_config())?;
+    println!(
+        "serving the journal '{}', open {} in a browser to log in.",
+        profile::current(),
+        server.login_url()
+    );
+    println!("press Ctrl+C to stop.");
+    server.run();
+    Ok(())
+}
+
+// ============================================================================
+// -------------------------------- RPC Command -------------------------------
+// ============================================================================
+fn rpc(args: &[String]) -> Result<(), String> {
+    let options = Options::parse(args, &[], &[])?;
+    no_positional(&options)?;
+
+    Rpc::new(load_config())
+        .run()
+        .map_err(|e| format!("failed to talk over stdio: {}", e))
+}
+
+// ============================================================================
+// ----------------------------- Journals Command -----------------------------
+// ============================================================================
+fn journals(args: &[String]) -> Result<(), String> {
+    let options = Options::parse(args, &["new"], &[])?;
+    no_positional(&options)?;
+
+    if let Some(name) = options.value("new") {
+        profile::create(name).map_err(|e| format!("failed to create the journal: {}", e))?;
+        println!("created the journal '{}'", name);
+        return Ok(());
+    }
+
+    let current = profile::current();
+    for name in profile::names() {
+        if name == current {
+            println!("* {}", name);
+        } else {
+            println!("  {}", name);
+        }
+    }
+    Ok(())
+}
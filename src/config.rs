@@ -0,0 +1,265 @@
+use std::fs;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+use super::hooks::Hooks;
+use super::month_log::{self, MetricValue};
+
+/// Settings read from the `config.toml` file.
+///
+/// Every section is optional, a missing file gives the default settings.
+///
+/// ```toml
+/// habits = ["meditate", "read", "no sugar"]
+/// rating_labels = ["shipped it", "productive", "okay", "slow", "on fire"]
+/// lock_after_minutes = 5
+/// privacy_mode = true
+/// ```
+#[derive(Deserialize)]
+pub struct Config {
+    /// Names of the points of the rating scale, from `+2` to `-2`. Empty
+    /// names are not shown.
+    #[serde(default = "default_rating_labels")]
+    pub rating_labels: [String; 5],
+    /// Names of the habits to tick off every day.
+    #[serde(default)]
+    pub habits: Vec<String>,
+    /// Fields recorded for every day next to the rating.
+    #[serde(default)]
+    pub metrics: Vec<Metric>,
+    /// Minutes without a key press after which the journal is locked, if it
+    /// has a passphrase.
+    pub lock_after_minutes: Option<u64>,
+    /// Whether the previews and statistics start blurred, eg on a computer
+    /// that is often used to share the screen.
+    #[serde(default)]
+    pub privacy_mode: bool,
+    /// Commands to run on save, delete and startup.
+    #[serde(default)]
+    pub hooks: Hooks,
+}
+
+/// A field the user wants to record every day (eg hours of sleep).
+///
+/// Defined in the config file like this:
+///
+/// ```toml
+/// [[metrics]]
+/// name = "sleep"
+/// type = "number"
+/// unit = "hours"
+/// min = 0
+/// max = 12
+///
+/// [[metrics]]
+/// name = "workout"
+/// type = "boolean"
+///
+/// [[metrics]]
+/// name = "caffeine"
+/// type = "choice"
+/// options = ["none", "a cup", "too much"]
+/// ```
+#[derive(Deserialize, Clone)]
+pub struct Metric {
+    pub name: String,
+    #[serde(flatten)]
+    pub kind: MetricKind,
+}
+
+/// The kind of value a `Metric` holds.
+#[derive(Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum MetricKind {
+    /// A number, optionally with a unit and the range of valid values, which
+    /// the charts use as well.
+    Number {
+        unit: Option<String>,
+        min: Option<f64>,
+        max: Option<f64>,
+    },
+    /// Yes or no.
+    Boolean,
+    /// One of the given options.
+    Choice { options: Vec<String> },
+}
+
+impl Metric {
+    /// Check that `value` is a valid value of this metric, return a
+    /// description of the problem if it isn't.
+    ///
+    /// Numbers have to be finite, since JSON can't hold the others, and
+    /// within `min` and `max` when they are set.
+    pub fn check(&self, value: &MetricValue) -> Result<(), String> {
+        match (&self.kind, value) {
+            (MetricKind::Number { min, max, .. }, MetricValue::Number(number)) => {
+                if !number.is_finite() {
+                    Err(format!("{}: '{}' is not a number", self.name, number))
+                } else if min.is_some_and(|min| *number < min) {
+                    Err(format!(
+                        "{}: {} is below the minimum of {}",
+                        self.name,
+                        number,
+                        min.unwrap()
+                    ))
+                } else if max.is_some_and(|max| *number > max) {
+                    Err(format!(
+                        "{}: {} is above the maximum of {}",
+                        self.name,
+                        number,
+                        max.unwrap()
+                    ))
+                } else {
+                    Ok(())
+                }
+            }
+            (MetricKind::Boolean, MetricValue::Boolean(_)) => Ok(()),
+            (MetricKind::Choice { options }, MetricValue::Choice(choice))
+                if options.contains(choice) =>
+            {
+                Ok(())
+            }
+            _ => Err(format!("{}: invalid value '{}'", self.name, value)),
+        }
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            rating_labels: default_rating_labels(),
+            habits: Vec::new(),
+            metrics: Vec::new(),
+            lock_after_minutes: None,
+            privacy_mode: false,
+            hooks: Hooks::default(),
+        }
+    }
+}
+
+fn default_rating_labels() -> [String; 5] {
+    ["awesome", "", "okay", "", "horrible"].map(String::from)
+}
+
+impl Config {
+    /// Return the name of `rating` on the rating scale, eg `+2 (awesome)`.
+    pub fn rating_label(&self, rating: i8) -> String {
+        let number = match rating {
+            0 => "0".to_string(),
+            rating => format!("{:+}", rating),
+        };
+        match self.rating_labels.get((2 - rating) as usize) {
+            Some(name) if !name.is_empty() => format!("{} ({})", number, name),
+            _ => number,
+        }
+    }
+
+    /// Read the config file, or return the default settings if there is none.
+    ///
+    /// Return a description of the problem if the file can't be read.
+    pub fn load() -> Result<Self, String> {
+        let path = path();
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+
+        let data = fs::read_to_string(&path)
+            .map_err(|e| format!("failed to read '{}': {}", path.display(), e))?;
+        toml::from_str(&data).map_err(|e| format!("invalid '{}': {}", path.display(), e))
+    }
+
+    /// Read the config file like `load`, with the default settings instead if
+    /// it can't be read.
+    ///
+    /// A warning to show to the user is returned in that case.
+    pub fn load_or_default() -> (Self, Option<String>) {
+        match Self::load() {
+            Ok(config) => (config, None),
+            Err(e) => (
+                Config::default(),
+                Some(format!("{}, using the default settings instead.", e)),
+            ),
+        }
+    }
+}
+
+/// Return the location of the config file of the journal in use, next to
+/// its data directory.
+pub fn path() -> PathBuf {
+    month_log::data_dir().parent().unwrap().join("config.toml")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metrics() -> Vec<Metric> {
+        let config: Config = toml::from_str(
+            r#"
+            [[metrics]]
+            name = "sleep"
+            type = "number"
+            min = 4
+            max = 10
+
+            [[metrics]]
+            name = "steps"
+            type = "number"
+
+            [[metrics]]
+            name = "workout"
+            type = "boolean"
+
+            [[metrics]]
+            name = "caffeine"
+            type = "choice"
+            options = ["none", "a cup"]
+            "#,
+        )
+        .unwrap();
+        config.metrics
+    }
+
+    #[test]
+    fn checked_values_survive_a_round_trip() {
+        let metrics = metrics();
+        let values = [
+            (0, MetricValue::Number(4.0)),
+            (0, MetricValue::Number(7.5)),
+            (0, MetricValue::Number(10.0)),
+            (1, MetricValue::Number(-12345.25)),
+            (2, MetricValue::Boolean(false)),
+            (3, MetricValue::Choice("a cup".to_string())),
+        ];
+        for (i, value) in values {
+            assert_eq!(metrics[i].check(&value), Ok(()));
+            let json = serde_json::to_string(&value).unwrap();
+            assert_eq!(serde_json::from_str::<MetricValue>(&json).unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn non_finite_numbers_are_rejected() {
+        let metrics = metrics();
+        for number in [f64::INFINITY, f64::NEG_INFINITY, f64::NAN] {
+            let value = MetricValue::Number(number);
+            assert!(metrics[1].check(&value).is_err());
+            // they would be saved as null, which can't be read back
+            let json = serde_json::to_string(&value).unwrap();
+            assert!(serde_json::from_str::<MetricValue>(&json).is_err());
+        }
+    }
+
+    #[test]
+    fn values_outside_the_range_or_kind_are_rejected() {
+        let metrics = metrics();
+        assert!(metrics[0].check(&MetricValue::Number(3.9)).is_err());
+        assert!(metrics[0].check(&MetricValue::Number(10.5)).is_err());
+        assert!(metrics[0].check(&MetricValue::Boolean(true)).is_err());
+        assert!(metrics[2].check(&MetricValue::Number(1.0)).is_err());
+        assert!(metrics[3]
+            .check(&MetricValue::Choice("too much".to_string()))
+            .is_err());
+    }
+}
@@ -0,0 +1,97 @@
+/// A line of the difference between two texts.
+#[derive(PartialEq, Eq, Debug)]
+pub enum Line<'a> {
+    /// The line is in both texts.
+    Same(&'a str),
+    /// The line is only in the old text.
+    Removed(&'a str),
+    /// The line is only in the new text.
+    Added(&'a str),
+}
+
+/// Compute the line by line difference between `old` and `new`.
+///
+/// Uses the longest common subsequence of lines, which is plenty for the size
+/// of a diary entry. Removed lines come before the added lines replacing them.
+pub fn lines<'a>(old: &'a str, new: &'a str) -> Vec<Line<'a>> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    // common[i][j] is the length of the longest common subsequence of
+    // old[i..] and new[j..]
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut diff = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            diff.push(Line::Same(old[i]));
+            i += 1;
+            j += 1;
+        } else if common[i + 1][j] >= common[i][j + 1] {
+            diff.push(Line::Removed(old[i]));
+            i += 1;
+        } else {
+            diff.push(Line::Added(new[j]));
+            j += 1;
+        }
+    }
+    diff.extend(old[i..].iter().map(|line| Line::Removed(line)));
+    diff.extend(new[j..].iter().map(|line| Line::Added(line)));
+
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identical_texts_are_the_same() {
+        assert_eq!(lines("a\nb", "a\nb"), [Line::Same("a"), Line::Same("b")]);
+        assert!(lines("", "").is_empty());
+    }
+
+    #[test]
+    fn changed_line_is_removed_then_added() {
+        assert_eq!(
+            lines("a\nb\nc", "a\nB\nc"),
+            [
+                Line::Same("a"),
+                Line::Removed("b"),
+                Line::Added("B"),
+                Line::Same("c"),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_the_longest_common_lines() {
+        assert_eq!(
+            lines("x\na\nb\nc", "a\nb\ny\nc\nz"),
+            [
+                Line::Removed("x"),
+                Line::Same("a"),
+                Line::Same("b"),
+                Line::Added("y"),
+                Line::Same("c"),
+                Line::Added("z"),
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_side_adds_or_removes_everything() {
+        assert_eq!(lines("", "a\nb"), [Line::Added("a"), Line::Added("b")]);
+        assert_eq!(lines("a", ""), [Line::Removed("a")]);
+    }
+}
@@ -0,0 +1,194 @@
+use std::fmt;
+use std::io;
+
+use chrono::{Datelike, Duration, Local, NaiveDate};
+
+use super::journal::Journal;
+
+/// How well a habit was kept up in a month.
+pub struct HabitStatistics {
+    pub name: String,
+    /// Number of days of the month on which the habit was done.
+    pub done: u32,
+    /// Number of days of the month so far (all of them for past months).
+    pub days: u32,
+    /// Number of days in a row the habit was done, up to the last day counted
+    /// in `days`. Days of previous months are counted as well.
+    pub streak: u32,
+}
+
+impl HabitStatistics {
+    /// Return the percentage of the days on which the habit was done.
+    pub fn completion_rate(&self) -> f64 {
+        if self.days == 0 {
+            0.0
+        } else {
+            f64::from(self.done) * 100.0 / f64::from(self.days)
+        }
+    }
+}
+
+impl fmt::Display for HabitStatistics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} - {}/{} ({:.0}%), streak {}",
+            self.name,
+            self.done,
+            self.days,
+            self.completion_rate(),
+            self.streak
+        )
+    }
+}
+
+/// Return the statistics of every habit for the month of `date`.
+pub fn month_statistics(
+    journal: &mut Journal,
+    habits: &[String],
+    date: NaiveDate,
+) -> io::Result<Vec<HabitStatistics>> {
+    let today = Local::today().naive_local();
+    let month_year = date.format("%B/%Y").to_string();
+    let log = journal.month(&month_year)?;
+
+    let last_day = log.first_day() + Duration::days(i64::from(log.days()) - 1);
+    let until = last_day.min(today);
+    let days = if until < log.first_day() {
+        0
+    } else {
+        until.day()
+    };
+
+    let done: Vec<u32> = habits
+        .iter()
+        .map(|habit| {
+            log.entries()
+                .iter()
+                .take(days as usize)
+                .filter(|entry| entry.has_done(habit))
+                .count() as u32
+        })
+        .collect();
+
+    let streaks = if days == 0 {
+        vec![0; habits.len()]
+    } else {
+        streaks(journal, habits, until)?
+    };
+
+    Ok(habits
+        .iter()
+        .zip(done)
+        .zip(streaks)
+        .map(|((habit, done), streak)| HabitStatistics {
+            name: habit.clone(),
+            done,
+            days,
+            streak,
+        })
+        .collect())
+}
+
+/// Return the number of days in a row each habit was done, up to `date`.
+///
+/// If `date` is today and a habit was not done yet, its streak up to
+/// yesterday is returned, since the day isn't over.
+fn streaks(journal: &mut Journal, habits: &[String], date: NaiveDate) -> io::Result<Vec<u32>> {
+    let earliest = journal.earliest_latest().0.naive_local();
+    let mut streaks = vec![0; habits.len()];
+    let mut ongoing = vec![true; habits.len()];
+    let mut skip_today = date == Local::today().naive_local();
+
+    // each month is read once and walked back from its last day counted
+    let mut month = date.with_day(1).unwrap();
+    let mut last_day = Some(date.day());
+    loop {
+        let log = journal.month(&month.format("%B/%Y").to_string())?;
+        let entries = &log.entries()[..last_day.unwrap_or(log.days()) as usize];
+        for (i, habit) in habits.iter().enumerate() {
+            if !ongoing[i] {
+                continue;
+            }
+            let mut days = entries.iter().rev().peekable();
+            if skip_today && days.peek().is_some_and(|entry| !entry.has_done(habit)) {
+                days.next();
+            }
+            let left = days.len() as u32;
+            match days.position(|entry| !entry.has_done(habit)) {
+                Some(done) => {
+                    streaks[i] += done as u32;
+                    ongoing[i] = false;
+                }
+                None => streaks[i] += left,
+            }
+        }
+        skip_today = false;
+        last_day = None;
+
+        if month <= earliest || !ongoing.contains(&true) {
+            return Ok(streaks);
+        }
+        month = month.pred().with_day(1).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::month_log::tests::TempDir;
+
+    fn mark_done(journal: &mut Journal, date: NaiveDate, habits: &[&str]) {
+        let habits = habits.iter().map(|habit| habit.to_string()).collect();
+        journal
+            .update(&date.format("%B/%Y").to_string(), |log| {
+                log.update_entry(date.day(), 1, "done".to_string());
+                log.set_habits(date.day(), habits);
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn streaks_go_on_into_previous_months() {
+        let _temp = TempDir::create();
+        let mut journal = Journal::new();
+        mark_done(&mut journal, NaiveDate::from_ymd(2022, 5, 29), &["read"]);
+        for day in [30, 31] {
+            mark_done(
+                &mut journal,
+                NaiveDate::from_ymd(2022, 5, day),
+                &["read", "walk"],
+            );
+        }
+        for day in 1..=3 {
+            mark_done(
+                &mut journal,
+                NaiveDate::from_ymd(2022, 6, day),
+                &["read", "walk"],
+            );
+        }
+        mark_done(&mut journal, NaiveDate::from_ymd(2022, 6, 30), &["walk"]);
+
+        let habits = ["read".to_string(), "walk".to_string(), "swim".to_string()];
+        let june3 = NaiveDate::from_ymd(2022, 6, 3);
+        assert_eq!(streaks(&mut journal, &habits, june3).unwrap(), [6, 5, 0]);
+
+        let june = month_statistics(&mut journal, &habits, june3).unwrap();
+        assert_eq!(june[0].done, 3);
+        assert_eq!(june[0].days, 30);
+        assert_eq!(june[0].streak, 0);
+        assert_eq!(june[1].streak, 1);
+    }
+
+    #[test]
+    fn today_counts_only_once_done() {
+        let _temp = TempDir::create();
+        let mut journal = Journal::new();
+        let today = Local::today().naive_local();
+        mark_done(&mut journal, today.pred(), &["read", "walk"]);
+        mark_done(&mut journal, today, &["walk"]);
+
+        let habits = ["read".to_string(), "walk".to_string()];
+        assert_eq!(streaks(&mut journal, &habits, today).unwrap(), [1, 2]);
+    }
+}
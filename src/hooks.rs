@@ -0,0 +1,159 @@
+use std::fmt;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::thread::{self, JoinHandle};
+
+use chrono::NaiveDate;
+use serde::Deserialize;
+
+use super::month_log::{DatedEntry, Entry};
+use super::profile;
+
+/// Commands to run when something happens in the journal, eg to back up,
+/// sync or send a notification.
+///
+/// Defined in the config file like this:
+///
+/// ```toml
+/// [hooks]
+/// post_save = "git add -A && git commit -m \"entry of $LIFELOG_DATE\""
+/// post_delete = "notify-send \"deleted the entry of $LIFELOG_DATE\""
+/// startup = "rsync -a ~/backup/lifelog/ ~/.local/share/lifelog/"
+/// ```
+///
+/// The commands are run by the shell in the directory of the journal. The
+/// entry is given as JSON on stdin, and the event, date and journal in the
+/// `LIFELOG_EVENT`, `LIFELOG_DATE` and `LIFELOG_JOURNAL` environment
+/// variables.
+#[derive(Deserialize, Default)]
+pub struct Hooks {
+    /// Run after an entry is saved.
+    pub post_save: Option<String>,
+    /// Run after an entry is deleted, with the entry as it was.
+    pub post_delete: Option<String>,
+    /// Run when the journal is opened, without an entry or date.
+    pub startup: Option<String>,
+}
+
+#[derive(Clone, Copy)]
+pub enum Event {
+    PostSave,
+    PostDelete,
+    Startup,
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Event::PostSave => write!(f, "post-save"),
+            Event::PostDelete => write!(f, "post-delete"),
+            Event::Startup => write!(f, "startup"),
+        }
+    }
+}
+
+impl Hooks {
+    /// Return the command to run on `event`, if there is one.
+    pub fn command(&self, event: Event) -> Option<&str> {
+        match event {
+            Event::PostSave => self.post_save.as_deref(),
+            Event::PostDelete => self.post_delete.as_deref(),
+            Event::Startup => self.startup.as_deref(),
+        }
+        .filter(|command| !command.trim().is_empty())
+    }
+}
+
+/// A hook ready to be run, which doesn't borrow anything so it can be run
+/// on another thread.
+pub struct Hook {
+    command: String,
+    event: Event,
+    date: Option<NaiveDate>,
+    /// Name and directory of the journal, taken when the hook is prepared in
+    /// case another journal is opened while it runs.
+    journal: String,
+    dir: PathBuf,
+    /// JSON of the entry, written to the command's stdin.
+    input: String,
+}
+
+impl Hook {
+    /// Prepare the hook of `event` for the entry of `date`, `None` if there
+    /// is no command for it.
+    pub fn new(hooks: &Hooks, event: Event, entry: Option<(NaiveDate, &Entry)>) -> Option<Self> {
+        Some(Hook {
+            command: hooks.command(event)?.to_string(),
+            event,
+            date: entry.map(|(date, _)| date),
+            journal: profile::current(),
+            dir: profile::dir(),
+            input: entry
+                .map(|(date, entry)| serde_json::to_string(&DatedEntry::new(date, entry)).unwrap())
+                .unwrap_or_default(),
+        })
+    }
+
+    /// Run the command and wait for it to finish.
+    ///
+    /// Return a description of the problem if it couldn't be started or
+    /// didn't succeed, with the end of what it wrote to stderr.
+    pub fn run(self) -> Result<(), String> {
+        let mut command = if cfg!(windows) {
+            let mut command = Command::new("cmd");
+            command.arg("/C").arg(&self.command);
+            command
+        } else {
+            let mut command = Command::new("sh");
+            command.arg("-c").arg(&self.command);
+            command
+        };
+        command
+            .current_dir(&self.dir)
+            .env("LIFELOG_EVENT", self.event.to_string())
+            .env("LIFELOG_JOURNAL", &self.journal)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped());
+        match self.date {
+            Some(date) => command.env("LIFELOG_DATE", date.to_string()),
+            None => command.env_remove("LIFELOG_DATE"),
+        };
+
+        let failed = |reason: String| format!("the {} hook failed: {}", self.event, reason);
+        let mut child = command.spawn().map_err(|e| failed(e.to_string()))?;
+        // a command that doesn't read its input closes the pipe early, which
+        // is fine
+        let _ = child.stdin.take().unwrap().write_all(self.input.as_bytes());
+        let output = child
+            .wait_with_output()
+            .map_err(|e| failed(e.to_string()))?;
+        if output.status.success() {
+            return Ok(());
+        }
+
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let lines: Vec<&str> = stderr.trim_end().lines().collect();
+        let mut reason = output.status.to_string();
+        for line in &lines[lines.len().saturating_sub(5)..] {
+            reason.push_str(&format!("\n{}", line));
+        }
+        Err(failed(reason))
+    }
+
+    /// Run the command on another thread, so it doesn't hold up the caller.
+    ///
+    /// `report` is called on that thread with the problem if the command
+    /// failed, see `run`. The thread is returned so it can be waited for.
+    pub fn spawn<F>(self, report: F) -> JoinHandle<()>
+    where
+        F: FnOnce(String) + Send + 'static,
+    {
+        thread::spawn(move || {
+            if let Err(e) = self.run() {
+                report(e);
+            }
+        })
+    }
+}
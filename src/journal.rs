@@ -0,0 +1,405 @@
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use chrono::{Date, Datelike, Local, NaiveDate};
+
+use super::month_log::{self, Entry, MonthLog};
+use super::{calendar, statistics};
+
+/// Cache of the month logs and calendar range, shared by all the screens.
+///
+/// It is kept in the cursive user data. Months are loaded lazily the first
+/// time they are needed and every change made through the journal is saved
+/// to disk right away, so the cache and the files stay consistent.
+/// Modification times of the files are compared on every access, so changes
+/// made by other programs (eg a sync tool) are picked up as well.
+///
+/// Changes are made while holding `DataLock`, so several lifelog processes
+/// (eg the TUI and `lifelog serve`) can work on the same journal.
+pub struct Journal {
+    /// Loaded month logs, by month and year (eg August/2022).
+    months: HashMap<String, CachedMonth>,
+    /// Earliest and latest date for the calendar view.
+    range: Option<CachedRange>,
+}
+
+struct CachedMonth {
+    log: MonthLog,
+    /// Modification time of the month file when it was loaded or saved,
+    /// `None` if the file does not exist.
+    modified: Option<SystemTime>,
+}
+
+struct CachedRange {
+    earliest: Date<Local>,
+    latest: Date<Local>,
+    /// Day on which the range was computed, the latest date depends on it.
+    computed_on: Date<Local>,
+    /// Modification times of the data directory and the year directories.
+    modified: Vec<(PathBuf, Option<SystemTime>)>,
+}
+
+impl Journal {
+    /// Construct an empty journal, nothing is read from disk yet.
+    pub fn new() -> Self {
+        Journal {
+            months: HashMap::new(),
+            range: None,
+        }
+    }
+
+    /// Return the `MonthLog` for the given `month_year` (eg August/2022).
+    ///
+    /// The month is read from disk if it was not loaded yet or if its file was
+    /// changed since. A file that can't be read is returned as an error.
+    pub fn month(&mut self, month_year: &str) -> io::Result<&MonthLog> {
+        let path = month_path(month_year);
+        let modified = modified(&path);
+
+        let up_to_date = self
+            .months
+            .get(month_year)
+            .is_some_and(|cached| cached.modified == modified);
+        if !up_to_date {
+            self.months.insert(
+                month_year.to_string(),
+                CachedMonth {
+                    log: MonthLog::get_month_log(month_year)?,
+                    modified,
+                },
+            );
+        }
+
+        Ok(&self.months[month_year].log)
+    }
+
+    /// Return the entry for the given date.
+    pub fn entry(&mut self, date: &Date<Local>) -> io::Result<&Entry> {
+        let month_year = date.format("%B/%Y").to_string();
+        Ok(self.month(&month_year)?.get_entry(date.day()))
+    }
+
+    /// Return the days in `from..=to` with their entries.
+    ///
+    /// The months of the range are loaded (or read again if they changed)
+    /// first, then the days are read from the cache.
+    pub fn days(
+        &mut self,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> io::Result<impl Iterator<Item = (NaiveDate, &Entry)> + '_> {
+        for (first, _) in statistics::months(from, to) {
+            self.month(&first.format("%B/%Y").to_string())?;
+        }
+
+        let months = &self.months;
+        Ok(from
+            .iter_days()
+            .take_while(move |date| *date <= to)
+            .map(move |date| {
+                let log = &months[&date.format("%B/%Y").to_string()].log;
+                (date, log.get_entry(date.day()))
+            }))
+    }
+
+    /// Change the `MonthLog` for the given `month_year` with `f` and save it.
+    ///
+    /// Return what `f` returned, or the error if the month couldn't be read
+    /// or saved.
+    pub fn update<F, R>(&mut self, month_year: &str, f: F) -> io::Result<R>
+    where
+        F: FnOnce(&mut MonthLog) -> R,
+    {
+        // the month is read again under the lock if another process changed
+        // it, so its changes are not overwritten
+        let _lock = DataLock::acquire()?;
+        self.month(month_year)?;
+        let cached = self.months.get_mut(month_year).unwrap();
+
+        let result = f(&mut cached.log);
+        cached.log.save_to_disk()?;
+        cached.modified = modified(&month_path(month_year));
+        Ok(result)
+    }
+
+    /// Return the earliest and latest date for the calendar view.
+    ///
+    /// See `calendar::earliest_latest`. The data directory is only read again
+    /// if a year directory was added, removed or changed.
+    pub fn earliest_latest(&mut self) -> (Date<Local>, Date<Local>) {
+        let today = Local::today();
+        let modified = directory_times();
+
+        let up_to_date = self
+            .range
+            .as_ref()
+            .is_some_and(|range| range.computed_on == today && range.modified == modified);
+        if !up_to_date {
+            let (earliest, latest) = calendar::earliest_latest();
+            self.range = Some(CachedRange {
+                earliest,
+                latest,
+                computed_on: today,
+                modified,
+            });
+        }
+
+        let range = self.range.as_ref().unwrap();
+        (range.earliest, range.latest)
+    }
+}
+
+impl Default for Journal {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Lock on the data directory of the journal in use, released when dropped.
+///
+/// It is a `data.lock` file next to the data directory, which only one
+/// process can create at a time. The file holds a token unique to the lock,
+/// so a lock that was taken over as stale is not released by its old owner.
+struct DataLock {
+    path: PathBuf,
+    token: String,
+}
+
+/// Age after which a lock is considered left behind by a process that
+/// crashed, a change only takes a few milliseconds.
+const STALE_LOCK: Duration = Duration::from_secs(3);
+
+impl DataLock {
+    fn acquire() -> io::Result<Self> {
+        let path = month_log::data_dir().with_file_name("data.lock");
+        let nanos = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let token = format!("{} {}", process::id(), nanos);
+
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    file.write_all(token.as_bytes())?;
+                    return Ok(DataLock { path, token });
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    let stale = modified(&path)
+                        .and_then(|time| time.elapsed().ok())
+                        .is_some_and(|age| age > STALE_LOCK);
+                    if stale {
+                        // take it over, it's created again on the next try
+                        match fs::remove_file(&path) {
+                            Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                                return Err(io::Error::new(
+                                    e.kind(),
+                                    format!(
+                                        "failed to remove the stale lock {}: {}",
+                                        path.display(),
+                                        e
+                                    ),
+                                ))
+                            }
+                            _ => continue,
+                        }
+                    }
+                    thread::sleep(Duration::from_millis(10));
+                }
+                Err(e) => {
+                    return Err(io::Error::new(
+                        e.kind(),
+                        format!("failed to lock the data directory: {}", e),
+                    ))
+                }
+            }
+        }
+    }
+}
+
+impl Drop for DataLock {
+    fn drop(&mut self) {
+        // leave the lock alone if it was taken over as stale by another
+        // process, it's theirs now
+        if fs::read_to_string(&self.path).is_ok_and(|token| token == self.token) {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Return the path of the month file for the given `month_year`.
+fn month_path(month_year: &str) -> PathBuf {
+    let (month, year) = month_year.split_once('/').expect("invalid month_year");
+    month_log::data_dir()
+        .join(year)
+        .join(format!("{}.json", month))
+}
+
+/// Return the modification time of `path`, `None` if it does not exist.
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+/// Return the modification times of the data directory and all the years in it.
+fn directory_times() -> Vec<(PathBuf, Option<SystemTime>)> {
+    let data_dir = month_log::data_dir();
+    let mut times = vec![(data_dir.clone(), modified(&data_dir))];
+
+    for year in calendar::years() {
+        let path = data_dir.join(year.to_string());
+        times.push((path.clone(), modified(&path)));
+    }
+    times
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::month_log::tests::TempDir;
+
+    /// Save `log` the way another program would, with a modification time
+    /// that differs from the one of the cached file.
+    fn save_outside(log: &MonthLog, age: Duration) {
+        log.save_to_disk().unwrap();
+        File::options()
+            .write(true)
+            .open(month_path(&log.month_year()))
+            .unwrap()
+            .set_modified(SystemTime::now() - age)
+            .unwrap();
+    }
+
+    #[test]
+    fn months_are_read_again_after_outside_changes() {
+        let _temp = TempDir::create();
+        let mut journal = Journal::new();
+        assert!(journal
+            .month("June/2022")
+            .unwrap()
+            .get_entry(1)
+            .is_default());
+
+        let mut log = MonthLog::get_month_log("June/2022").unwrap();
+        log.update_entry(1, 1, "from the sync tool".to_string());
+        save_outside(&log, Duration::from_secs(60));
+        assert_eq!(
+            journal.month("June/2022").unwrap().get_entry(1).get_text(),
+            "from the sync tool"
+        );
+
+        log.update_entry(1, 2, "changed again".to_string());
+        save_outside(&log, Duration::from_secs(30));
+        let date = Local.ymd(2022, 6, 1);
+        assert_eq!(journal.entry(&date).unwrap().get_rating(), 2);
+    }
+
+    #[test]
+    fn updates_are_saved_right_away() {
+        let _temp = TempDir::create();
+        let mut journal = Journal::new();
+        let day = journal
+            .update("June/2022", |log| {
+                log.update_entry(5, 1, "saved".to_string());
+                5
+            })
+            .unwrap();
+        assert_eq!(day, 5);
+
+        let saved = MonthLog::get_month_log("June/2022").unwrap();
+        assert_eq!(saved.get_entry(5).get_text(), "saved");
+        assert_eq!(
+            journal.month("June/2022").unwrap().get_entry(5).get_text(),
+            "saved"
+        );
+        assert!(!month_log::data_dir().with_file_name("data.lock").exists());
+    }
+
+    #[test]
+    fn unreadable_months_are_errors() {
+        let _temp = TempDir::create();
+        let path = month_path("June/2022");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, "{bad").unwrap();
+
+        let mut journal = Journal::new();
+        assert!(journal.month("June/2022").is_err());
+        assert!(journal
+            .update("June/2022", |log| log.update_entry(
+                1,
+                1,
+                "lost".to_string()
+            ))
+            .is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "{bad");
+    }
+
+    #[test]
+    fn calendar_range_follows_new_years() {
+        let _temp = TempDir::create();
+        let mut journal = Journal::new();
+        let today = Local::today();
+        assert_eq!(
+            journal.earliest_latest().0,
+            Local.ymd(today.year(), today.month(), 1)
+        );
+
+        journal
+            .update("March/2021", |log| {
+                log.update_entry(3, 0, "old".to_string())
+            })
+            .unwrap();
+        assert_eq!(journal.earliest_latest().0, Local.ymd(2021, 3, 1));
+    }
+
+    fn lock_path() -> PathBuf {
+        month_log::data_dir().with_file_name("data.lock")
+    }
+
+    #[test]
+    fn locks_hold_their_token_until_dropped() {
+        let _temp = TempDir::create();
+        let lock = DataLock::acquire().unwrap();
+        assert_eq!(fs::read_to_string(lock_path()).unwrap(), lock.token);
+        assert!(lock.token.starts_with(&format!("{} ", process::id())));
+
+        drop(lock);
+        assert!(!lock_path().exists());
+    }
+
+    #[test]
+    fn stale_locks_are_taken_over() {
+        let _temp = TempDir::create();
+        fs::write(lock_path(), "12345 1").unwrap();
+        File::options()
+            .write(true)
+            .open(lock_path())
+            .unwrap()
+            .set_modified(SystemTime::now() - STALE_LOCK * 2)
+            .unwrap();
+
+        let lock = DataLock::acquire().unwrap();
+        assert_eq!(fs::read_to_string(lock_path()).unwrap(), lock.token);
+        drop(lock);
+        assert!(!lock_path().exists());
+    }
+
+    #[test]
+    fn locks_taken_over_are_left_alone() {
+        let _temp = TempDir::create();
+        let lock = DataLock::acquire().unwrap();
+        // another process decided the lock was stale and took it
+        fs::write(lock_path(), "12345 2").unwrap();
+
+        drop(lock);
+        assert_eq!(fs::read_to_string(lock_path()).unwrap(), "12345 2");
+    }
+}
@@ -0,0 +1,70 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use argon2::Argon2;
+use password_hash::rand_core::OsRng;
+use password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+
+use super::profile;
+
+/// When the user last pressed a key or used the mouse.
+static LAST_ACTIVITY: Mutex<Option<Instant>> = Mutex::new(None);
+
+/// Return whether the journal in use is locked with a passphrase.
+pub fn is_set() -> bool {
+    path().exists()
+}
+
+/// Lock the journal in use with `passphrase`, replacing the previous one.
+///
+/// Only a hash of the passphrase is stored.
+pub fn set(passphrase: &str) -> io::Result<()> {
+    let salt = SaltString::generate(&mut OsRng);
+    let hash = Argon2::default()
+        .hash_password(passphrase.as_bytes(), &salt)
+        .map_err(|e| io::Error::other(e.to_string()))?;
+    fs::create_dir_all(profile::dir())?;
+    fs::write(path(), hash.to_string())
+}
+
+/// Stop asking for a passphrase to open the journal in use.
+pub fn remove() -> io::Result<()> {
+    fs::remove_file(path())
+}
+
+/// Return whether `passphrase` is the one the journal in use is locked with.
+pub fn verify(passphrase: &str) -> bool {
+    let data = match fs::read_to_string(path()) {
+        Ok(data) => data,
+        Err(_) => return false,
+    };
+    match PasswordHash::new(data.trim()) {
+        Ok(hash) => Argon2::default()
+            .verify_password(passphrase.as_bytes(), &hash)
+            .is_ok(),
+        Err(_) => false,
+    }
+}
+
+/// Remember that the user did something just now.
+pub fn touch() {
+    *LAST_ACTIVITY.lock().unwrap() = Some(Instant::now());
+}
+
+/// Return for how long the user hasn't done anything.
+pub fn idle_for() -> Duration {
+    LAST_ACTIVITY
+        .lock()
+        .unwrap()
+        .map(|last| last.elapsed())
+        .unwrap_or_default()
+}
+
+/// Return the location of the file with the hash of the passphrase, next to
+/// the data directory of the journal in use.
+fn path() -> PathBuf {
+    profile::dir().join("passphrase")
+}
@@ -1,41 +1,202 @@
-use std::cell::{Ref, RefCell, RefMut};
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+use std::env;
+use std::process;
 use std::rc::Rc;
 
-use chrono::{Date, Datelike, Local};
+use chrono::{Date, Datelike, Duration, Local, NaiveDate, TimeZone, Timelike};
 use cursive::align::HAlign;
-use cursive::event::Key;
+use cursive::event::{Event, EventTrigger};
+use cursive::theme::{BaseColor, Color, Effect, Style};
+use cursive::utils::markup::StyledString;
 use cursive::view::{Nameable, Resizable};
 use cursive::views::{
-    Dialog, DialogFocus, HideableView, LinearLayout, OnEventView, RadioGroup, ScrollView, TextArea,
-    TextView,
+    Checkbox, Dialog, DialogFocus, EditView, LinearLayout, OnEventView, RadioGroup, ScrollView,
+    SelectView, StackView, TextArea, TextView,
 };
 use cursive::{Cursive, XY};
 use cursive_calendar_view::{CalendarView, EnglishLocale};
 
+mod analysis;
+mod attachment;
+mod book;
 mod calendar;
+mod capsule;
+mod chart;
+mod check;
+mod cli;
+mod config;
+mod diff;
+mod habits;
+mod hooks;
+mod journal;
+mod lock;
+mod markdown;
+mod merge;
 mod month_log;
+mod nav;
+mod profile;
+mod query;
+mod rpc;
+mod sentiment;
+mod server;
+mod site;
+mod statistics;
+mod theme;
 
-use month_log::MonthLog;
+use analysis::Analysis;
+use config::{Config, MetricKind};
+use hooks::Hook;
+use journal::Journal;
+use merge::{Merge, Resolution};
+use month_log::{Entry, MetricValue, MonthLog};
+use nav::{Navigator, Screen};
+use query::Query;
+
+/// Data shared by all the screens, kept in the cursive user data.
+struct App {
+    journal: Journal,
+    nav: Navigator,
+    config: Config,
+    /// Whether the screens are hidden behind the lock screen.
+    locked: bool,
+    /// Whether the previews and statistics are blurred.
+    privacy: bool,
+    /// Day whose entry is shown even if it's private or blurred.
+    revealed: Option<Date<Local>>,
+    /// Filter of the entries screen.
+    filter: Option<Filter>,
+    /// Time capsules of the journal, read when it's opened and after each
+    /// change so the calendar doesn't read them on every move.
+    capsules: Vec<capsule::Capsule>,
+}
+
+/// Filter of the entries screen.
+struct Filter {
+    /// The query as it was typed.
+    text: String,
+    query: Query,
+    /// Days of the journal that match the query, the earliest first.
+    days: Vec<NaiveDate>,
+    /// Matching day the calendar was last moved to, to tell which way it
+    /// moves next.
+    at: NaiveDate,
+}
+
+impl App {
+    fn new(config: Config) -> Self {
+        App {
+            journal: Journal::new(),
+            nav: Navigator::new(),
+            privacy: config.privacy_mode,
+            config,
+            locked: false,
+            revealed: None,
+            filter: None,
+            capsules: Vec::new(),
+        }
+    }
+}
 
 fn main() {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+    if let Err(e) = cli::select_journal(&mut args) {
+        eprintln!("lifelog: {}", e);
+        process::exit(1);
+    }
+    if !args.is_empty() {
+        if let Err(e) = cli::run(&args) {
+            eprintln!("lifelog: {}", e);
+            process::exit(1);
+        }
+        return;
+    }
+
     let mut siv = cursive::default();
+    siv.add_global_callback('q', Cursive::quit);
+    // every event but the refresh of the screen counts as activity
+    siv.set_on_pre_event_inner(
+        EventTrigger::from_fn(|event| *event != Event::Refresh),
+        |_| {
+            lock::touch();
+            None
+        },
+    );
+    siv.add_global_callback(Event::Refresh, lock_if_idle);
+    open_journal(&mut siv);
+    siv.run();
+}
+
+/// Set up the app for the journal in use and show its main menu.
+///
+/// Used on startup and when switching to another journal.
+fn open_journal(s: &mut Cursive) {
+    let theme_warning = load_theme(s);
+    let (config, config_warning) = Config::load_or_default();
+
+    // the screens of the previous journal are still open, keep the navigator
+    // so that they can be closed
+    let mut app = App::new(config);
+    if let Some(previous) = s.take_user_data::<App>() {
+        app.nav = previous.nav;
+    }
+    let capsule_warning = match capsule::load() {
+        Ok(capsules) => {
+            app.capsules = capsules;
+            None
+        }
+        Err(e) => Some(format!("failed to read the time capsules: {}", e)),
+    };
+    // refresh every second to notice when the journal has to be locked
+    s.set_fps(if app.config.lock_after_minutes.is_some() {
+        1
+    } else {
+        0
+    });
+    s.set_user_data(app);
 
-    let theme_file = month_log::data_dir()
-        .parent()
-        .unwrap()
-        .join("theme")
-        .join("theme.toml");
-    if theme_file.exists() {
-        siv.load_theme_file(theme_file)
-            .expect("invalid theme.toml file");
+    show_main_menu(s);
+    for warning in [theme_warning, config_warning, capsule_warning]
+        .into_iter()
+        .flatten()
+    {
+        nav::message(s, warning);
     }
+    startup_check(s);
+    show_opened_capsules(s);
+    run_hook(s, hooks::Event::Startup, None);
 
-    siv.add_global_callback('q', Cursive::quit);
+    lock::touch();
+    if lock::is_set() {
+        show_lock(s);
+    }
+}
+
+fn show_main_menu(s: &mut Cursive) {
+    let mut welcome = String::from("welcome to lifelog, a log of your uneventful life.");
+    match month_log::on_this_day(Local::today()).len() {
+        0 => (),
+        1 => welcome.push_str("\n\nyou wrote 1 entry on this day in past years."),
+        n => welcome.push_str(&format!(
+            "\n\nyou wrote {} entries on this day in past years.",
+            n
+        )),
+    }
 
-    let mut dialog = Dialog::text("welcome to lifelog, a log of your uneventful life.")
-        .title("lifelog")
+    let title = match profile::current().as_str() {
+        profile::DEFAULT => "lifelog".to_string(),
+        name => format!("lifelog - {}", name),
+    };
+
+    let mut dialog = Dialog::text(welcome)
+        .title(title)
         .button("entries", show_entries)
         .button("new entry", new_entry)
+        .button("time capsule", write_capsule)
+        .button("on this day", show_on_this_day)
+        .button("charts", show_charts)
+        .button("journals", show_journals)
+        .button("settings", show_settings)
         .button("about", show_about)
         .button("quit", Cursive::quit)
         .h_align(HAlign::Center);
@@ -43,80 +204,571 @@ fn main() {
     // focus on 'new entry' button
     dialog.set_focus(DialogFocus::Button(1));
 
-    siv.add_layer(HideableView::new(dialog).with_name("main"));
+    nav::replace(s, Screen::MainMenu, dialog);
+}
 
-    siv.run();
+/// Offer to repair the problems of the data directory or to merge the
+/// conflict copies made by sync tools, if there are any.
+fn startup_check(s: &mut Cursive) {
+    let problems = check::check();
+    // unreadable directories are reported when merging
+    let conflict_files = merge::conflict_files(&mut Vec::new()).len();
+    if problems.iter().any(|problem| problem.is_repairable()) {
+        show_problems(s, problems);
+    } else if conflict_files > 0 {
+        nav::overlay(
+            s,
+            Screen::Popup,
+            Dialog::text(format!(
+                "found {} conflicting copies of month files (made by a sync tool).\n\
+                merge them into your entries now?",
+                conflict_files
+            ))
+            .title("conflicts")
+            .button("Merge", |siv| {
+                nav::back(siv);
+                let merge = Merge::from_conflict_files();
+                show_merge(siv, Rc::new(RefCell::new(Some(merge))), false);
+            })
+            .button("Later", nav::back)
+            .max_width(64),
+        );
+    }
+}
+
+/// Apply the chosen theme.
+///
+/// If it can't be loaded, the default theme is used instead and a warning to
+/// show to the user is returned.
+fn load_theme(siv: &mut Cursive) -> Option<String> {
+    match theme::load(&theme::selected()) {
+        Ok(theme) => {
+            siv.set_theme(theme);
+            None
+        }
+        Err(e) => {
+            siv.set_theme(theme::load(theme::DEFAULT).unwrap());
+            Some(format!("{}, using the default theme instead.", e))
+        }
+    }
 }
 
 // ============================================================================
 // ------------------------------ Entries Button ------------------------------
 // ============================================================================
 fn show_entries(s: &mut Cursive) {
-    hide_main_menu(s);
+    let today = Local::today();
+    let mut calendar = CalendarView::<Local, EnglishLocale>::new(today);
 
-    let month_log = Rc::new(RefCell::new(MonthLog::current_month_log()));
-
-    let mut calendar = CalendarView::<Local, EnglishLocale>::new(Local::today());
-
-    let (earliest_date, latest_date) = calendar::earliest_latest();
+    let (earliest_date, latest_date) = calendar_range(s);
     calendar.set_earliest_date(Some(earliest_date));
     calendar.set_latest_date(Some(latest_date));
 
-    let month_log_clone = Rc::clone(&month_log);
-    calendar.set_on_select(move |siv: &mut Cursive, date: &Date<Local>| {
-        update_preview(siv, date, month_log_clone.borrow_mut());
-        update_statistics(siv, month_log_clone.borrow());
+    calendar.set_on_select(|siv: &mut Cursive, date: &Date<Local>| {
+        let date = &next_match(siv, *date);
+        app(siv).revealed = None;
+        update_preview(siv, date);
+        update_statistics(siv, date);
     });
+    calendar.set_on_submit(edit_entry);
 
-    let another_log_clone = Rc::clone(&month_log);
-    calendar.set_on_submit(move |siv: &mut Cursive, date: &Date<Local>| {
-        let log = another_log_clone.borrow_mut();
-        edit_entry(siv, date, log);
-    });
+    let raw_text = Checkbox::new().on_change(|siv, _| refresh_entries(siv));
+    let privacy = Checkbox::new()
+        .with_checked(app(s).privacy)
+        .on_change(|siv, checked| {
+            app(siv).privacy = checked;
+            refresh_entries(siv);
+        });
 
-    let log = month_log.borrow();
-    let today_entry = log.get_todays_entry();
+    let today_entry = entry_preview(s, &today);
     let preview = Dialog::around(ScrollView::new(
-        TextView::new(today_entry.to_string()).with_name("preview"),
+        TextView::new(today_entry).with_name("preview"),
     ))
     .title("preview")
     .fixed_size(XY { x: 64, y: 20 });
 
-    let calendar = Dialog::around(calendar).title("select date");
-    let statistics = TextView::new(log.get_statistics()).with_name("statistics");
+    let statistics = statistics_content(s, &today);
+
+    // the grid of the habits is next to the calendar, so the days line up
+    let mut calendar_row = LinearLayout::horizontal()
+        .child(Dialog::around(calendar.with_name("calendar")).title("select date"));
+    if !app(s).config.habits.is_empty() {
+        let grid = TextView::new(habit_grid_content(s, &today)).with_name("habit_grid");
+        calendar_row.add_child(Dialog::around(grid).title("habits"));
+    }
+
+    let statistics = TextView::new(statistics).with_name("statistics");
     let column = LinearLayout::vertical()
-        .child(OnEventView::new(calendar).on_event(Key::Esc, unhide_main_menu))
+        .child(calendar_row)
         .child(TextView::new(" press <ESC> to go back."))
+        .child(TextView::new(" press <r> to show a hidden entry."))
+        .child(TextView::new(" press </> to filter the entries."))
+        .child(TextView::new("").with_name("filter_status"))
+        .child(
+            LinearLayout::horizontal()
+                .child(raw_text.with_name("raw_text"))
+                .child(TextView::new(" show raw text")),
+        )
+        .child(
+            LinearLayout::horizontal()
+                .child(privacy)
+                .child(TextView::new(" privacy mode")),
+        )
         .child(Dialog::around(statistics));
 
     let layout = LinearLayout::horizontal().child(column).child(preview);
-    s.add_layer(layout);
+    nav::open(
+        s,
+        Screen::Entries,
+        OnEventView::new(layout)
+            .on_event('r', reveal_entry)
+            .on_event('/', show_filter),
+    );
+    nav::on_return(s, refresh_entries);
+    nav::on_close(s, |siv| app(siv).filter = None);
+}
+
+/// Return the range of days that can be picked in the calendar.
+fn calendar_range(s: &mut Cursive) -> (Date<Local>, Date<Local>) {
+    let (earliest_date, mut latest_date) = journal(s).earliest_latest();
+    // time capsules can be looked up on the day they open
+    if let Some(last) = app(s).capsules.last() {
+        let opens = Local.ymd(
+            last.opens().year(),
+            last.opens().month(),
+            last.opens().day(),
+        );
+        latest_date = latest_date.max(opens);
+    }
+    (earliest_date, latest_date)
+}
+
+/// Show the changes made on other screens to the selected day and its month.
+fn refresh_entries(s: &mut Cursive) {
+    if app(s).filter.is_some() {
+        update_filter(s);
+    }
+    let date = s
+        .call_on_name(
+            "calendar",
+            |view: &mut CalendarView<Local, EnglishLocale>| view.date(),
+        )
+        .unwrap();
+    update_preview(s, &date);
+    update_statistics(s, &date);
+}
+
+fn update_preview(s: &mut Cursive, date: &Date<Local>) {
+    let content = entry_preview(s, date);
+    s.call_on_name("preview", |view: &mut TextView| view.set_content(content));
 }
 
-fn update_preview(s: &mut Cursive, date: &Date<Local>, mut log: RefMut<MonthLog>) {
-    s.call_on_name("preview", |view: &mut TextView| {
-        let month_year = date.format("%B/%Y").to_string();
-        if log.month_year() != month_year {
-            *log = MonthLog::get_month_log(&month_year)
+/// Show the entry of the selected day even if it's private or blurred, or
+/// hide it again.
+fn reveal_entry(s: &mut Cursive) {
+    let date = s
+        .call_on_name(
+            "calendar",
+            |view: &mut CalendarView<Local, EnglishLocale>| view.date(),
+        )
+        .unwrap();
+    let revealed = &mut app(s).revealed;
+    *revealed = if *revealed == Some(date) {
+        None
+    } else {
+        Some(date)
+    };
+    update_preview(s, &date);
+}
+
+/// Ask for a query to filter the entries with and list the matching days.
+///
+/// The calendar only stops on the matching days and the other days are hidden
+/// in the preview, until the filter is cleared.
+fn show_filter(s: &mut Cursive) {
+    let query = app(s)
+        .filter
+        .as_ref()
+        .map(|filter| filter.text.clone())
+        .unwrap_or_default();
+
+    let matches = SelectView::<NaiveDate>::new().on_submit(|siv, date: &NaiveDate| {
+        let date = Local.ymd(date.year(), date.month(), date.day());
+        siv.call_on_name(
+            "calendar",
+            |view: &mut CalendarView<Local, EnglishLocale>| {
+                view.set_selected_date(date);
+                view.set_view_date(date);
+            },
+        );
+        nav::back(siv);
+    });
+
+    nav::overlay(
+        s,
+        Screen::Filter,
+        Dialog::around(
+            LinearLayout::vertical()
+                .child(TextView::new(
+                    "eg: rating>=1 tag:work after:2022-06-01 text:\"deploy\"",
+                ))
+                .child(
+                    EditView::new()
+                        .content(query)
+                        .on_submit(apply_filter)
+                        .with_name("filter_query"),
+                )
+                .child(TextView::new("").with_name("filter_count"))
+                .child(ScrollView::new(matches.with_name("filter_matches")).fixed_height(10)),
+        )
+        .title("filter entries")
+        .button("Filter", |siv| {
+            let query = siv
+                .call_on_name("filter_query", |view: &mut EditView| view.get_content())
+                .unwrap();
+            apply_filter(siv, &query);
+        })
+        .button("Clear", |siv| {
+            siv.call_on_name("filter_query", |view: &mut EditView| view.set_content(""));
+            apply_filter(siv, "");
+        })
+        .button("Back", nav::back)
+        .fixed_width(64),
+    );
+    if app(s).filter.is_some() {
+        list_filter_matches(s);
+    }
+}
+
+/// Filter the entries with `text`, or remove the filter if it's empty.
+fn apply_filter(s: &mut Cursive, text: &str) {
+    let query = match Query::parse(text) {
+        Ok(query) => query,
+        Err(e) => {
+            nav::message(s, format!("invalid query: {}", e));
+            return;
         }
+    };
 
-        let selected_entry = log.get_entry(date.day());
-        view.set_content(selected_entry.to_string());
+    app(s).filter = (!query.is_empty()).then(|| Filter {
+        text: text.to_string(),
+        query,
+        days: Vec::new(),
+        at: NaiveDate::MIN,
     });
+    update_filter(s);
+    list_filter_matches(s);
+}
+
+/// Find the days that match the filter again, narrow the calendar to their
+/// range and move it to the nearest one.
+fn update_filter(s: &mut Cursive) {
+    let days: Vec<NaiveDate> = filter_matches(s)
+        .into_iter()
+        .map(|(date, _)| date)
+        .collect();
+    let (mut earliest_date, mut latest_date) = calendar_range(s);
+    if let (Some(first), Some(last)) = (days.first(), days.last()) {
+        earliest_date = Local.ymd(first.year(), first.month(), first.day());
+        latest_date = Local.ymd(last.year(), last.month(), last.day());
+    }
+    if let Some(filter) = &mut app(s).filter {
+        filter.days = days;
+    }
+
+    let date = s
+        .call_on_name(
+            "calendar",
+            |view: &mut CalendarView<Local, EnglishLocale>| {
+                view.set_earliest_date(Some(earliest_date));
+                view.set_latest_date(Some(latest_date));
+                view.set_view_date(view.date());
+                view.date()
+            },
+        )
+        .unwrap();
+    let date = next_match(s, date);
+    update_preview(s, &date);
+    update_statistics(s, &date);
 }
 
-fn update_statistics(s: &mut Cursive, log: Ref<MonthLog>) {
+/// Move the calendar from `date` to the next day that matches the filter in
+/// the direction it was moving, as the calendar can't mark single days, and
+/// return the day it's on.
+///
+/// The matching days of the month are listed below the calendar.
+fn next_match(s: &mut Cursive, date: Date<Local>) -> Date<Local> {
+    let day = date.naive_local();
+    let (next, status) = match &mut app(s).filter {
+        None => (day, String::new()),
+        Some(filter) => {
+            let after = filter.days.iter().find(|match_day| **match_day > day);
+            let before = filter.days.iter().rev().find(|match_day| **match_day < day);
+            let next = if filter.days.is_empty() || filter.days.contains(&day) {
+                day
+            } else if day > filter.at {
+                *after.or(before).unwrap()
+            } else {
+                *before.or(after).unwrap()
+            };
+            filter.at = next;
+
+            let month_days: Vec<String> = filter
+                .days
+                .iter()
+                .filter(|match_day| match_day.with_day(1) == next.with_day(1))
+                .map(|match_day| match_day.day().to_string())
+                .collect();
+            let status = if month_days.is_empty() {
+                format!(" filter: {}\n no matches this month.", filter.text)
+            } else {
+                format!(
+                    " filter: {}\n matches this month: {}",
+                    filter.text,
+                    month_days.join(", ")
+                )
+            };
+            (next, status)
+        }
+    };
+
+    s.call_on_name("filter_status", |view: &mut TextView| {
+        view.set_content(status)
+    });
+    if next == day {
+        return date;
+    }
+    let next = Local.ymd(next.year(), next.month(), next.day());
+    s.call_on_name(
+        "calendar",
+        |view: &mut CalendarView<Local, EnglishLocale>| {
+            view.set_selected_date(next);
+            view.set_view_date(next);
+        },
+    );
+    next
+}
+
+/// Show the days that match the filter in the filter screen.
+fn list_filter_matches(s: &mut Cursive) {
+    let privacy = app(s).privacy;
+    let mut items = Vec::new();
+    for (date, entry) in filter_matches(s) {
+        let line = entry.get_text().lines().next().unwrap_or("");
+        let line = if entry.is_private() {
+            "(private)".to_string()
+        } else if privacy {
+            blur(line)
+        } else {
+            line.to_string()
+        };
+        items.push((
+            format!("{}  {:+}  {}", date, entry.get_rating(), line),
+            date,
+        ));
+    }
+
+    let count = match (app(s).filter.is_some(), items.len()) {
+        (false, _) => String::new(),
+        (true, 0) => "no entries match the filter.".to_string(),
+        (true, 1) => "1 entry matches the filter:".to_string(),
+        (true, n) => format!("{} entries match the filter:", n),
+    };
+    s.call_on_name("filter_count", |view: &mut TextView| {
+        view.set_content(count)
+    });
+    s.call_on_name("filter_matches", |view: &mut SelectView<NaiveDate>| {
+        view.clear();
+        view.add_all(items);
+    });
+}
+
+/// Return the days of the journal that match the filter with their entries,
+/// the earliest first.
+///
+/// If a month can't be read, the error is shown and nothing matches.
+fn filter_matches(s: &mut Cursive) -> Vec<(NaiveDate, Entry)> {
+    let (earliest_date, latest_date) = journal(s).earliest_latest();
+    let app = app(s);
+    let found = match &app.filter {
+        Some(filter) => filter.query.find(
+            &mut app.journal,
+            earliest_date.naive_local(),
+            latest_date.naive_local(),
+        ),
+        None => Ok(Vec::new()),
+    };
+    found.unwrap_or_else(|e| {
+        nav::message(s, e.to_string());
+        Vec::new()
+    })
+}
+
+/// Return the preview of the entry of `date` for the entries screen.
+///
+/// Private entries are hidden and everything is blurred in privacy mode,
+/// unless the day was revealed.
+fn entry_preview(s: &mut Cursive, date: &Date<Local>) -> StyledString {
+    let raw = s
+        .call_on_name("raw_text", |view: &mut Checkbox| view.is_checked())
+        .unwrap_or(false);
+    let revealed = app(s).revealed == Some(*date);
+    let privacy = app(s).privacy;
+    let app = app(s);
+    let entry = match app.journal.entry(date) {
+        Ok(entry) => entry,
+        Err(e) => return StyledString::plain(e.to_string()),
+    };
+
+    if let Some(filter) = &app.filter {
+        if !filter.query.matches(date.naive_local(), entry) {
+            return StyledString::plain(format!(
+                "this day doesn't match the filter '{}'.\n\npress </> to change it.",
+                filter.text
+            ));
+        }
+    }
+
+    let mut content = if revealed {
+        preview_content(entry, raw)
+    } else if entry.is_private() {
+        StyledString::plain("this entry is private.\n\npress <r> to show it.")
+    } else if privacy {
+        StyledString::plain(blur(preview_content(entry, raw).source()))
+    } else {
+        preview_content(entry, raw)
+    };
+
+    let capsules = capsule::opening_on(&app.capsules, date.naive_local());
+    if !capsules.is_empty() {
+        let capsules = capsules_content(&capsules);
+        content.append_plain("\n\n");
+        if privacy && !revealed {
+            content.append_plain(blur(capsules.source()));
+        } else {
+            content.append(capsules);
+        }
+    }
+    content
+}
+
+/// Return the preview of an entry, with its text rendered from Markdown
+/// unless `raw` is set.
+fn preview_content(entry: &Entry, raw: bool) -> StyledString {
+    if raw || entry.is_default() {
+        return StyledString::plain(entry.to_string());
+    }
+
+    let mut content = StyledString::plain(format!("rating: {}\n", entry.get_rating()));
+    if let Some(written) = entry.written_note() {
+        content.append_styled(format!("{}\n", written), Style::from(Effect::Italic));
+    }
+    content.append_plain("\n");
+    content.append(markdown::render(entry.get_text()));
+    if !entry.get_habits().is_empty() {
+        content.append_plain(format!(
+            "\n\nhabits done: {}",
+            entry.get_habits().join(", ")
+        ));
+    }
+    if !entry.get_metrics().is_empty() {
+        content.append_plain(format!("\n\n{}", entry.metric_list()));
+    }
+    if !entry.get_attachments().is_empty() {
+        content.append_plain(format!("\n\n{}", entry.attachment_list()));
+    }
+    content
+}
+
+fn update_statistics(s: &mut Cursive, date: &Date<Local>) {
+    let statistics = statistics_content(s, date);
     s.call_on_name("statistics", |view: &mut TextView| {
-        view.set_content(log.get_statistics())
+        view.set_content(statistics)
     });
+
+    let grid = habit_grid_content(s, date);
+    s.call_on_name("habit_grid", |view: &mut TextView| view.set_content(grid));
+}
+
+/// Hide the text but keep its shape, for the privacy mode.
+fn blur(text: &str) -> String {
+    text.chars()
+        .map(|c| if c.is_alphanumeric() { '░' } else { c })
+        .collect()
+}
+
+/// Return the statistics of the ratings and habits for the month of `date`.
+fn statistics_content(s: &mut Cursive, date: &Date<Local>) -> String {
+    let month_year = date.format("%B/%Y").to_string();
+    let statistics = match journal(s).month(&month_year) {
+        Ok(log) => log.get_statistics(),
+        Err(e) => return e.to_string(),
+    };
+    let mut content = statistics.summary(&app(s).config);
+
+    let habits = app(s).config.habits.clone();
+    if !habits.is_empty() {
+        content.push_str("\n\nhabits:");
+        match habits::month_statistics(journal(s), &habits, date.naive_local()) {
+            Ok(habits) => {
+                for habit in habits {
+                    content.push_str(&format!("\n{}", habit));
+                }
+            }
+            Err(e) => content.push_str(&format!("\n{}", e)),
+        }
+    }
+
+    let capsules: Vec<String> = app(s)
+        .capsules
+        .iter()
+        .filter(|capsule| capsule.opens().format("%B/%Y").to_string() == month_year)
+        .map(|capsule| {
+            let state = if capsule.is_open(Local::today().naive_local()) {
+                "open"
+            } else {
+                "sealed"
+            };
+            format!("\n{} ({})", capsule.opens().format("%-d %B"), state)
+        })
+        .collect();
+    if !capsules.is_empty() {
+        content.push_str("\n\ntime capsules:");
+        content.push_str(&capsules.concat());
+    }
+
+    if app(s).privacy {
+        blur(&content)
+    } else {
+        content
+    }
 }
 
-fn edit_entry(s: &mut Cursive, date: &Date<Local>, log: RefMut<MonthLog>) {
+/// Return the grid of the habits done in the month of `date`.
+fn habit_grid_content(s: &mut Cursive, date: &Date<Local>) -> String {
+    let habits = app(s).config.habits.clone();
+    let month_year = date.format("%B/%Y").to_string();
+    let grid = match journal(s).month(&month_year) {
+        Ok(log) => chart::habit_grid(log, &habits),
+        Err(e) => return e.to_string(),
+    };
+
+    if app(s).privacy {
+        blur(&grid)
+    } else {
+        grid
+    }
+}
+
+fn edit_entry(s: &mut Cursive, date: &Date<Local>) {
     let day = date.day();
     let day_clone = day;
     let month_year = date.format("%B/%Y").to_string();
     let month_year_clone = month_year.clone();
-    let selected_entry = log.get_entry(day);
+    let selected_entry = match journal_entry(s, date) {
+        Some(entry) => entry,
+        None => return,
+    };
 
     let content = if selected_entry.is_default() {
         ""
@@ -126,72 +778,1080 @@ fn edit_entry(s: &mut Cursive, date: &Date<Local>, log: RefMut<MonthLog>) {
 
     let mut dialog = Dialog::new()
         .title(date.format("%d %B, %Y").to_string())
-        .content(TextArea::new().content(content).with_name("diary_entry"));
+        .content(editor(content, selected_entry.is_private()));
 
-    dialog.add_button("Update", move |siv| {
-        ask_rating(siv, &month_year, day, "entries".to_string())
+    let month_year_attachments = month_year.clone();
+    let month_year_history = month_year.clone();
+    dialog.add_button("Update", move |siv| ask_rating(siv, &month_year, day));
+    dialog.add_button("Attachments", move |siv| {
+        show_attachments(siv, &month_year_attachments, day)
     });
+    dialog.add_button("History", move |siv| {
+        show_history(siv, &month_year_history, day)
+    });
+    let deleted_date = date.naive_local();
     dialog.add_button("Delete", move |siv| {
-        let mut log = MonthLog::get_month_log(&month_year_clone);
-        log.delete_entry(day_clone);
-        log.save_to_disk();
-        back_to_entries(siv);
+        let deleted = match month_log(siv, &month_year_clone) {
+            Some(log) => log.get_entry(day_clone).clone(),
+            None => return,
+        };
+        match update_month(siv, &month_year_clone, |log| log.delete_entry(day_clone)) {
+            Some(Ok(())) => (),
+            Some(Err(e)) => {
+                nav::message(siv, format!("failed to delete the attachments: {}", e));
+                return;
+            }
+            None => return,
+        }
+        nav::back(siv);
+        if !deleted.is_default() {
+            run_hook(siv, hooks::Event::PostDelete, Some((deleted_date, deleted)));
+        }
+    });
+    dialog.add_button("Back", nav::back);
+
+    nav::open(s, Screen::Editor, dialog.fixed_size(XY { x: 64, y: 20 }));
+}
+
+fn show_attachments(s: &mut Cursive, month_year: &str, day: u32) {
+    let entry = match month_log(s, month_year) {
+        Some(log) => log.get_entry(day).clone(),
+        None => return,
+    };
+
+    if entry.is_default() {
+        nav::message(s, "save the entry before adding attachments.");
+        return;
+    }
+
+    let submit_month_year = month_year.to_string();
+    let add_month_year = month_year.to_string();
+    let open_month_year = month_year.to_string();
+    let remove_month_year = month_year.to_string();
+
+    let list = SelectView::<String>::new()
+        .with_all_str(entry.get_attachments().iter().cloned())
+        .on_submit(move |siv, name: &String| open_attachment(siv, &submit_month_year, day, name));
+
+    nav::overlay(
+        s,
+        Screen::Attachments,
+        Dialog::around(ScrollView::new(list.with_name("attachments")).min_height(5))
+            .title("attachments")
+            .button("Add", move |siv| {
+                ask_attachment_path(siv, &add_month_year, day)
+            })
+            .button("Open", move |siv| {
+                if let Some(name) = selected_attachment(siv) {
+                    open_attachment(siv, &open_month_year, day, &name);
+                }
+            })
+            .button("Remove", move |siv| {
+                if let Some(name) = selected_attachment(siv) {
+                    remove_attachment(siv, &remove_month_year, day, &name);
+                }
+            })
+            .button("Back", nav::back)
+            .min_width(40),
+    );
+}
+
+fn selected_attachment(s: &mut Cursive) -> Option<String> {
+    s.call_on_name("attachments", |view: &mut SelectView<String>| {
+        view.selection()
+    })
+    .flatten()
+    .map(|name| name.to_string())
+}
+
+fn open_attachment(s: &mut Cursive, month_year: &str, day: u32, name: &str) {
+    if let Err(e) = attachment::open(month_year, day, name) {
+        nav::message(s, format!("failed to open attachment: {}", e));
+    }
+}
+
+fn ask_attachment_path(s: &mut Cursive, month_year: &str, day: u32) {
+    let month_year = month_year.to_string();
+
+    nav::overlay(
+        s,
+        Screen::Popup,
+        Dialog::new()
+            .title("path of the file to attach")
+            .content(EditView::new().with_name("attachment_path").min_width(48))
+            .button("Ok", move |siv| {
+                let path = siv
+                    .call_on_name("attachment_path", |view: &mut EditView| view.get_content())
+                    .unwrap();
+                let source = attachment::expand_home(path.trim());
+
+                match attachment::add(&month_year, day, &source) {
+                    Ok(name) => {
+                        let added = update_month(siv, &month_year, |log| {
+                            log.add_attachment(day, name.clone())
+                        });
+                        if added.is_none() {
+                            return;
+                        }
+
+                        nav::back(siv);
+                        siv.call_on_name("attachments", |view: &mut SelectView<String>| {
+                            view.add_item_str(name)
+                        });
+                    }
+                    Err(e) => nav::message(siv, format!("failed to attach file: {}", e)),
+                }
+            })
+            .button("Cancel", nav::back),
+    );
+}
+
+fn remove_attachment(s: &mut Cursive, month_year: &str, day: u32, name: &str) {
+    if let Err(e) = attachment::remove(month_year, day, name) {
+        nav::message(s, format!("failed to remove attachment: {}", e));
+        return;
+    }
+
+    if update_month(s, month_year, |log| log.remove_attachment(day, name)).is_none() {
+        return;
+    }
+
+    s.call_on_name("attachments", |view: &mut SelectView<String>| {
+        if let Some(id) = view.selected_id() {
+            view.remove_item(id);
+        }
+    });
+}
+
+/// Show the previous versions of an entry, with the changes made since each
+/// of them, and let the user restore one.
+fn show_history(s: &mut Cursive, month_year: &str, day: u32) {
+    let entry = match month_log(s, month_year) {
+        Some(log) => log.get_entry(day).clone(),
+        None => return,
+    };
+
+    if entry.get_history().is_empty() {
+        nav::message(s, "this entry has no earlier versions.");
+        return;
+    }
+
+    let mut versions = SelectView::<usize>::new();
+    for (i, revision) in entry.get_history().iter().enumerate().rev() {
+        let saved = match revision.saved() {
+            Some(saved) => saved.format("%-d %b %Y, %H:%M").to_string(),
+            None => "unknown date".to_string(),
+        };
+        versions.add_item(format!("{} ({:+})", saved, revision.get_rating()), i);
+    }
+
+    let select_entry = entry.clone();
+    let versions = versions.on_select(move |siv, i: &usize| {
+        let content = revision_diff(&select_entry, *i);
+        siv.call_on_name("history_diff", |view: &mut TextView| {
+            view.set_content(content)
+        });
     });
-    dialog.add_button("Back", back_to_entries);
 
-    s.pop_layer();
-    s.add_layer(dialog.fixed_size(XY { x: 64, y: 20 }))
+    let last = entry.get_history().len() - 1;
+    let diff = TextView::new(revision_diff(&entry, last)).with_name("history_diff");
+    let layout = LinearLayout::horizontal()
+        .child(Dialog::around(versions.with_name("history_versions")).title("versions"))
+        .child(
+            Dialog::around(ScrollView::new(diff))
+                .title("changes since then")
+                .fixed_size(XY { x: 56, y: 18 }),
+        );
+
+    let month_year = month_year.to_string();
+    nav::open(
+        s,
+        Screen::History,
+        Dialog::around(layout)
+            .title("history")
+            .button("Restore", move |siv| {
+                let index = siv
+                    .call_on_name("history_versions", |view: &mut SelectView<usize>| {
+                        view.selection()
+                    })
+                    .flatten();
+                if let Some(index) = index {
+                    restore_revision(siv, &month_year, day, *index);
+                }
+            })
+            .button("Back", nav::back),
+    );
+}
+
+/// Return the line diff between the version at `index` of the entry's history
+/// and its current text.
+fn revision_diff(entry: &Entry, index: usize) -> StyledString {
+    let revision = &entry.get_history()[index];
+    let mut content = StyledString::plain(format!(
+        "rating: {} -> {}\n\n",
+        revision.get_rating(),
+        entry.get_rating()
+    ));
+
+    for line in diff::lines(revision.get_text(), entry.get_text()) {
+        match line {
+            diff::Line::Same(text) => content.append_plain(format!("  {}\n", text)),
+            diff::Line::Removed(text) => {
+                content.append_styled(format!("- {}\n", text), Color::Dark(BaseColor::Red))
+            }
+            diff::Line::Added(text) => {
+                content.append_styled(format!("+ {}\n", text), Color::Dark(BaseColor::Green))
+            }
+        }
+    }
+    content
+}
+
+fn restore_revision(s: &mut Cursive, month_year: &str, day: u32, index: usize) {
+    if update_month(s, month_year, |log| log.restore_revision(day, index)).is_none() {
+        return;
+    }
+    run_saved_hook(s, month_year, day);
+    let text = match month_log(s, month_year) {
+        Some(log) => log.get_entry(day).get_text().to_string(),
+        None => return,
+    };
+
+    nav::back(s);
+    s.call_on_name("diary_entry", |view: &mut TextArea| view.set_content(text));
+    nav::message(
+        s,
+        "version restored! the replaced one was added to the history.",
+    );
 }
 
 // ============================================================================
 // ----------------------------- New Entry Button -----------------------------
 // ============================================================================
+/// Hour of the day before which a new entry may still be meant for the
+/// previous day (eg when writing after midnight or after a flight).
+const LATE_ENTRY_HOUR: u32 = 6;
+
 fn new_entry(s: &mut Cursive) {
-    let month_log = MonthLog::current_month_log();
-    hide_main_menu(s);
-
-    if month_log.get_todays_entry().is_default() {
-        s.add_layer(
-            Dialog::new()
-                .title("how was your day?")
-                .content(TextArea::new().with_name("diary_entry"))
-                .button("Ok", |siv| {
-                    let today = Local::today();
-                    let (month_year, day) = (today.format("%B/%Y").to_string(), today.day());
-                    ask_rating(siv, &month_year, day, "main".to_string());
-                })
-                .button("Cancel", unhide_main_menu)
-                .fixed_size(XY { x: 64, y: 20 }),
-        );
+    let today = Local::today();
+    let yesterday = today.pred();
+
+    let mut days = Vec::new();
+    if Local::now().hour() < LATE_ENTRY_HOUR {
+        match journal_entry(s, &yesterday) {
+            Some(entry) if entry.is_default() => days.push(yesterday),
+            Some(_) => (),
+            None => return,
+        }
+    }
+    match journal_entry(s, &today) {
+        Some(entry) if entry.is_default() => days.push(today),
+        Some(_) => (),
+        None => return,
+    }
+
+    match days[..] {
+        [] => nav::message(s, "you already have an entry for today."),
+        [date] => write_new_entry(s, date),
+        _ => {
+            let mut dialog =
+                Dialog::text("it's past midnight, which day is this entry for?").title("new entry");
+            for date in days {
+                dialog.add_button(date.format("%A, %-d %B").to_string(), move |siv| {
+                    nav::back(siv);
+                    write_new_entry(siv, date);
+                });
+            }
+            dialog.add_button("Cancel", nav::back);
+            nav::overlay(s, Screen::Popup, dialog);
+        }
+    }
+}
+
+/// Open the editor for a new entry on `date`.
+fn write_new_entry(s: &mut Cursive, date: Date<Local>) {
+    let title = if date == Local::today() {
+        "how was your day?".to_string()
     } else {
-        s.add_layer(
-            Dialog::text("you already have an entry for today.").button("Ok", unhide_main_menu),
+        date.format("how was %A, %-d %B?").to_string()
+    };
+
+    nav::open(
+        s,
+        Screen::Editor,
+        Dialog::new()
+            .title(title)
+            .content(editor("", false))
+            .button("Ok", move |siv| {
+                let (month_year, day) = (date.format("%B/%Y").to_string(), date.day());
+                ask_rating(siv, &month_year, day);
+            })
+            .button("Cancel", nav::back)
+            .fixed_size(XY { x: 64, y: 20 }),
+    );
+}
+
+// ============================================================================
+// --------------------------- Time Capsule Button ----------------------------
+// ============================================================================
+fn write_capsule(s: &mut Cursive) {
+    let next_year = Local::today().naive_local() + Duration::days(365);
+
+    let layout = LinearLayout::vertical()
+        .child(TextView::new("open it on (YYYY-MM-DD):"))
+        .child(
+            EditView::new()
+                .content(next_year.to_string())
+                .with_name("capsule_date"),
+        )
+        .child(TextView::new("\ndear future me,"))
+        .child(TextArea::new().with_name("capsule_text").full_height());
+
+    nav::open(
+        s,
+        Screen::Capsule,
+        Dialog::around(layout)
+            .title("time capsule")
+            .button("Seal", seal_capsule)
+            .button("Cancel", nav::back)
+            .fixed_size(XY { x: 64, y: 20 }),
+    );
+}
+
+fn seal_capsule(s: &mut Cursive) {
+    let date = s
+        .call_on_name("capsule_date", |view: &mut EditView| view.get_content())
+        .unwrap();
+    let text = s
+        .call_on_name("capsule_text", |view: &mut TextArea| {
+            view.get_content().to_string()
+        })
+        .unwrap();
+
+    let opens = match NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d") {
+        Ok(opens) => opens,
+        Err(_) => {
+            nav::message(s, "the date has to be written as YYYY-MM-DD.");
+            return;
+        }
+    };
+    if opens <= Local::today().naive_local() {
+        nav::message(s, "a time capsule has to open after today.");
+        return;
+    }
+    if text.trim().is_empty() {
+        nav::message(s, "the letter is empty.");
+        return;
+    }
+
+    match capsule::add(capsule::Capsule::new(opens, &text)) {
+        Ok(capsules) => {
+            app(s).capsules = capsules;
+            nav::back(s);
+            nav::message(
+                s,
+                opens
+                    .format("sealed! it will open on %-d %B %Y.")
+                    .to_string(),
+            );
+        }
+        Err(e) => nav::message(s, format!("failed to save the time capsule: {}", e)),
+    }
+}
+
+/// Let the user know about the time capsules that opened since they were
+/// last read.
+fn show_opened_capsules(s: &mut Cursive) {
+    let capsules = capsule::unread(&app(s).capsules, Local::today().naive_local());
+    let text = match capsules.len() {
+        0 => return,
+        1 => format!(
+            "a time capsule you wrote on {} has opened!",
+            capsules[0].written().format("%-d %B %Y")
+        ),
+        n => format!("{} time capsules you wrote have opened!", n),
+    };
+
+    nav::overlay(
+        s,
+        Screen::Popup,
+        Dialog::text(text)
+            .title("time capsule")
+            .button("Read", move |siv| {
+                nav::back(siv);
+                match capsule::mark_read(Local::today().naive_local()) {
+                    Ok(capsules) => app(siv).capsules = capsules,
+                    Err(e) => nav::message(siv, format!("failed to save the time capsules: {}", e)),
+                }
+                nav::open(
+                    siv,
+                    Screen::Capsule,
+                    Dialog::around(ScrollView::new(TextView::new(capsules_content(&capsules))))
+                        .title("time capsule")
+                        .button("Back", nav::back)
+                        .fixed_size(XY { x: 64, y: 20 }),
+                );
+            })
+            .button("Later", nav::back),
+    );
+}
+
+/// Return the text of the capsules that are open, and when the others open.
+fn capsules_content(capsules: &[capsule::Capsule]) -> StyledString {
+    let today = Local::today().naive_local();
+    let mut content = StyledString::new();
+
+    for capsule in capsules {
+        let written = capsule.written().format("%-d %B %Y");
+        if !content.is_empty() {
+            content.append_plain("\n\n");
+        }
+        match capsule.text(today) {
+            Ok(Some(text)) => {
+                content.append_styled(
+                    format!("time capsule written on {}", written),
+                    Style::from(Effect::Bold),
+                );
+                content.append_plain("\n");
+                content.append(markdown::render(&text));
+            }
+            Ok(None) => content.append_plain(format!(
+                "a sealed time capsule written on {} opens in {} days.",
+                written,
+                (capsule.opens() - today).num_days()
+            )),
+            Err(e) => content.append_plain(format!(
+                "the time capsule written on {} can't be read: {}",
+                written, e
+            )),
+        }
+    }
+    content
+}
+
+// ============================================================================
+// ---------------------------- On This Day Button ----------------------------
+// ============================================================================
+fn show_on_this_day(s: &mut Cursive) {
+    let today = Local::today();
+    let entries = month_log::on_this_day(today);
+
+    if entries.is_empty() {
+        nav::message(s, "no entries on this day in past years.");
+        return;
+    }
+
+    let mut content = StyledString::new();
+    for (year, entry) in entries {
+        let years_ago = match today.year() - year {
+            1 => "1 year ago".to_string(),
+            n => format!("{} years ago", n),
+        };
+        if !content.is_empty() {
+            content.append_plain("\n\n");
+        }
+        content.append_styled(
+            format!("{} ({})", year, years_ago),
+            Style::from(Effect::Bold),
         );
+        content.append_plain("\n");
+        if entry.is_private() {
+            content.append_plain("this entry is private.");
+        } else if app(s).privacy {
+            content.append_plain(blur(preview_content(&entry, false).source()));
+        } else {
+            content.append(preview_content(&entry, false));
+        }
+    }
+
+    nav::open(
+        s,
+        Screen::OnThisDay,
+        Dialog::around(ScrollView::new(TextView::new(content)))
+            .title(today.format("on this day, %d %B").to_string())
+            .button("Back", nav::back)
+            .fixed_size(XY { x: 64, y: 20 }),
+    );
+}
+
+// ============================================================================
+// ------------------------------- Charts Button ------------------------------
+// ============================================================================
+fn show_charts(s: &mut Cursive) {
+    let today = Local::today().naive_local();
+    let month = Rc::new(Cell::new(today.with_day(1).unwrap()));
+
+    let content = month_chart(s, month.get());
+    let previous = Rc::clone(&month);
+    let next = Rc::clone(&month);
+
+    nav::open(
+        s,
+        Screen::Charts,
+        Dialog::around(TextView::new(content).with_name("chart"))
+            .title("charts")
+            .button("Previous", move |siv| {
+                let (earliest, _) = journal(siv).earliest_latest();
+                let first = (previous.get() - Duration::days(1)).with_day(1).unwrap();
+                if first >= earliest.naive_local() {
+                    previous.set(first);
+                    let content = month_chart(siv, first);
+                    siv.call_on_name("chart", |view: &mut TextView| view.set_content(content));
+                }
+            })
+            .button("Next", move |siv| {
+                let first = (next.get() + Duration::days(31)).with_day(1).unwrap();
+                if first <= Local::today().naive_local() {
+                    next.set(first);
+                    let content = month_chart(siv, first);
+                    siv.call_on_name("chart", |view: &mut TextView| view.set_content(content));
+                }
+            })
+            .button("Analysis", show_analysis)
+            .button("Back", nav::back)
+            .min_width(72),
+    );
+}
+
+/// Show what the habits and metrics have to do with the rating, over a range
+/// of days chosen with the buttons.
+fn show_analysis(s: &mut Cursive) {
+    let content = analysis_content(s, Some(30));
+
+    let mut dialog = Dialog::around(ScrollView::new(
+        TextView::new(content).with_name("analysis"),
+    ))
+    .title("what makes a good day?");
+    for (label, days) in [
+        ("30 days", Some(30)),
+        ("year", Some(365)),
+        ("all time", None),
+    ] {
+        dialog.add_button(label, move |siv| {
+            let content = analysis_content(siv, days);
+            siv.call_on_name("analysis", |view: &mut TextView| view.set_content(content));
+        });
+    }
+    dialog.add_button("Back", nav::back);
+
+    nav::open(s, Screen::Analysis, dialog.fixed_size(XY { x: 80, y: 22 }));
+}
+
+/// Return the analysis of the last `days` days, or of all of them.
+fn analysis_content(s: &mut Cursive, days: Option<i64>) -> String {
+    let today = Local::today().naive_local();
+    let earliest = journal(s).earliest_latest().0.naive_local();
+    let from = match days {
+        Some(days) => (today - Duration::days(days - 1)).max(earliest),
+        None => earliest,
+    };
+
+    let app = app(s);
+    match Analysis::for_range(&mut app.journal, from, today, &app.config) {
+        Ok(analysis) => analysis.to_string(),
+        Err(e) => e.to_string(),
+    }
+}
+
+/// Return the title and the chart of the month starting on `first`.
+fn month_chart(s: &mut Cursive, first: NaiveDate) -> String {
+    let metrics = app(s).config.metrics.clone();
+    let log = match journal(s).month(&first.format("%B/%Y").to_string()) {
+        Ok(log) => log,
+        Err(e) => return e.to_string(),
+    };
+
+    let mut content = first.format("%B %Y\n\n").to_string();
+    content.push_str(&chart::month_chart(log, &metrics));
+    if metrics.is_empty() {
+        content.push_str(&format!(
+            "\nadd your own metrics (eg hours of sleep) to '{}' to chart them here.",
+            config::path().display()
+        ));
+    }
+    content
+}
+
+// ============================================================================
+// ------------------------------ Startup Check -------------------------------
+// ============================================================================
+
+/// Tell the user about problems in the data directory and offer to repair them.
+fn show_problems(s: &mut Cursive, problems: Vec<check::Problem>) {
+    let list = problems
+        .iter()
+        .map(|problem| format!("- {}", problem))
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    nav::overlay(
+        s,
+        Screen::Problems,
+        Dialog::around(ScrollView::new(TextView::new(format!(
+            "some files in the data directory have problems that can crash \
+            lifelog:\n\n{}\n\nrepair them now? the original files will be backed up.",
+            list
+        ))))
+        .title("data check")
+        .button("Repair", move |siv| {
+            let text = match check::repair(&problems) {
+                Ok(backup_dir) => format!(
+                    "repaired! the original files are in '{}'",
+                    backup_dir.display()
+                ),
+                Err(e) => format!("failed to repair the data: {}", e),
+            };
+            nav::back(siv);
+            nav::message(siv, text);
+        })
+        .button("Ignore", nav::back)
+        .max_width(80)
+        .max_height(24),
+    );
+}
+
+// ============================================================================
+// ---------------------------------- Merge -----------------------------------
+// ============================================================================
+
+/// Start a TUI that only lets the user resolve the conflicts of `merge`.
+///
+/// Used by the `merge` command, the program quits once the merge is saved.
+fn resolve_conflicts(merge: Merge) {
+    let mut siv = cursive::default();
+    let theme_warning = load_theme(&mut siv);
+    let (config, config_warning) = Config::load_or_default();
+    siv.set_user_data(App::new(config));
+
+    show_merge(&mut siv, Rc::new(RefCell::new(Some(merge))), true);
+    for warning in [theme_warning, config_warning].into_iter().flatten() {
+        nav::message(&mut siv, warning);
+    }
+    siv.run();
+}
+
+/// Show the next conflict of `merge` or, if all of them are resolved, save it.
+///
+/// The merge is taken out of its `Option` to be saved, the buttons of the
+/// conflict dialog still share it at that point.
+///
+/// If `quit_after` is set, the conflicts are the only screen and the program
+/// quits when the merge is done. Otherwise the conflicts are shown on top of
+/// the other screens and closing them (eg with `<ESC>`) cancels the merge.
+fn show_merge(s: &mut Cursive, merge: Rc<RefCell<Option<Merge>>>, quit_after: bool) {
+    nav::close(s, Screen::Conflict);
+    let remaining = match merge.borrow().as_ref() {
+        Some(merge) => merge.conflicts().len(),
+        // already saved, eg after a double click on the last button
+        None => return,
+    };
+
+    if remaining == 0 {
+        let merge = merge.borrow_mut().take().unwrap();
+        let files = merge.merged_files();
+        let skipped: String = merge
+            .errors
+            .iter()
+            .map(|(path, e)| format!("\nskipped '{}': {}", path.display(), e))
+            .collect();
+        let text = match merge.save() {
+            Ok(()) if files > 0 => format!("merge saved, {} conflict copies removed.", files),
+            Ok(()) => "merge saved.".to_string(),
+            Err(e) => format!("failed to save the merge: {}", e),
+        } + &skipped;
+
+        if quit_after {
+            nav::replace(
+                s,
+                Screen::Popup,
+                Dialog::text(text).button("Ok", Cursive::quit),
+            );
+        } else {
+            nav::message(s, text);
+        }
+        return;
+    }
+
+    let (title, ours, theirs) = {
+        let merge = merge.borrow();
+        let conflict = &merge.as_ref().unwrap().conflicts()[0];
+        (
+            format!(
+                "{} {} ({} left)",
+                conflict.day,
+                conflict.month_year.replace('/', " "),
+                remaining
+            ),
+            preview_content(&conflict.ours, true),
+            preview_content(&conflict.theirs, true),
+        )
+    };
+
+    let side = |title: &str, content: StyledString| {
+        Dialog::around(ScrollView::new(TextView::new(content)))
+            .title(title)
+            .fixed_size(XY { x: 40, y: 18 })
+    };
+    let layout = LinearLayout::horizontal()
+        .child(side("this device", ours))
+        .child(side("other copy", theirs));
+
+    let mut dialog = Dialog::around(layout).title(format!("conflict on {}", title));
+    for (label, resolution) in [
+        ("Keep this device's", Resolution::Ours),
+        ("Keep other", Resolution::Theirs),
+        ("Keep both", Resolution::Both),
+    ] {
+        let merge = Rc::clone(&merge);
+        dialog.add_button(label, move |siv| {
+            if let Some(merge) = merge.borrow_mut().as_mut() {
+                merge.resolve_next(resolution);
+            }
+            show_merge(siv, Rc::clone(&merge), quit_after);
+        });
+    }
+
+    if quit_after {
+        dialog.add_button("Cancel", Cursive::quit);
+        nav::replace(s, Screen::Conflict, dialog);
+    } else {
+        dialog.add_button("Cancel", nav::back);
+        nav::open(s, Screen::Conflict, dialog);
+    }
+}
+
+// ============================================================================
+// ------------------------------ Journals Button -----------------------------
+// ============================================================================
+fn show_journals(s: &mut Cursive) {
+    let current = profile::current();
+    let mut journals = SelectView::<String>::new().on_submit(switch_journal);
+    for name in profile::names() {
+        let label = if name == current {
+            format!("{} (open)", name)
+        } else {
+            name.clone()
+        };
+        journals.add_item(label, name);
+    }
+
+    nav::overlay(
+        s,
+        Screen::Journals,
+        Dialog::around(journals.with_name("journals"))
+            .title("journals")
+            .button("Open", |siv| {
+                let name = siv
+                    .call_on_name("journals", |view: &mut SelectView<String>| view.selection())
+                    .flatten();
+                if let Some(name) = name {
+                    switch_journal(siv, &name);
+                }
+            })
+            .button("New", ask_journal_name)
+            .button("Back", nav::back)
+            .min_width(32),
+    );
+}
+
+fn ask_journal_name(s: &mut Cursive) {
+    nav::overlay(
+        s,
+        Screen::Popup,
+        Dialog::new()
+            .title("name of the new journal")
+            .content(
+                EditView::new()
+                    .on_submit(create_journal)
+                    .with_name("journal_name")
+                    .min_width(32),
+            )
+            .button("Ok", |siv| {
+                let name = siv
+                    .call_on_name("journal_name", |view: &mut EditView| view.get_content())
+                    .unwrap();
+                create_journal(siv, &name);
+            })
+            .button("Cancel", nav::back),
+    );
+}
+
+fn create_journal(s: &mut Cursive, name: &str) {
+    match profile::create(name.trim()) {
+        Ok(()) => switch_journal(s, name.trim()),
+        Err(e) => nav::message(s, format!("failed to create the journal: {}", e)),
+    }
+}
+
+/// Close all the screens and open the journal called `name`.
+fn switch_journal(s: &mut Cursive, name: &str) {
+    match profile::set_current(name) {
+        Ok(()) => open_journal(s),
+        Err(e) => nav::message(s, e),
     }
 }
 
+// ============================================================================
+// ------------------------------ Settings Button -----------------------------
+// ============================================================================
+fn show_settings(s: &mut Cursive) {
+    let selected = theme::selected();
+    let warning = theme::load(&selected).err().unwrap_or_default();
+
+    let mut themes = SelectView::<&str>::new().on_select(|siv, name: &&str| {
+        // preview the theme, it's only kept if it's saved
+        let warning = match theme::load(name) {
+            Ok(theme) => {
+                siv.set_theme(theme);
+                String::new()
+            }
+            Err(e) => e,
+        };
+        siv.call_on_name("theme_warning", |view: &mut TextView| {
+            view.set_content(warning)
+        });
+    });
+    for name in theme::names() {
+        themes.add_item(name, name);
+    }
+    if let Some(i) = theme::names().iter().position(|name| *name == selected) {
+        themes.set_selection(i);
+    }
+
+    let layout = LinearLayout::vertical()
+        .child(TextView::new("theme:"))
+        .child(themes.with_name("themes"))
+        .child(TextView::new(warning).with_name("theme_warning"))
+        .child(TextView::new(format!(
+            "\nyou can also make your own theme in '{}'.",
+            theme::custom_path().display()
+        )))
+        .child(TextView::new(lock_status()).with_name("lock_status"));
+
+    nav::open(
+        s,
+        Screen::Settings,
+        Dialog::around(layout)
+            .title("settings")
+            .button("Save", |siv| {
+                let name = siv
+                    .call_on_name("themes", |view: &mut SelectView<&str>| view.selection())
+                    .flatten();
+                if let Some(name) = name {
+                    if let Err(e) = theme::load(&name) {
+                        nav::message(siv, e);
+                        return;
+                    }
+                    if let Err(e) = theme::save_selected(&name) {
+                        nav::message(siv, format!("failed to save the theme: {}", e));
+                        return;
+                    }
+                }
+                nav::back(siv);
+            })
+            .button("Passphrase", ask_passphrase)
+            .button("Back", nav::back)
+            .max_width(72),
+    );
+    // go back to the saved theme when leaving without saving
+    nav::on_close(s, |siv| {
+        load_theme(siv);
+    });
+}
+
+fn ask_passphrase(s: &mut Cursive) {
+    let mut layout = LinearLayout::vertical();
+    if lock::is_set() {
+        layout.add_child(TextView::new("current passphrase:"));
+        layout.add_child(EditView::new().secret().with_name("current_passphrase"));
+    }
+    layout.add_child(TextView::new("new passphrase:"));
+    layout.add_child(EditView::new().secret().with_name("new_passphrase"));
+    layout.add_child(TextView::new("repeat the new passphrase:"));
+    layout.add_child(EditView::new().secret().with_name("repeated_passphrase"));
+    layout.add_child(TextView::new(
+        "\nleave it empty to open the journal without a passphrase.",
+    ));
+
+    nav::overlay(
+        s,
+        Screen::Popup,
+        Dialog::around(layout)
+            .title("passphrase")
+            .button("Ok", save_passphrase)
+            .button("Cancel", nav::back)
+            .max_width(48),
+    );
+}
+
+fn save_passphrase(s: &mut Cursive) {
+    let mut field = |name: &str| {
+        s.call_on_name(name, |view: &mut EditView| view.get_content())
+            .map(|content| content.to_string())
+    };
+    let current = field("current_passphrase");
+    let new = field("new_passphrase").unwrap();
+    let repeated = field("repeated_passphrase").unwrap();
+
+    if current.is_some_and(|current| !lock::verify(&current)) {
+        nav::message(s, "the current passphrase is wrong.");
+        return;
+    }
+    if new != repeated {
+        nav::message(s, "the new passphrases are not the same.");
+        return;
+    }
+
+    let result = if new.is_empty() {
+        if lock::is_set() {
+            lock::remove()
+        } else {
+            Ok(())
+        }
+    } else {
+        lock::set(&new)
+    };
+    match result {
+        Ok(()) => {
+            nav::back(s);
+            let status = lock_status();
+            s.call_on_name("lock_status", |view: &mut TextView| {
+                view.set_content(status)
+            });
+        }
+        Err(e) => nav::message(s, format!("failed to save the passphrase: {}", e)),
+    }
+}
+
+fn lock_status() -> String {
+    if lock::is_set() {
+        "\nthe journal is locked with a passphrase.".to_string()
+    } else {
+        "\nthe journal is not locked.".to_string()
+    }
+}
+
+// ============================================================================
+// ------------------------------- Lock Screen --------------------------------
+// ============================================================================
+/// Hide all the screens behind a dialog that asks for the passphrase.
+///
+/// The dialog is not a screen of the navigator, so it can't be closed with
+/// `<ESC>` and it stays on top of whatever was open.
+fn show_lock(s: &mut Cursive) {
+    if app(s).locked {
+        return;
+    }
+    app(s).locked = true;
+
+    let dialog = Dialog::new()
+        .title("lifelog is locked")
+        .content(
+            LinearLayout::vertical()
+                .child(TextView::new("passphrase:"))
+                .child(
+                    EditView::new()
+                        .secret()
+                        .on_submit(unlock)
+                        .with_name("passphrase")
+                        .min_width(32),
+                )
+                .child(TextView::new("").with_name("lock_warning")),
+        )
+        .button("Unlock", |siv| {
+            let passphrase = siv
+                .call_on_name("passphrase", |view: &mut EditView| view.get_content())
+                .unwrap();
+            unlock(siv, &passphrase);
+        })
+        .button("Quit", Cursive::quit);
+
+    // the stack centers the dialog, a fullscreen layer is painted over the
+    // screens below
+    let mut stack = StackView::new();
+    stack.add_layer(dialog);
+    s.add_fullscreen_layer(stack.full_screen().with_name("lock"));
+}
+
+fn unlock(s: &mut Cursive, passphrase: &str) {
+    if !lock::verify(passphrase) {
+        s.call_on_name("passphrase", |view: &mut EditView| view.set_content(""));
+        s.call_on_name("lock_warning", |view: &mut TextView| {
+            view.set_content("wrong passphrase.")
+        });
+        return;
+    }
+
+    if let Some(position) = s.screen_mut().find_layer_from_name("lock") {
+        s.screen_mut().remove_layer(position);
+    }
+    app(s).locked = false;
+    lock::touch();
+}
+
+/// Lock the journal if it has a passphrase and nothing was done for the
+/// time set in the config.
+fn lock_if_idle(s: &mut Cursive) {
+    let minutes = match app(s).config.lock_after_minutes {
+        Some(minutes) => minutes,
+        None => return,
+    };
+    if lock::is_set() && lock::idle_for().as_secs() >= minutes * 60 {
+        show_lock(s);
+    }
+}
+
+// ============================================================================
+// ---------------------------------- Hooks -----------------------------------
+// ============================================================================
+/// Run the hook of `event` (if there is one) on another thread, so a slow
+/// command doesn't freeze the screen.
+///
+/// Hooks run after the data is saved, a failing hook only shows a message.
+fn run_hook(s: &mut Cursive, event: hooks::Event, entry: Option<(NaiveDate, Entry)>) {
+    let entry = entry.as_ref().map(|(date, entry)| (*date, entry));
+    let hook = match Hook::new(&app(s).config.hooks, event, entry) {
+        Some(hook) => hook,
+        None => return,
+    };
+
+    let sink = s.cb_sink().clone();
+    hook.spawn(move |e| {
+        // nothing to report to if lifelog was closed in the meantime
+        let _ = sink.send(Box::new(move |siv| nav::message(siv, e)));
+    });
+}
+
+/// Run the post-save hook with the entry of `day` as it was saved.
+fn run_saved_hook(s: &mut Cursive, month_year: &str, day: u32) {
+    let log = match month_log(s, month_year) {
+        Some(log) => log,
+        None => return,
+    };
+    let date = log.first_day().with_day(day).unwrap();
+    let entry = log.get_entry(day).clone();
+    run_hook(s, hooks::Event::PostSave, Some((date, entry)));
+}
+
 // ============================================================================
 // ------------------------------- About Button -------------------------------
 // ============================================================================
 fn show_about(s: &mut Cursive) {
-    s.add_layer(
-        Dialog::info(format!(
+    nav::overlay(
+        s,
+        Screen::About,
+        Dialog::text(format!(
             "a simple diary that you can use from your terminal.\n\n\
         - this is designed for you to only have 1 entry per day, in which \
         you rate how your day went.\n\
         - you can press <q> anytime to quit the program.\n\
         - all the diary entries are saved in: '{}'\n\
-        - you can customize the program by creating your own theme file at: '{}'\n\
+        - you can pick a theme in the settings, or create your own theme file at: '{}'\n\
         (for more info on customization, check 'https://tinyurl.com/fpc2yau2')",
             month_log::data_dir().to_str().unwrap(),
-            month_log::data_dir()
-                .parent()
-                .unwrap()
-                .join("theme")
-                .join("theme.toml")
-                .to_str()
-                .unwrap()
+            theme::custom_path().to_str().unwrap()
         ))
+        .button("Ok", nav::back)
         .max_width(80),
     );
 }
@@ -199,70 +1859,372 @@ fn show_about(s: &mut Cursive) {
 // ============================================================================
 // ---------------------------------- Common ----------------------------------
 // ============================================================================
-fn hide_main_menu(s: &mut Cursive) {
-    s.call_on_name("main", |view: &mut HideableView<Dialog>| {
-        view.hide();
-    });
+/// Return the data shared by all the screens.
+fn app(s: &mut Cursive) -> &mut App {
+    s.user_data().expect("app is not set up")
 }
 
-fn unhide_main_menu(s: &mut Cursive) {
-    s.pop_layer();
-    s.call_on_name("main", |view: &mut HideableView<Dialog>| {
-        view.unhide();
-    });
+/// Return the journal cache kept in the cursive user data.
+fn journal(s: &mut Cursive) -> &mut Journal {
+    &mut app(s).journal
+}
+
+/// Return the `MonthLog` for the given `month_year` from the journal.
+///
+/// If its file can't be read, the error is shown in a popup and `None` is
+/// returned, so the caller can give up on what it was doing.
+fn month_log<'a>(s: &'a mut Cursive, month_year: &str) -> Option<&'a MonthLog> {
+    if let Err(e) = journal(s).month(month_year) {
+        nav::message(s, e.to_string());
+        return None;
+    }
+    journal(s).month(month_year).ok()
+}
+
+/// Return the entry for the given date from the journal, see `month_log`.
+fn journal_entry<'a>(s: &'a mut Cursive, date: &Date<Local>) -> Option<&'a Entry> {
+    let month_year = date.format("%B/%Y").to_string();
+    month_log(s, &month_year).map(|log| log.get_entry(date.day()))
+}
+
+/// Change the `MonthLog` for the given `month_year` with `f` and save it.
+///
+/// Return what `f` returned, or show the error and return `None` if the
+/// month couldn't be read or saved.
+fn update_month<F, R>(s: &mut Cursive, month_year: &str, f: F) -> Option<R>
+where
+    F: FnOnce(&mut MonthLog) -> R,
+{
+    match journal(s).update(month_year, f) {
+        Ok(result) => Some(result),
+        Err(e) => {
+            nav::message(s, e.to_string());
+            None
+        }
+    }
 }
 
-fn back_to_entries(s: &mut Cursive) {
-    s.pop_layer();
-    show_entries(s);
+/// Return the text area of the editor, with the box to mark the entry as
+/// private below it.
+fn editor(text: &str, private: bool) -> LinearLayout {
+    LinearLayout::vertical()
+        .child(
+            TextArea::new()
+                .content(text)
+                .with_name("diary_entry")
+                .full_height(),
+        )
+        .child(
+            LinearLayout::horizontal()
+                .child(
+                    Checkbox::new()
+                        .with_checked(private)
+                        .with_name("private_entry"),
+                )
+                .child(TextView::new(" private (hidden in previews)")),
+        )
 }
 
-fn ask_rating(s: &mut Cursive, month_year: &str, day: u32, exit_to: String) {
+fn ask_rating(s: &mut Cursive, month_year: &str, day: u32) {
     let text = s
         .call_on_name("diary_entry", |view: &mut TextArea| {
             view.get_content().to_string()
         })
         .unwrap();
+    let private = s
+        .call_on_name("private_entry", |view: &mut Checkbox| view.is_checked())
+        .unwrap();
 
     let mut options = RadioGroup::new();
     let mut linear_layout = LinearLayout::vertical();
 
-    let ratings = [
-        (2, "+2 (awesome)"),
-        (1, "+1"),
-        (0, " 0 (okay)"),
-        (-1, "-1"),
-        (-2, "-2 (horrible)"),
-    ];
+    let labels: Vec<(i8, String)> = (-2..=2)
+        .rev()
+        .map(|rating| (rating, app(s).config.rating_label(rating)))
+        .collect();
+
+    // keep the rating of an existing entry, otherwise suggest one that fits
+    // the tone of the text
+    let suggestion = sentiment::suggest_rating(&text);
+    let entry = match month_log(s, month_year) {
+        Some(log) => log.get_entry(day),
+        None => return,
+    };
+    let selected = if entry.is_default() {
+        suggestion.unwrap_or(0)
+    } else {
+        entry.get_rating()
+    };
 
-    for (value, label) in ratings {
-        linear_layout.add_child(options.button(value, label));
+    for (value, label) in labels {
+        // line the numbers up, 0 has no sign
+        let label = if value == 0 {
+            format!(" {}", label)
+        } else {
+            label
+        };
+        let button = options.button(value, label);
+        if value == selected {
+            linear_layout.add_child(button.selected());
+        } else {
+            linear_layout.add_child(button);
+        }
+    }
+    if let Some(suggestion) = suggestion {
+        linear_layout.add_child(TextView::new(format!(
+            "\nthe text sounds like a {:+} day.",
+            suggestion
+        )));
     }
     let month_year = month_year.to_string();
+    let config = &app(s).config;
+    let has_more_steps = !config.habits.is_empty() || !config.metrics.is_empty();
 
-    s.pop_layer();
-    s.add_layer(
+    nav::open(
+        s,
+        Screen::Rating,
         Dialog::new()
             .title("how was your day?")
             .content(linear_layout)
-            .button("Save", move |siv| {
-                let rating = *options.selection();
-                save_entry(siv, &month_year, day, rating, &text, &exit_to);
-            }),
+            .button(if has_more_steps { "Next" } else { "Save" }, move |siv| {
+                let draft = Draft {
+                    month_year: month_year.clone(),
+                    day,
+                    rating: *options.selection(),
+                    text: text.clone(),
+                    private,
+                    habits: None,
+                    metrics: None,
+                };
+                next_step(siv, draft);
+            })
+            .button("Back", nav::back),
     )
 }
 
-fn save_entry(s: &mut Cursive, month_year: &str, day: u32, rating: i8, text: &str, exit_to: &str) {
-    let mut month_log = MonthLog::get_month_log(month_year);
-    month_log.update_entry(day, rating, text.to_string());
-    month_log.save_to_disk();
+/// An entry that is being written, before it is saved.
+#[derive(Clone)]
+struct Draft {
+    month_year: String,
+    day: u32,
+    rating: i8,
+    text: String,
+    private: bool,
+    /// Habits done that day, `None` until the habits step is done.
+    habits: Option<Vec<String>>,
+    /// Values of the custom metrics, `None` until the metrics step is done.
+    metrics: Option<BTreeMap<String, MetricValue>>,
+}
 
-    let dialog = match exit_to {
-        "main" => Dialog::text("entry saved!").button("Ok", unhide_main_menu),
-        "entries" => Dialog::text("entry updated!").button("Ok", back_to_entries),
-        _ => panic!("exit_to was not equal to either 'main' or 'entries'."),
+/// Open the next step of writing an entry after the rating, or save it.
+fn next_step(s: &mut Cursive, draft: Draft) {
+    let config = &app(s).config;
+    if draft.habits.is_none() && !config.habits.is_empty() {
+        ask_habits(s, draft);
+    } else if draft.metrics.is_none() && !config.metrics.is_empty() {
+        ask_metrics(s, draft);
+    } else {
+        save_entry(s, draft);
+    }
+}
+
+/// Ask which of the habits defined in the config file were done that day.
+fn ask_habits(s: &mut Cursive, draft: Draft) {
+    let habits = app(s).config.habits.clone();
+    let done = match month_log(s, &draft.month_year) {
+        Some(log) => log.get_entry(draft.day).get_habits().to_vec(),
+        None => return,
+    };
+
+    let mut checklist = LinearLayout::vertical();
+    for (i, habit) in habits.iter().enumerate() {
+        checklist.add_child(
+            LinearLayout::horizontal()
+                .child(
+                    Checkbox::new()
+                        .with_checked(done.contains(habit))
+                        .with_name(format!("habit-{}", i)),
+                )
+                .child(TextView::new(format!(" {}", habit))),
+        );
+    }
+
+    let is_last = app(s).config.metrics.is_empty();
+    nav::open(
+        s,
+        Screen::Habits,
+        Dialog::around(checklist)
+            .title("which habits did you keep?")
+            .button(if is_last { "Save" } else { "Next" }, move |siv| {
+                let mut draft = draft.clone();
+                let checked = habits.iter().enumerate().filter(|(i, _)| {
+                    siv.call_on_name(&format!("habit-{}", i), |view: &mut Checkbox| {
+                        view.is_checked()
+                    })
+                    .unwrap_or(false)
+                });
+                draft.habits = Some(checked.map(|(_, habit)| habit.clone()).collect());
+                next_step(siv, draft);
+            })
+            .button("Back", nav::back),
+    );
+}
+
+/// Ask for the values of the custom metrics defined in the config file.
+fn ask_metrics(s: &mut Cursive, draft: Draft) {
+    let metrics = app(s).config.metrics.clone();
+    let current = match month_log(s, &draft.month_year) {
+        Some(log) => log.get_entry(draft.day).get_metrics().clone(),
+        None => return,
     };
 
-    s.pop_layer();
-    s.add_layer(dialog);
+    let width = metrics
+        .iter()
+        .map(|metric| metric.name.chars().count())
+        .max()
+        .unwrap_or(0)
+        + 2;
+
+    let mut form = LinearLayout::vertical();
+    for (i, metric) in metrics.iter().enumerate() {
+        let name = format!("metric-{}", i);
+        let value = current.get(&metric.name);
+        let mut row = LinearLayout::horizontal()
+            .child(TextView::new(format!("{:width$}", metric.name)).fixed_width(width));
+
+        match &metric.kind {
+            MetricKind::Number { unit, .. } => {
+                let content = match value {
+                    Some(MetricValue::Number(number)) => number.to_string(),
+                    _ => String::new(),
+                };
+                row.add_child(
+                    EditView::new()
+                        .content(content)
+                        .with_name(name)
+                        .fixed_width(10),
+                );
+                if let Some(unit) = unit {
+                    row.add_child(TextView::new(format!(" {}", unit)));
+                }
+            }
+            MetricKind::Boolean => {
+                let checked = matches!(value, Some(MetricValue::Boolean(true)));
+                row.add_child(Checkbox::new().with_checked(checked).with_name(name));
+            }
+            MetricKind::Choice { options } => {
+                let mut select = SelectView::<Option<String>>::new().popup();
+                select.add_item("-", None);
+                for option in options {
+                    select.add_item(option.clone(), Some(option.clone()));
+                }
+                if let Some(MetricValue::Choice(choice)) = value {
+                    if let Some(i) = options.iter().position(|option| option == choice) {
+                        select.set_selection(i + 1);
+                    }
+                }
+                row.add_child(select.with_name(name));
+            }
+        }
+        form.add_child(row);
+    }
+
+    nav::open(
+        s,
+        Screen::Metrics,
+        Dialog::around(form)
+            .title("anything else to note?")
+            .button("Save", move |siv| {
+                let mut values = current.clone();
+                for (i, metric) in metrics.iter().enumerate() {
+                    let name = format!("metric-{}", i);
+                    let value = match &metric.kind {
+                        MetricKind::Number { .. } => {
+                            let content = siv
+                                .call_on_name(&name, |view: &mut EditView| view.get_content())
+                                .unwrap();
+                            let content = content.trim();
+                            if content.is_empty() {
+                                None
+                            } else if let Ok(number) = content.parse() {
+                                Some(MetricValue::Number(number))
+                            } else {
+                                nav::message(
+                                    siv,
+                                    format!("{}: '{}' is not a number.", metric.name, content),
+                                );
+                                return;
+                            }
+                        }
+                        MetricKind::Boolean => siv
+                            .call_on_name(&name, |view: &mut Checkbox| view.is_checked())
+                            .map(MetricValue::Boolean),
+                        MetricKind::Choice { .. } => siv
+                            .call_on_name(&name, |view: &mut SelectView<Option<String>>| {
+                                view.selection()
+                            })
+                            .flatten()
+                            .and_then(|choice| (*choice).clone())
+                            .map(MetricValue::Choice),
+                    };
+
+                    if let Some(Err(e)) = value.as_ref().map(|value| metric.check(value)) {
+                        nav::message(siv, format!("{}.", e));
+                        return;
+                    }
+                    match value {
+                        Some(value) => values.insert(metric.name.clone(), value),
+                        None => values.remove(&metric.name),
+                    };
+                }
+                let mut draft = draft.clone();
+                draft.metrics = Some(values);
+                next_step(siv, draft);
+            })
+            .button("Back", nav::back),
+    );
+}
+
+/// Save the entry and go back to the screen the editor was opened from.
+///
+/// The habits and custom metrics are only changed if the draft has them.
+fn save_entry(s: &mut Cursive, draft: Draft) {
+    let Draft {
+        month_year,
+        day,
+        rating,
+        text,
+        private,
+        habits,
+        metrics,
+    } = draft;
+
+    let saved = update_month(s, &month_year, |log| {
+        let is_new = log.get_entry(day).is_default();
+        log.update_entry(day, rating, text);
+        log.set_private(day, private);
+        if let Some(habits) = habits {
+            log.set_habits(day, habits);
+        }
+        if let Some(metrics) = metrics {
+            log.set_metrics(day, metrics);
+        }
+        is_new
+    });
+    let is_new = match saved {
+        Some(is_new) => is_new,
+        // the editor stays open, so the text is not lost
+        None => return,
+    };
+
+    nav::close(s, Screen::Editor);
+    run_saved_hook(s, &month_year, day);
+    nav::message(
+        s,
+        if is_new {
+            "entry saved!"
+        } else {
+            "entry updated!"
+        },
+    );
 }
@@ -0,0 +1,323 @@
+use cursive::theme::{Effect, PaletteColor, Style};
+use cursive::utils::markup::StyledString;
+use pulldown_cmark::{CodeBlockKind, Event, HeadingLevel, Options, Parser, Tag};
+
+/// Render the Markdown in `text` into HTML, with the same extensions as the
+/// preview pane.
+pub fn to_html(text: &str) -> String {
+    let options = Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS;
+    let mut html = String::new();
+    pulldown_cmark::html::push_html(&mut html, Parser::new_ext(text, options));
+    html
+}
+
+/// Render the Markdown in `text` into XHTML, for EPUB books.
+///
+/// Same as `to_html` except that HTML written in the text is escaped, since
+/// it is usually not valid XML.
+pub fn to_xhtml(text: &str) -> String {
+    let options = Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS;
+    let events = Parser::new_ext(text, options).map(|event| match event {
+        Event::Html(html) => Event::Text(html),
+        event => event,
+    });
+    let mut xhtml = String::new();
+    pulldown_cmark::html::push_html(&mut xhtml, events);
+    xhtml
+}
+
+/// Render the Markdown in `text` into a `StyledString` for the preview pane.
+///
+/// Headings, emphasis, lists, code, block quotes, links and rules are
+/// styled; anything else (eg tables, html) is shown as plain text.
+/// Line breaks are kept as they were typed since diary entries are usually
+/// not wrapped by hand.
+pub fn render(text: &str) -> StyledString {
+    let options = Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS;
+    let mut renderer = Renderer::new();
+
+    for event in Parser::new_ext(text, options) {
+        renderer.event(event);
+    }
+
+    renderer.output
+}
+
+/// Walks the events produced by `pulldown_cmark` and builds the styled output.
+struct Renderer {
+    output: StyledString,
+    /// Styles of all the currently open inline/block elements.
+    styles: Vec<Style>,
+    /// One element per open list, `Some(n)` is the next number of an ordered list.
+    lists: Vec<Option<u64>>,
+    /// Number of currently open block quotes.
+    quotes: usize,
+    /// Whether the next piece of text starts a new line (and needs a prefix).
+    line_start: bool,
+    /// Whether a list item marker was just written.
+    item_start: bool,
+}
+
+impl Renderer {
+    fn new() -> Self {
+        Renderer {
+            output: StyledString::new(),
+            styles: Vec::new(),
+            lists: Vec::new(),
+            quotes: 0,
+            line_start: true,
+            item_start: false,
+        }
+    }
+
+    fn event(&mut self, event: Event) {
+        match event {
+            Event::Start(tag) => self.start(tag),
+            Event::End(tag) => self.end(tag),
+            Event::Text(text) | Event::Html(text) => self.text(&text),
+            Event::Code(code) => {
+                self.styles.push(code_style());
+                self.text(&code);
+                self.styles.pop();
+            }
+            Event::SoftBreak | Event::HardBreak => self.newline(),
+            Event::Rule => {
+                self.block_break();
+                self.text(&"─".repeat(32));
+                self.newline();
+            }
+            Event::TaskListMarker(checked) => self.text(if checked { "[x] " } else { "[ ] " }),
+            Event::FootnoteReference(name) => self.text(&format!("[^{}]", name)),
+        }
+    }
+
+    fn start(&mut self, tag: Tag) {
+        match tag {
+            Tag::Paragraph => {
+                if self.lists.is_empty() {
+                    self.block_break();
+                } else if !self.item_start && !self.line_start {
+                    self.newline();
+                }
+            }
+            Tag::Heading(level, ..) => {
+                self.block_break();
+                self.styles.push(heading_style(level));
+            }
+            Tag::BlockQuote => {
+                self.block_break();
+                self.quotes += 1;
+                self.styles.push(Style::from(Effect::Italic));
+            }
+            Tag::CodeBlock(_) => {
+                self.block_break();
+                self.styles.push(code_style());
+            }
+            Tag::List(start) => {
+                if self.lists.is_empty() {
+                    self.block_break();
+                } else if !self.line_start {
+                    self.newline();
+                }
+                self.lists.push(start);
+            }
+            Tag::Item => {
+                if !self.line_start {
+                    self.newline();
+                }
+                let marker = match self.lists.last_mut() {
+                    Some(Some(number)) => {
+                        *number += 1;
+                        format!("{}. ", *number - 1)
+                    }
+                    _ => "• ".to_string(),
+                };
+                self.prefix(self.lists.len() - 1);
+                self.output
+                    .append_styled(marker, Style::from(PaletteColor::Highlight));
+                self.item_start = true;
+            }
+            Tag::Emphasis => self.styles.push(Style::from(Effect::Italic)),
+            Tag::Strong => self.styles.push(Style::from(Effect::Bold)),
+            Tag::Strikethrough => self.styles.push(Style::from(Effect::Strikethrough)),
+            Tag::Link(..) | Tag::Image(..) => self.styles.push(Style::from(Effect::Underline)),
+            Tag::TableRow | Tag::TableHead => {
+                if !self.line_start {
+                    self.newline();
+                }
+            }
+            Tag::TableCell => self.text("| "),
+            Tag::Table(_) | Tag::FootnoteDefinition(_) => self.block_break(),
+        }
+    }
+
+    fn end(&mut self, tag: Tag) {
+        match tag {
+            Tag::Heading(..) => {
+                self.styles.pop();
+                self.newline();
+            }
+            Tag::BlockQuote => {
+                self.styles.pop();
+                self.quotes -= 1;
+                if !self.line_start {
+                    self.newline();
+                }
+            }
+            Tag::CodeBlock(kind) => {
+                self.styles.pop();
+                if let CodeBlockKind::Fenced(_) = kind {
+                    // fenced code blocks end with a line break of their own
+                    self.line_start = true;
+                } else if !self.line_start {
+                    self.newline();
+                }
+            }
+            Tag::List(_) => {
+                self.lists.pop();
+            }
+            Tag::Item | Tag::Paragraph => {
+                if !self.line_start {
+                    self.newline();
+                }
+            }
+            Tag::Emphasis | Tag::Strong | Tag::Strikethrough => {
+                self.styles.pop();
+            }
+            Tag::Link(_, url, _) | Tag::Image(_, url, _) => {
+                self.styles.pop();
+                if !url.is_empty() {
+                    self.text(&format!(" ({})", url));
+                }
+            }
+            Tag::TableCell => self.text(" "),
+            Tag::Table(_) | Tag::TableHead | Tag::TableRow | Tag::FootnoteDefinition(_) => (),
+        }
+    }
+
+    /// Append text with the currently active style, prefixing every new line.
+    fn text(&mut self, text: &str) {
+        for (i, line) in text.split('\n').enumerate() {
+            if i > 0 {
+                self.newline();
+            }
+            if line.is_empty() {
+                continue;
+            }
+            if self.line_start {
+                self.prefix(self.lists.len());
+            }
+            self.output
+                .append_styled(line.to_string(), Style::merge(&self.styles));
+            self.item_start = false;
+        }
+    }
+
+    /// Write the quote bars and list indentation at the start of a line.
+    fn prefix(&mut self, indent: usize) {
+        if self.quotes > 0 {
+            self.output.append_styled(
+                "│ ".repeat(self.quotes),
+                Style::from(PaletteColor::Secondary),
+            );
+        }
+        self.output.append_plain("  ".repeat(indent));
+        self.line_start = false;
+    }
+
+    fn newline(&mut self) {
+        self.output.append_plain("\n");
+        self.line_start = true;
+        self.item_start = false;
+    }
+
+    /// Separate a new block from the previous one with an empty line.
+    fn block_break(&mut self) {
+        if self.output.is_empty() {
+            return;
+        }
+        if !self.line_start {
+            self.newline();
+        }
+        if !self.output.source().ends_with("\n\n") {
+            self.newline();
+        }
+    }
+}
+
+fn heading_style(level: HeadingLevel) -> Style {
+    match level {
+        HeadingLevel::H1 => Style::merge(&[
+            Style::from(Effect::Bold),
+            Style::from(Effect::Underline),
+            Style::from(PaletteColor::TitlePrimary),
+        ]),
+        HeadingLevel::H2 => Style::merge(&[
+            Style::from(Effect::Bold),
+            Style::from(PaletteColor::TitlePrimary),
+        ]),
+        _ => Style::from(Effect::Bold),
+    }
+}
+
+fn code_style() -> Style {
+    Style::from(PaletteColor::Secondary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Return the style of the first span of `rendered` that is `content`.
+    fn style_of(rendered: &StyledString, content: &str) -> Style {
+        *rendered
+            .spans()
+            .find(|span| span.content == content)
+            .unwrap()
+            .attr
+    }
+
+    #[test]
+    fn keeps_line_breaks_as_typed() {
+        assert_eq!(render("just text").source(), "just text\n");
+        assert_eq!(
+            render("first line\nsecond line\n\nnew paragraph").source(),
+            "first line\nsecond line\n\nnew paragraph\n"
+        );
+    }
+
+    #[test]
+    fn styles_headings_and_emphasis() {
+        let rendered = render("# Title\nsome *text* and **more** `code`");
+        assert_eq!(rendered.source(), "Title\n\nsome text and more code\n");
+        assert!(style_of(&rendered, "Title").effects.contains(Effect::Bold));
+        assert!(style_of(&rendered, "text").effects.contains(Effect::Italic));
+        assert!(style_of(&rendered, "more").effects.contains(Effect::Bold));
+        assert_eq!(style_of(&rendered, "code"), code_style());
+    }
+
+    #[test]
+    fn lists_are_marked_and_indented() {
+        assert_eq!(
+            render("- a\n- b\n  - c\n\n1. x\n2. y").source(),
+            "• a\n• b\n  • c\n\n1. x\n2. y\n"
+        );
+        assert_eq!(
+            render("- [x] done\n- [ ] todo").source(),
+            "• [x] done\n• [ ] todo\n"
+        );
+    }
+
+    #[test]
+    fn quotes_code_links_and_rules() {
+        assert_eq!(
+            render("> quoted\n> more\n\nafter").source(),
+            "│ quoted\n│ more\n\nafter\n"
+        );
+        assert_eq!(render("```\ncode\n```\nend").source(), "code\n\nend\n");
+        assert_eq!(
+            render("[link](https://a.b)\n\n---").source(),
+            format!("link (https://a.b)\n\n{}\n", "─".repeat(32))
+        );
+    }
+}
@@ -0,0 +1,417 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use cursive::reexports::time::Month as tMonth;
+
+use super::attachment;
+use super::month_log::{self, Entry, MonthLog};
+
+/// Merge of entries from another data directory or from conflict copies of
+/// month files into the data directory.
+///
+/// Days that only have an entry on one side are merged automatically. Days
+/// with a different entry on both sides are collected as `Conflict`s, which
+/// need to be resolved before the merge is saved.
+pub struct Merge {
+    /// Month logs of the data directory with the other entries merged in.
+    logs: Vec<MonthLog>,
+    /// Days that have a different entry on both sides.
+    conflicts: Vec<Conflict>,
+    /// Conflict copies that can be deleted once the merge is saved.
+    merged_files: Vec<PathBuf>,
+    /// Attachments folder of the other data directory (if merging one).
+    other_attachments: Option<PathBuf>,
+    /// Files that could not be read, with the reason why.
+    pub errors: Vec<(PathBuf, io::Error)>,
+}
+
+/// A day with a different entry in the data directory and the other side.
+pub struct Conflict {
+    /// Month and year of the day (eg August/2022).
+    pub month_year: String,
+    pub day: u32,
+    /// Entry in the data directory.
+    pub ours: Entry,
+    /// Entry in the other data directory or conflict copy.
+    pub theirs: Entry,
+}
+
+/// How to resolve a `Conflict`.
+#[derive(Clone, Copy)]
+pub enum Resolution {
+    /// Keep the entry of the data directory.
+    Ours,
+    /// Keep the other entry.
+    Theirs,
+    /// Keep the text of both, the rating of the data directory's entry is kept.
+    Both,
+}
+
+impl Merge {
+    fn new() -> Self {
+        Merge {
+            logs: Vec::new(),
+            conflicts: Vec::new(),
+            merged_files: Vec::new(),
+            other_attachments: None,
+            errors: Vec::new(),
+        }
+    }
+
+    /// Merge all the month files of another data directory.
+    ///
+    /// `other` is expected to have the same layout as `month_log::data_dir()`.
+    /// Attachments that don't exist in the data directory are copied when the
+    /// merge is saved.
+    pub fn from_dir(other: &Path) -> io::Result<Self> {
+        let mut merge = Self::new();
+
+        for year in fs::read_dir(other)? {
+            let year = year?;
+            let is_year = year
+                .file_name()
+                .to_str()
+                .is_some_and(|name| name.parse::<i32>().is_ok());
+            if !is_year {
+                continue;
+            }
+
+            for file in fs::read_dir(year.path())? {
+                let path = file?.path();
+                let is_month_file = path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .and_then(|name| name.strip_suffix(".json"))
+                    .is_some_and(|month| month.parse::<tMonth>().is_ok());
+
+                if is_month_file {
+                    merge.add_file(path, false);
+                }
+            }
+        }
+
+        let attachments = other.join("attachments");
+        if attachments.exists() {
+            merge.other_attachments = Some(attachments);
+        }
+
+        Ok(merge)
+    }
+
+    /// Merge all the conflict copies found in the data directory.
+    pub fn from_conflict_files() -> Self {
+        let mut merge = Self::new();
+        for path in conflict_files(&mut merge.errors) {
+            merge.add_file(path, true);
+        }
+        merge
+    }
+
+    /// Merge the month file at `path` into the corresponding data dir log.
+    fn add_file(&mut self, path: PathBuf, delete_after: bool) {
+        let theirs = match MonthLog::read_file(&path) {
+            Ok(log) => log,
+            Err(e) => {
+                self.errors.push((path, e));
+                return;
+            }
+        };
+
+        let month_year = theirs.month_year();
+        let index = match self
+            .logs
+            .iter()
+            .position(|log| log.month_year() == month_year)
+        {
+            Some(index) => index,
+            None => match MonthLog::get_month_log(&month_year) {
+                Ok(log) => {
+                    self.logs.push(log);
+                    self.logs.len() - 1
+                }
+                Err(e) => {
+                    self.errors.push((path, e));
+                    return;
+                }
+            },
+        };
+
+        let ours = &mut self.logs[index];
+        for day in ours.merge(&theirs) {
+            self.conflicts.push(Conflict {
+                month_year: month_year.clone(),
+                day,
+                ours: ours.get_entry(day).clone(),
+                theirs: theirs.get_entry(day).clone(),
+            });
+        }
+
+        if delete_after {
+            self.merged_files.push(path);
+        }
+    }
+
+    /// Return the days that still have to be resolved.
+    pub fn conflicts(&self) -> &[Conflict] {
+        &self.conflicts
+    }
+
+    /// Return the number of files that were merged or will be deleted.
+    pub fn merged_files(&self) -> usize {
+        self.merged_files.len()
+    }
+
+    /// Resolve the first remaining conflict.
+    pub fn resolve_next(&mut self, resolution: Resolution) {
+        if self.conflicts.is_empty() {
+            return;
+        }
+        let conflict = self.conflicts.remove(0);
+
+        let entry = match resolution {
+            Resolution::Ours => return,
+            Resolution::Theirs => conflict.theirs,
+            Resolution::Both => conflict.ours.combined_with(&conflict.theirs),
+        };
+
+        let log = self
+            .logs
+            .iter_mut()
+            .find(|log| log.month_year() == conflict.month_year)
+            .expect("conflict without a month log");
+        log.set_entry(conflict.day, entry);
+    }
+
+    /// Resolve all the remaining conflicts the same way.
+    pub fn resolve_all(&mut self, resolution: Resolution) {
+        while !self.conflicts.is_empty() {
+            self.resolve_next(resolution);
+        }
+    }
+
+    /// Save the merged logs to disk, copy the other side's attachments and
+    /// delete the merged conflict copies.
+    ///
+    /// Conflicts that were not resolved keep the data directory's entry.
+    pub fn save(self) -> io::Result<()> {
+        for log in &self.logs {
+            log.save_to_disk()?;
+        }
+
+        if let Some(other) = &self.other_attachments {
+            attachment::copy_missing(other, &month_log::data_dir().join("attachments"))?;
+        }
+
+        for path in &self.merged_files {
+            fs::remove_file(path)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Return the conflict copies of month files in the data directory.
+///
+/// Sync tools create these when a file was changed on two devices, eg
+/// `October.sync-conflict-20221005-120000-ABCDEFG.json` (Syncthing) or
+/// `October (conflicted copy 2022-10-05).json` (Dropbox, Nextcloud).
+/// Directories that can't be read are skipped and added to `errors`.
+pub fn conflict_files(errors: &mut Vec<(PathBuf, io::Error)>) -> Vec<PathBuf> {
+    let data_dir = month_log::data_dir();
+    let mut files = Vec::new();
+
+    let years = match fs::read_dir(&data_dir) {
+        Ok(years) => years,
+        Err(e) => {
+            errors.push((data_dir, e));
+            return files;
+        }
+    };
+    for year in years {
+        let year = match year {
+            Ok(year) => year.path(),
+            Err(e) => {
+                errors.push((data_dir.clone(), e));
+                continue;
+            }
+        };
+        if !year.is_dir() || year.file_name() == Some("attachments".as_ref()) {
+            continue;
+        }
+
+        let paths = match fs::read_dir(&year) {
+            Ok(paths) => paths,
+            Err(e) => {
+                errors.push((year, e));
+                continue;
+            }
+        };
+        for file in paths {
+            let path = match file {
+                Ok(file) => file.path(),
+                Err(e) => {
+                    errors.push((year.clone(), e));
+                    continue;
+                }
+            };
+            let name = path.file_name().and_then(|name| name.to_str());
+            if name.is_some_and(is_conflict_file) {
+                files.push(path);
+            }
+        }
+    }
+
+    files.sort();
+    files
+}
+
+/// Check if `name` is the file name of a conflict copy of a month file.
+pub fn is_conflict_file(name: &str) -> bool {
+    let stem = match name.strip_suffix(".json") {
+        Some(stem) => stem,
+        None => return false,
+    };
+    let month = stem.split(['.', ' ']).next().unwrap();
+
+    month != stem && month.parse::<tMonth>().is_ok() && stem.contains("conflict")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::month_log::tests::TempDir;
+
+    /// Return a log for June 2022 with the given entries.
+    fn june(days: &[(u32, Entry)]) -> MonthLog {
+        let mut log: MonthLog =
+            serde_json::from_str(r#"{"month": "June", "year": 2022, "entries": []}"#).unwrap();
+        log.repair("June", 2022);
+        for (day, entry) in days {
+            log.set_entry(*day, entry.clone());
+        }
+        log
+    }
+
+    /// Write `log` as a file called `name` in the year directory of `dir`.
+    fn write(dir: &Path, name: &str, log: &MonthLog) -> PathBuf {
+        let path = dir.join(log.year().to_string()).join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, serde_json::to_string(log).unwrap()).unwrap();
+        path
+    }
+
+    fn saved_june() -> MonthLog {
+        MonthLog::get_month_log("June/2022").unwrap()
+    }
+
+    #[test]
+    fn other_directories_are_merged_with_their_attachments() {
+        let temp = TempDir::create();
+        let mut ours = june(&[(1, Entry::new(1, "ours")), (3, Entry::new(0, "same"))]);
+        ours.add_attachment(3, "a.png".to_string());
+        ours.save_to_disk().unwrap();
+
+        let other = temp.path().join("other");
+        let mut theirs = june(&[(2, Entry::new(2, "theirs")), (3, Entry::new(0, "same"))]);
+        theirs.add_attachment(3, "b.png".to_string());
+        write(&other, "June.json", &theirs);
+        let attachment = other.join("attachments/2022/June/03/b.png");
+        fs::create_dir_all(attachment.parent().unwrap()).unwrap();
+        fs::write(&attachment, "png").unwrap();
+        fs::write(other.join("2022/notes.txt"), "not a month").unwrap();
+
+        let merge = Merge::from_dir(&other).unwrap();
+        assert!(merge.conflicts().is_empty());
+        assert!(merge.errors.is_empty());
+        merge.save().unwrap();
+
+        let log = saved_june();
+        assert_eq!(log.get_entry(1).get_text(), "ours");
+        assert_eq!(log.get_entry(2).get_text(), "theirs");
+        assert_eq!(log.get_entry(3).get_attachments(), ["a.png", "b.png"]);
+        assert!(attachment::day_dir("June/2022", 3).join("b.png").is_file());
+        // the other directory is left as it is
+        assert!(other.join("2022/June.json").is_file());
+    }
+
+    #[test]
+    fn unreadable_files_are_skipped() {
+        let temp = TempDir::create();
+        let other = temp.path().join("other");
+        fs::create_dir_all(other.join("2022")).unwrap();
+        fs::write(other.join("2022/June.json"), "{bad").unwrap();
+
+        let merge = Merge::from_dir(&other).unwrap();
+        assert_eq!(merge.errors.len(), 1);
+        assert_eq!(merge.errors[0].0, other.join("2022/June.json"));
+        assert!(Merge::from_dir(&temp.path().join("missing")).is_err());
+    }
+
+    #[test]
+    fn conflict_copies_are_resolved_and_deleted() {
+        let _temp = TempDir::create();
+        let data_dir = month_log::data_dir();
+        june(&[
+            (1, Entry::new(1, "ours 1")),
+            (2, Entry::new(1, "ours 2")),
+            (3, Entry::new(1, "ours 3")),
+        ])
+        .save_to_disk()
+        .unwrap();
+        let copy = write(
+            &data_dir,
+            "June (conflicted copy 2022-06-04).json",
+            &june(&[
+                (1, Entry::new(-1, "theirs 1")),
+                (2, Entry::new(-1, "theirs 2")),
+                (3, Entry::new(-1, "theirs 3")),
+                (4, Entry::new(2, "theirs 4")),
+            ]),
+        );
+
+        assert_eq!(conflict_files(&mut Vec::new()), vec![copy.clone()]);
+        let mut merge = Merge::from_conflict_files();
+        assert_eq!(merge.conflicts().len(), 3);
+        assert_eq!(merge.merged_files(), 1);
+
+        merge.resolve_next(Resolution::Ours);
+        merge.resolve_next(Resolution::Theirs);
+        merge.resolve_all(Resolution::Both);
+        assert!(merge.conflicts().is_empty());
+        merge.save().unwrap();
+
+        let log = saved_june();
+        assert!(log.get_entry(1) == &Entry::new(1, "ours 1"));
+        assert!(log.get_entry(2) == &Entry::new(-1, "theirs 2"));
+        assert!(log.get_entry(3) == &Entry::new(1, "ours 3\n\n---\n\ntheirs 3"));
+        assert!(log.get_entry(4) == &Entry::new(2, "theirs 4"));
+        assert!(!copy.exists());
+        assert!(conflict_files(&mut Vec::new()).is_empty());
+    }
+
+    #[test]
+    fn unresolved_conflicts_keep_our_entry() {
+        let _temp = TempDir::create();
+        june(&[(1, Entry::new(1, "ours"))]).save_to_disk().unwrap();
+        write(
+            &month_log::data_dir(),
+            "June.sync-conflict-20220602-101010-ABCDEFG.json",
+            &june(&[(1, Entry::new(2, "theirs"))]),
+        );
+
+        let merge = Merge::from_conflict_files();
+        assert_eq!(merge.conflicts().len(), 1);
+        merge.save().unwrap();
+        assert_eq!(saved_june().get_entry(1).get_text(), "ours");
+    }
+
+    #[test]
+    fn recognizes_conflict_copies() {
+        assert!(is_conflict_file("June (conflicted copy 2022-06-02).json"));
+        assert!(is_conflict_file("June.sync-conflict-20220602-101010.json"));
+        assert!(!is_conflict_file("June.json"));
+        assert!(!is_conflict_file("Juno (conflicted copy).json"));
+        assert!(!is_conflict_file("June (conflicted copy).txt"));
+    }
+}
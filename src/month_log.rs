@@ -1,16 +1,21 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
+use std::env;
 use std::fmt;
 use std::fs::{self, File};
-use std::io::Read;
+use std::io::{self, Read};
 use std::path::{Path, PathBuf};
 
-use chrono::{Datelike, Local};
+use chrono::{Date, DateTime, Datelike, FixedOffset, Local, NaiveDate};
 use cursive::reexports::time::{util::days_in_year_month, Month as tMonth};
-use etcetera::base_strategy::{choose_base_strategy, BaseStrategy};
 use serde::{Deserialize, Serialize};
 
+#[cfg(not(test))]
+use super::profile;
+use super::statistics::Statistics;
+use super::{attachment, calendar};
+
 /// An object containing diary entries for a given month.
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct MonthLog {
     /// The name of the month in capitalized case (eg `November`)
     month: String,
@@ -45,23 +50,23 @@ impl MonthLog {
         }
     }
 
-    /// Construct a `MonthLog` from JSON file.
-    fn from_file<P: AsRef<Path>>(path: P) -> Self {
+    /// Try to construct a `MonthLog` from JSON file.
+    ///
+    /// A missing or malformed file is returned as an error.
+    pub fn read_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
         let mut data = String::new();
+        File::open(path)?.read_to_string(&mut data)?;
 
-        File::open(path)
-            .expect("failed to open data file")
-            .read_to_string(&mut data)
-            .expect("failed to read data file");
-
-        serde_json::from_str(&data).expect("failed to parse json file")
+        Ok(serde_json::from_str(&data)?)
     }
 
     /// Return the `MonthLog` for the given `month_year` (eg August/2022).
     ///
     /// If the JSON file from which the object can be constructed does not exist,
-    /// construct a brand new object.
-    pub fn get_month_log(month_year: &str) -> Self {
+    /// construct a brand new object. A file that can't be read, or has more
+    /// entries than the month has days, is an error which tells to run
+    /// `lifelog check`. A file with too few entries gets empty ones.
+    pub fn get_month_log(month_year: &str) -> io::Result<Self> {
         let [month, year]: [&str; 2] = month_year
             .split('/')
             .collect::<Vec<&str>>()
@@ -72,22 +77,35 @@ impl MonthLog {
 
         let path = data_directory.join(year);
         if !path.exists() {
-            return Self::new(month, year);
+            return Ok(Self::new(month, year));
         }
 
         let data_file = path.join(format!("{}.json", month));
 
-        if data_file.exists() {
-            Self::from_file(data_file)
-        } else {
-            Self::new(month, year)
+        if !data_file.exists() {
+            return Ok(Self::new(month, year));
         }
-    }
 
-    /// Return the `MonthLog` for the current month and year.
-    pub fn current_month_log() -> Self {
-        let month_year = Local::today().format("%B/%Y").to_string();
-        Self::get_month_log(&month_year)
+        let unreadable = |kind, e: &dyn fmt::Display| {
+            io::Error::new(
+                kind,
+                format!(
+                    "failed to read {}: {}, run `lifelog check` to repair it",
+                    data_file.display(),
+                    e
+                ),
+            )
+        };
+        let mut log = Self::read_file(&data_file).map_err(|e| unreadable(e.kind(), &e))?;
+
+        let month_days = days_in_year_month(year.parse().unwrap(), month.parse().unwrap());
+        if log.entries.len() > month_days as usize {
+            let problem = format!("{} entries for {} days", log.entries.len(), month_days);
+            return Err(unreadable(io::ErrorKind::InvalidData, &problem));
+        }
+        // missing days are empty, `check` reports them
+        log.entries.resize_with(month_days as usize, Entry::default);
+        Ok(log)
     }
 
     /// Return the user entry for the given day.
@@ -95,20 +113,145 @@ impl MonthLog {
         &self.entries[day as usize - 1]
     }
 
-    /// Return the user entry for today.
-    pub fn get_todays_entry(&self) -> &Entry {
-        let day = Local::today().day();
-        self.get_entry(day)
-    }
-
     /// Update diary entry for the given day.
+    ///
+    /// Attachments of the entry are kept as they are. If the entry was empty,
+    /// the current time and time zone are recorded as its creation time,
+    /// otherwise the previous rating and text are kept in its history.
     pub fn update_entry(&mut self, day: u32, rating: i8, text: String) {
-        self.entries[day as usize - 1] = Entry { rating, text };
+        let entry = &mut self.entries[day as usize - 1];
+        if entry.rating == rating && entry.text == text {
+            return;
+        }
+
+        let now = Local::now();
+        let now = now.with_timezone(now.offset());
+        if entry.is_default() {
+            entry.written = Some(now);
+            entry.zone = current_zone();
+        } else {
+            entry.history.push(Revision {
+                rating: entry.rating,
+                text: entry.text.clone(),
+                saved: entry.modified.or(entry.written),
+            });
+            entry.modified = Some(now);
+        }
+        entry.rating = rating;
+        entry.text = text;
+    }
+
+    /// Replace the values of the custom metrics of the given day's entry.
+    pub fn set_metrics(&mut self, day: u32, metrics: BTreeMap<String, MetricValue>) {
+        self.entries[day as usize - 1].metrics = metrics;
+    }
+
+    /// Mark the given day's entry as private or not.
+    pub fn set_private(&mut self, day: u32, private: bool) {
+        self.entries[day as usize - 1].private = private;
+    }
+
+    /// Replace the habits done on the given day.
+    pub fn set_habits(&mut self, day: u32, habits: Vec<String>) {
+        self.entries[day as usize - 1].habits = habits;
+    }
+
+    /// Make the version at `index` of the history of the given day's entry
+    /// the current one.
+    ///
+    /// The current version is added to the history, so nothing is lost.
+    pub fn restore_revision(&mut self, day: u32, index: usize) {
+        let revision = self.entries[day as usize - 1].history[index].clone();
+        self.update_entry(day, revision.rating, revision.text);
     }
 
     /// Change the entry for the given day to a default entry.
-    pub fn delete_entry(&mut self, day: u32) {
+    ///
+    /// The attachments of the entry are deleted from disk first, the entry is
+    /// kept if they can't be.
+    pub fn delete_entry(&mut self, day: u32) -> io::Result<()> {
+        attachment::remove_all(&self.month_year(), day)?;
         self.entries[day as usize - 1] = Entry::default();
+        Ok(())
+    }
+
+    /// Record that a file named `name` was attached to the entry of the given day.
+    pub fn add_attachment(&mut self, day: u32, name: String) {
+        self.entries[day as usize - 1].attachments.push(name);
+    }
+
+    /// Forget about the attachment named `name` of the given day's entry.
+    pub fn remove_attachment(&mut self, day: u32, name: &str) {
+        self.entries[day as usize - 1]
+            .attachments
+            .retain(|attachment| attachment != name);
+    }
+
+    /// Replace the entry for the given day.
+    pub fn set_entry(&mut self, day: u32, entry: Entry) {
+        self.entries[day as usize - 1] = entry;
+    }
+
+    /// Merge the entries of `other` (a log for the same month) into this one.
+    ///
+    /// Days that only have an entry in `other` are copied over. Return the days
+    /// on which both logs have a different entry, these are left untouched.
+    pub fn merge(&mut self, other: &MonthLog) -> Vec<u32> {
+        let mut conflicts = Vec::new();
+
+        for (i, (ours, theirs)) in self.entries.iter_mut().zip(&other.entries).enumerate() {
+            if theirs.is_default() {
+                continue;
+            }
+            if ours.is_default() {
+                *ours = theirs.clone();
+            } else if ours.rating == theirs.rating && ours.text == theirs.text {
+                ours.add_attachments(&theirs.attachments);
+            } else {
+                conflicts.push(i as u32 + 1);
+            }
+        }
+
+        conflicts
+    }
+
+    /// Return the name of the month (eg `November`).
+    pub fn month(&self) -> &str {
+        &self.month
+    }
+
+    /// Return the full gregorian year (eg `2022`).
+    pub fn year(&self) -> u32 {
+        self.year
+    }
+
+    /// Return all the entries of the month, the first one is for day 1.
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    /// Make the `MonthLog` valid for the given month and year.
+    ///
+    /// The month and year are overwritten, missing days get a default entry,
+    /// entries past the end of the month are dropped and invalid ratings are
+    /// brought into the `-2..=2` range.
+    pub fn repair(&mut self, month: &str, year: u32) {
+        let month_days = days_in_year_month(year as i32, month.parse::<tMonth>().unwrap());
+
+        self.month = month.to_string();
+        self.year = year;
+        self.entries
+            .resize_with(month_days as usize, Entry::default);
+
+        for entry in &mut self.entries {
+            if !entry.has_valid_rating() {
+                entry.rating = if entry.rating == 42 {
+                    0
+                } else {
+                    entry.rating.clamp(-2, 2)
+                };
+            }
+        }
     }
 
     /// Return the path at which this `MonthLog` should be saved.
@@ -121,14 +264,18 @@ impl MonthLog {
     /// Create and save JSON file to disk by serializing data with `serde`.
     ///
     /// If the directory for the year's entries does not exist, create it.
-    pub fn save_to_disk(&self) {
-        let data = serde_json::to_string(self).unwrap();
+    /// The file is written next to the month file first and then renamed,
+    /// so other processes never read a half-written month.
+    pub fn save_to_disk(&self) -> io::Result<()> {
+        let data = serde_json::to_string(self)?;
         let path = self.path();
         let parent_dir = path.parent().unwrap();
         if !parent_dir.exists() {
-            fs::create_dir(parent_dir).expect("failed to create directory in data dir");
+            fs::create_dir(parent_dir)?;
         };
-        fs::write(path, data).unwrap();
+        let temporary = parent_dir.join(format!(".{}.json.tmp", self.month));
+        fs::write(&temporary, data)?;
+        fs::rename(temporary, path)
     }
 
     /// Return a string with the object's month and year (eg August/2022).
@@ -136,33 +283,66 @@ impl MonthLog {
         format!("{}/{}", &self.month, &self.year)
     }
 
+    /// Return the first day of the month this `MonthLog` is for.
+    pub fn first_day(&self) -> NaiveDate {
+        let month = self.month.parse::<tMonth>().unwrap() as u32;
+        NaiveDate::from_ymd(self.year as i32, month, 1)
+    }
+
+    /// Return the number of days (and so entries) in the month.
+    pub fn days(&self) -> u32 {
+        self.entries.len() as u32
+    }
+
     /// Get statistics for the MonthLog (how many days are rated what number).
-    pub fn get_statistics(&self) -> String {
-        let mut data: HashMap<i8, u8> = HashMap::new();
+    pub fn get_statistics(&self) -> Statistics {
+        Statistics::for_month_log(self)
+    }
+}
 
-        for i in -2..3 {
-            data.insert(i, 0);
-        }
-        data.insert(42, 0);
+/// Collect the entries written on the same month and day as `date` in all the
+/// previous years, starting with the most recent one.
+///
+/// Years without an entry for that day are skipped. For February 29 only leap
+/// years can have an entry.
+pub fn on_this_day(date: Date<Local>) -> Vec<(i32, Entry)> {
+    let month = date.format("%B").to_string();
+
+    calendar::years()
+        .into_iter()
+        .rev()
+        .filter(|year| *year < date.year())
+        .filter_map(|year| {
+            // skip unreadable files, `check` reports them
+            let path = data_dir()
+                .join(year.to_string())
+                .join(format!("{}.json", month));
+            let log = MonthLog::read_file(path).ok()?;
+            let entry = log.entries.get(date.day() as usize - 1)?;
+            if entry.is_default() {
+                None
+            } else {
+                Some((year, entry.clone()))
+            }
+        })
+        .collect()
+}
 
-        for entry in &self.entries {
-            data.entry(entry.get_rating()).and_modify(|e| *e += 1);
+/// Return the name of the time zone the program runs in.
+///
+/// The `TZ` environment variable is used if set, since that's what the local
+/// time is computed with, otherwise the system's time zone.
+fn current_zone() -> Option<String> {
+    match env::var("TZ") {
+        Ok(zone) if !zone.trim_start_matches(':').is_empty() => {
+            Some(zone.trim_start_matches(':').to_string())
         }
-
-        format!(
-            "+2 (awesome) - {}\n\
-            +1 - {}\n\
-            0 (okay) - {}\n\
-            -1 - {}\n\
-            -2 (horrible) - {}\n\n\
-            no data - {}",
-            data[&2], data[&1], data[&0], data[&-1], data[&-2], data[&42]
-        )
+        _ => iana_time_zone::get_timezone().ok(),
     }
 }
 
 /// An entry for a given day with the rating for the day and some user text.
-#[derive(Serialize, Deserialize, PartialEq)]
+#[derive(Serialize, Deserialize, PartialEq, Clone)]
 pub struct Entry {
     /// Rating for a given day.
     ///
@@ -182,6 +362,81 @@ pub struct Entry {
     /// Text for the diary entry.
     /// If the entry is empty, field will be "wow, such empty"
     text: String,
+    /// File names of the attachments of the entry.
+    ///
+    /// The files themselves are kept in the folder returned by
+    /// `attachment::day_dir`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    attachments: Vec<String>,
+    /// Local time (with its UTC offset) at which the entry was first saved.
+    ///
+    /// Entries written before this was recorded don't have it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    written: Option<DateTime<FixedOffset>>,
+    /// IANA name of the time zone the entry was written in (eg `Europe/Paris`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    zone: Option<String>,
+    /// Local time at which the entry was last changed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    modified: Option<DateTime<FixedOffset>>,
+    /// Names of the habits (see `config::Config::habits`) done that day.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    habits: Vec<String>,
+    /// Values of the custom metrics (see `config::Metric`), by metric name.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    metrics: BTreeMap<String, MetricValue>,
+    /// Previous versions of the entry, the oldest one first.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    history: Vec<Revision>,
+    /// Whether the text is hidden in previews until it is asked for.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    private: bool,
+}
+
+/// The value of a custom metric for a day.
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+#[serde(untagged)]
+pub enum MetricValue {
+    Boolean(bool),
+    Number(f64),
+    Choice(String),
+}
+
+impl fmt::Display for MetricValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MetricValue::Boolean(true) => write!(f, "yes"),
+            MetricValue::Boolean(false) => write!(f, "no"),
+            MetricValue::Number(number) => write!(f, "{}", number),
+            MetricValue::Choice(choice) => write!(f, "{}", choice),
+        }
+    }
+}
+
+/// A previous version of an `Entry`.
+#[derive(Serialize, Deserialize, PartialEq, Clone)]
+pub struct Revision {
+    rating: i8,
+    text: String,
+    /// Local time at which this version was saved, if known.
+    saved: Option<DateTime<FixedOffset>>,
+}
+
+impl Revision {
+    /// Get the rating of this version.
+    pub fn get_rating(&self) -> i8 {
+        self.rating
+    }
+
+    /// Get the text of this version.
+    pub fn get_text(&self) -> &str {
+        &self.text
+    }
+
+    /// Get the local time at which this version was saved.
+    pub fn saved(&self) -> Option<DateTime<FixedOffset>> {
+        self.saved
+    }
 }
 
 impl Default for Entry {
@@ -190,6 +445,14 @@ impl Default for Entry {
         Entry {
             rating: 42,
             text: "wow, such empty".to_string(),
+            attachments: Vec::new(),
+            written: None,
+            zone: None,
+            modified: None,
+            habits: Vec::new(),
+            metrics: BTreeMap::new(),
+            history: Vec::new(),
+            private: false,
         }
     }
 }
@@ -217,7 +480,24 @@ impl fmt::Display for Entry {
 "#
             )
         } else {
-            write!(f, "rating: {}\n\n{}", self.rating, self.text)
+            write!(f, "rating: {}", self.rating)?;
+            if let Some(written) = self.written_note() {
+                write!(f, "\n{}", written)?;
+            }
+            if let Some(modified) = self.modified_note() {
+                write!(f, "\n{}", modified)?;
+            }
+            write!(f, "\n\n{}", self.text)?;
+            if !self.habits.is_empty() {
+                write!(f, "\n\nhabits done: {}", self.habits.join(", "))?;
+            }
+            if !self.metrics.is_empty() {
+                write!(f, "\n\n{}", self.metric_list())?;
+            }
+            if !self.attachments.is_empty() {
+                write!(f, "\n\n{}", self.attachment_list())?;
+            }
+            Ok(())
         }
     }
 }
@@ -228,21 +508,171 @@ impl Entry {
         &self.text
     }
 
+    /// Return the tags of the text: the words that start with `#` (eg
+    /// `#hiking`), in lowercase and without duplicates.
+    ///
+    /// Markdown headings are not tags, their `#` is followed by a space.
+    pub fn tags(&self) -> Vec<String> {
+        let mut tags: Vec<String> = Vec::new();
+        for word in self.text.split_whitespace() {
+            let tag: String = match word.strip_prefix('#') {
+                Some(rest) => rest
+                    .chars()
+                    .take_while(|c| c.is_alphanumeric() || *c == '-' || *c == '_')
+                    .collect::<String>()
+                    .to_lowercase(),
+                None => continue,
+            };
+            if !tag.is_empty() && !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+        tags
+    }
+
     /// Get the value stored in the rating field.
-    fn get_rating(&self) -> i8 {
+    pub fn get_rating(&self) -> i8 {
         self.rating
     }
 
+    /// Get the file names of the entry's attachments.
+    pub fn get_attachments(&self) -> &[String] {
+        &self.attachments
+    }
+
+    /// Return when and in which time zone the entry was written, in the local
+    /// time of that place (eg `written on 18 October 2022 at 23:40 (UTC+09:00,
+    /// Asia/Tokyo)`).
+    pub fn written_note(&self) -> Option<String> {
+        let written = self.written?;
+        let mut note = format!(
+            "written on {} (UTC{}",
+            written.format("%-d %B %Y at %H:%M"),
+            written.offset()
+        );
+        if let Some(zone) = &self.zone {
+            note.push_str(&format!(", {}", zone));
+        }
+        note.push(')');
+        Some(note)
+    }
+
+    /// Return when the entry was last changed (eg `edited on 20 October 2022
+    /// at 10:15 (UTC+02:00)`).
+    pub fn modified_note(&self) -> Option<String> {
+        let modified = self.modified?;
+        Some(format!(
+            "edited on {} (UTC{})",
+            modified.format("%-d %B %Y at %H:%M"),
+            modified.offset()
+        ))
+    }
+
+    /// Get the names of the habits done that day.
+    pub fn get_habits(&self) -> &[String] {
+        &self.habits
+    }
+
+    /// Check if the text is hidden in previews until it is asked for.
+    pub fn is_private(&self) -> bool {
+        self.private
+    }
+
+    /// Check if `habit` was done that day.
+    pub fn has_done(&self, habit: &str) -> bool {
+        self.habits.iter().any(|done| done == habit)
+    }
+
+    /// Get the values of the entry's custom metrics, by metric name.
+    pub fn get_metrics(&self) -> &BTreeMap<String, MetricValue> {
+        &self.metrics
+    }
+
+    /// Return a listing of the entry's custom metrics, as shown in the preview.
+    pub fn metric_list(&self) -> String {
+        let mut list = String::from("metrics:");
+        for (name, value) in &self.metrics {
+            list.push_str(&format!("\n- {}: {}", name, value));
+        }
+        list
+    }
+
+    /// Get the previous versions of the entry, the oldest one first.
+    pub fn get_history(&self) -> &[Revision] {
+        &self.history
+    }
+
+    /// Return a listing of the entry's attachments, as shown in the preview.
+    pub fn attachment_list(&self) -> String {
+        let mut list = String::from("attachments:");
+        for name in &self.attachments {
+            list.push_str(&format!("\n- {}", name));
+        }
+        list
+    }
+
+    /// Return an entry containing the text of both entries, one after another.
+    ///
+    /// The rating of `self` is kept and the attachments of both are combined.
+    pub fn combined_with(&self, other: &Entry) -> Entry {
+        let mut entry = self.clone();
+        entry.text = format!("{}\n\n---\n\n{}", self.text, other.text);
+        entry.add_attachments(&other.attachments);
+        entry
+    }
+
+    /// Add the attachment names that the entry doesn't have yet.
+    fn add_attachments(&mut self, names: &[String]) {
+        for name in names {
+            if !self.attachments.contains(name) {
+                self.attachments.push(name.clone());
+            }
+        }
+    }
+
+    /// Check if the rating is on the `-2..=2` scale (or `42` for an empty entry).
+    pub fn has_valid_rating(&self) -> bool {
+        (-2..=2).contains(&self.rating) || self.is_default()
+    }
+
     /// Check if the entry is empty.
     pub fn is_default(&self) -> bool {
         *self == Entry::default()
     }
 }
 
-/// Return the location of the data directory.
+/// An entry with its date, the way it is given to other programs (eg by the
+/// `export` command or to hooks), without its history.
+#[derive(Serialize)]
+pub struct DatedEntry<'a> {
+    date: NaiveDate,
+    rating: i8,
+    text: &'a str,
+    habits: &'a [String],
+    metrics: &'a BTreeMap<String, MetricValue>,
+    attachments: &'a [String],
+    private: bool,
+}
+
+impl<'a> DatedEntry<'a> {
+    pub fn new(date: NaiveDate, entry: &'a Entry) -> Self {
+        DatedEntry {
+            date,
+            rating: entry.rating,
+            text: &entry.text,
+            habits: &entry.habits,
+            metrics: &entry.metrics,
+            attachments: &entry.attachments,
+            private: entry.private,
+        }
+    }
+}
+
+/// Return the location of the data directory of the journal in use.
 ///
 /// If the data directory does not exist, create it.
-/// Choice of the data directory location is dependent on the underlying os.
+/// Choice of the data directory location is dependent on the underlying os
+/// and on the journal in use (see `profile::dir`).
 ///
 /// All of the user's diary entries will be saved in `.json` files.
 /// The folder structure will be sth like this:
@@ -250,20 +680,178 @@ impl Entry {
 /// ```text
 /// ./lifelog/data
 /// ├── 2022
-/// │   ├── January.json
-/// │   ├── February.json
-/// │   └── ...
+/// │   ├── January.json
+/// │   ├── February.json
+/// │   └── ...
 /// └── 2023
 ///     ├── January.json
 ///     ├── February.json
 ///     └── ...
 /// ```
 pub fn data_dir() -> PathBuf {
-    let strategy = choose_base_strategy().expect("failed to find config directory");
-    let path = strategy.data_dir().join("lifelog").join("data");
+    #[cfg(test)]
+    let path = tests::TempDir::current().join("data");
+    #[cfg(not(test))]
+    let path = profile::dir().join("data");
 
     if !path.exists() {
         fs::create_dir_all(&path).expect("failed to create data directory");
     }
     path
 }
+
+#[cfg(test)]
+impl Entry {
+    /// Construct an entry with the given rating and text.
+    pub fn new(rating: i8, text: &str) -> Self {
+        Entry {
+            rating,
+            text: text.to_string(),
+            ..Entry::default()
+        }
+    }
+
+    /// Return the entry with the given habits done.
+    pub fn with_habits(mut self, habits: &[&str]) -> Self {
+        self.habits = habits.iter().map(|habit| habit.to_string()).collect();
+        self
+    }
+
+    /// Return the entry with the given metric values.
+    pub fn with_metrics(mut self, metrics: &[(&str, MetricValue)]) -> Self {
+        self.metrics = metrics
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.clone()))
+            .collect();
+        self
+    }
+
+    /// Return the entry marked as private or not.
+    pub fn with_private(mut self, private: bool) -> Self {
+        self.private = private;
+        self
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::cell::RefCell;
+    use std::process;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    thread_local! {
+        static CURRENT: RefCell<Option<PathBuf>> = const { RefCell::new(None) };
+    }
+
+    /// Lifelog directory of the test running on the current thread, used
+    /// by `data_dir` instead of the real one and removed when dropped.
+    pub struct TempDir(PathBuf);
+
+    impl TempDir {
+        pub fn create() -> Self {
+            static COUNT: AtomicUsize = AtomicUsize::new(0);
+            let path = env::temp_dir().join(format!(
+                "lifelog-test-{}-{}",
+                process::id(),
+                COUNT.fetch_add(1, Ordering::Relaxed)
+            ));
+            fs::create_dir_all(&path).unwrap();
+            CURRENT.with(|current| *current.borrow_mut() = Some(path.clone()));
+            TempDir(path)
+        }
+
+        pub fn path(&self) -> &Path {
+            &self.0
+        }
+
+        /// Return the lifelog directory of the running test.
+        pub fn current() -> PathBuf {
+            CURRENT
+                .with(|current| current.borrow().clone())
+                .expect("tests that use the data directory need a TempDir")
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            CURRENT.with(|current| *current.borrow_mut() = None);
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// Write a June 2022 month file with `count` entries, the first one rated.
+    fn write_june(count: usize) {
+        let mut entries = vec![Entry::default(); count];
+        entries[0] = Entry::new(2, "first");
+        let log = MonthLog {
+            month: "June".to_string(),
+            year: 2022,
+            entries,
+        };
+        fs::create_dir_all(data_dir().join("2022")).unwrap();
+        fs::write(
+            data_dir().join("2022/June.json"),
+            serde_json::to_string(&log).unwrap(),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn missing_months_are_empty() {
+        let _temp = TempDir::create();
+        let log = MonthLog::get_month_log("February/2024").unwrap();
+        assert_eq!(log.days(), 29);
+        assert!(log.entries().iter().all(Entry::is_default));
+    }
+
+    #[test]
+    fn saved_months_are_read_back() {
+        let _temp = TempDir::create();
+        let mut log = MonthLog::get_month_log("June/2022").unwrap();
+        log.update_entry(5, 1, "a good day".to_string());
+        log.save_to_disk().unwrap();
+
+        let log = MonthLog::get_month_log("June/2022").unwrap();
+        assert_eq!(log.get_entry(5).get_rating(), 1);
+        assert_eq!(log.get_entry(5).get_text(), "a good day");
+    }
+
+    #[test]
+    fn short_months_are_padded() {
+        let _temp = TempDir::create();
+        write_june(10);
+        let log = MonthLog::get_month_log("June/2022").unwrap();
+        assert_eq!(log.days(), 30);
+        assert_eq!(log.get_entry(1).get_text(), "first");
+        assert!(log.get_entry(30).is_default());
+    }
+
+    #[test]
+    fn unreadable_months_are_errors() {
+        let _temp = TempDir::create();
+        write_june(31);
+        let e = MonthLog::get_month_log("June/2022").err().unwrap();
+        assert!(e.to_string().contains("31 entries for 30 days"));
+
+        fs::write(data_dir().join("2022/June.json"), "{bad").unwrap();
+        let e = MonthLog::get_month_log("June/2022").err().unwrap();
+        assert!(e.to_string().contains("run `lifelog check`"));
+    }
+
+    #[test]
+    fn tags_are_lowercase_and_unique() {
+        let entry = Entry::new(
+            1,
+            "Lunch with #Anna, then #work and more #WORK.\n#anna-b #x_y",
+        );
+        assert_eq!(entry.tags(), ["anna", "work", "anna-b", "x_y"]);
+    }
+
+    #[test]
+    fn headings_and_inner_hashes_are_not_tags() {
+        let entry = Entry::new(1, "# Title\n## Notes\nissue a#1 and # alone");
+        assert!(entry.tags().is_empty());
+    }
+}
@@ -0,0 +1,219 @@
+use cursive::event::{Callback, Key};
+use cursive::view::{Nameable, View};
+use cursive::views::{BoxedView, Dialog, HideableView, OnEventView};
+use cursive::Cursive;
+
+/// The screens of the TUI.
+///
+/// Used to tell which screens are open, eg so a flow of screens can be closed
+/// up to the screen it started from.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Screen {
+    MainMenu,
+    Entries,
+    Editor,
+    Capsule,
+    Rating,
+    Habits,
+    Metrics,
+    Attachments,
+    Filter,
+    History,
+    OnThisDay,
+    Charts,
+    Analysis,
+    About,
+    Settings,
+    Journals,
+    Problems,
+    Conflict,
+    /// A message or a small form shown on top of another screen.
+    Popup,
+}
+
+/// Stack of the open screens, the last one is on top.
+///
+/// Every screen is a cursive layer. Opening a screen hides the ones below it
+/// instead of removing them, so their state (eg the selected date of the
+/// calendar) is still there when going back.
+pub struct Navigator {
+    screens: Vec<OpenScreen>,
+}
+
+struct OpenScreen {
+    screen: Screen,
+    /// Whether the screen is shown on top of the previous one instead of
+    /// hiding it.
+    overlay: bool,
+    /// Called when the screen is shown again after the ones above it closed.
+    on_return: Option<Callback>,
+    /// Called when the screen is closed.
+    on_close: Option<Callback>,
+}
+
+impl Navigator {
+    pub fn new() -> Self {
+        Navigator {
+            screens: Vec::new(),
+        }
+    }
+
+    /// Return the indices of the screens that are currently visible: the top
+    /// screen and, if it's an overlay, the screens it is shown on top of.
+    fn visible(&self) -> std::ops::Range<usize> {
+        let start = self
+            .screens
+            .iter()
+            .rposition(|open| !open.overlay)
+            .unwrap_or(0);
+        start..self.screens.len()
+    }
+}
+
+impl Default for Navigator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Open `view` as a new screen, hiding the visible screens.
+pub fn open<V: View>(s: &mut Cursive, screen: Screen, view: V) {
+    push(s, screen, view, false);
+}
+
+/// Show `view` on top of the visible screens, eg a message or a small form.
+pub fn overlay<V: View>(s: &mut Cursive, screen: Screen, view: V) {
+    push(s, screen, view, true);
+}
+
+/// Show a message on top of the visible screens, closed with `Ok` or `<ESC>`.
+pub fn message<S: Into<String>>(s: &mut Cursive, text: S) {
+    overlay(
+        s,
+        Screen::Popup,
+        Dialog::text(text.into()).button("Ok", back),
+    );
+}
+
+/// Close the open screens and open `view` instead of them.
+///
+/// Used by the screens that are shown instead of the main menu (eg when only
+/// resolving conflicts from the command line).
+pub fn replace<V: View>(s: &mut Cursive, screen: Screen, view: V) {
+    while !navigator(s).screens.is_empty() {
+        pop(s);
+    }
+    open(s, screen, view);
+}
+
+fn push<V: View>(s: &mut Cursive, screen: Screen, view: V, overlay: bool) {
+    if !overlay {
+        let visible = navigator(s).visible();
+        for i in visible {
+            set_hidden(s, i, true);
+        }
+    }
+
+    let index = navigator(s).screens.len();
+    navigator(s).screens.push(OpenScreen {
+        screen,
+        overlay,
+        on_return: None,
+        on_close: None,
+    });
+
+    let view = OnEventView::new(view).on_event(Key::Esc, back);
+    s.add_layer(HideableView::new(BoxedView::boxed(view)).with_name(layer_name(index)));
+}
+
+/// Run `cb` every time the current screen is shown again after going back to it.
+pub fn on_return<F>(s: &mut Cursive, cb: F)
+where
+    F: Fn(&mut Cursive) + 'static,
+{
+    if let Some(open) = navigator(s).screens.last_mut() {
+        open.on_return = Some(Callback::from_fn(cb));
+    }
+}
+
+/// Run `cb` when the current screen is closed, whichever way it is closed.
+pub fn on_close<F>(s: &mut Cursive, cb: F)
+where
+    F: Fn(&mut Cursive) + 'static,
+{
+    if let Some(open) = navigator(s).screens.last_mut() {
+        open.on_close = Some(Callback::from_fn(cb));
+    }
+}
+
+/// Close the current screen and go back to the previous one.
+///
+/// The first screen (eg the main menu) can't be closed this way.
+pub fn back(s: &mut Cursive) {
+    if navigator(s).screens.len() > 1 {
+        pop(s);
+        returned(s);
+    }
+}
+
+/// Close all the screens above `screen` and `screen` itself.
+///
+/// Does nothing if `screen` is not open.
+pub fn close(s: &mut Cursive, screen: Screen) {
+    let is_open = navigator(s)
+        .screens
+        .iter()
+        .skip(1)
+        .any(|open| open.screen == screen);
+    if !is_open {
+        return;
+    }
+
+    while let Some(open) = pop(s) {
+        if open == screen {
+            break;
+        }
+    }
+    returned(s);
+}
+
+/// Remove the top screen without showing the one below.
+fn pop(s: &mut Cursive) -> Option<Screen> {
+    let open = navigator(s).screens.pop()?;
+    s.pop_layer();
+    if let Some(cb) = open.on_close {
+        cb(s);
+    }
+    Some(open.screen)
+}
+
+/// Show the screens that were hidden by the closed one and let the top
+/// screen know that it's visible again.
+fn returned(s: &mut Cursive) {
+    let visible = navigator(s).visible();
+    for i in visible {
+        set_hidden(s, i, false);
+    }
+
+    let on_return = navigator(s)
+        .screens
+        .last()
+        .and_then(|open| open.on_return.clone());
+    if let Some(cb) = on_return {
+        cb(s);
+    }
+}
+
+fn set_hidden(s: &mut Cursive, index: usize, hidden: bool) {
+    s.call_on_name(&layer_name(index), |view: &mut HideableView<BoxedView>| {
+        view.set_visible(!hidden)
+    });
+}
+
+fn layer_name(index: usize) -> String {
+    format!("screen-{}", index)
+}
+
+fn navigator(s: &mut Cursive) -> &mut Navigator {
+    &mut super::app(s).nav
+}
@@ -0,0 +1,105 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use etcetera::base_strategy::{choose_base_strategy, BaseStrategy};
+
+/// Name of the journal that lives directly in the lifelog directory (the only
+/// journal before named journals were added).
+pub const DEFAULT: &str = "default";
+
+/// Name of the journal in use, `None` for the default journal.
+static CURRENT: Mutex<Option<String>> = Mutex::new(None);
+
+/// Return the name of the journal in use.
+pub fn current() -> String {
+    CURRENT
+        .lock()
+        .unwrap()
+        .clone()
+        .unwrap_or_else(|| DEFAULT.to_string())
+}
+
+/// Use the journal called `name` from now on.
+///
+/// All the paths (data, theme, config) are computed from the journal in use,
+/// so every screen and command works on that journal.
+pub fn set_current(name: &str) -> Result<(), String> {
+    if !names().iter().any(|journal| journal == name) {
+        return Err(format!("unknown journal '{}'", name));
+    }
+
+    *CURRENT.lock().unwrap() = if name == DEFAULT {
+        None
+    } else {
+        Some(name.to_string())
+    };
+    Ok(())
+}
+
+/// Return the names of all the journals, the default one first.
+pub fn names() -> Vec<String> {
+    let mut names = vec![DEFAULT.to_string()];
+
+    if let Ok(dirs) = fs::read_dir(journals_dir()) {
+        let mut others: Vec<String> = dirs
+            .filter_map(|dir| dir.ok())
+            .filter(|dir| dir.path().is_dir())
+            .filter_map(|dir| dir.file_name().into_string().ok())
+            .collect();
+        others.sort();
+        names.extend(others);
+    }
+    names
+}
+
+/// Create a new journal called `name`.
+pub fn create(name: &str) -> io::Result<()> {
+    let invalid = |reason: &str| Err(io::Error::new(io::ErrorKind::InvalidInput, reason));
+
+    if name.is_empty() {
+        return invalid("the name can't be empty");
+    }
+    if !name
+        .chars()
+        .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+    {
+        return invalid("only letters, digits, '-' and '_' can be used in the name");
+    }
+    if names().iter().any(|journal| journal == name) {
+        return invalid("there is already a journal with that name");
+    }
+
+    fs::create_dir_all(journals_dir().join(name).join("data"))
+}
+
+/// Return the directory of the journal in use, which contains its `data`
+/// and `theme` directories and its `config.toml` file.
+///
+/// ```text
+/// ./lifelog
+/// ├── data                 (default journal)
+/// ├── theme
+/// ├── config.toml
+/// └── journals
+///     └── work             (journal named "work")
+///         ├── data
+///         ├── theme
+///         └── config.toml
+/// ```
+pub fn dir() -> PathBuf {
+    match CURRENT.lock().unwrap().as_ref() {
+        Some(name) => journals_dir().join(name),
+        None => lifelog_dir(),
+    }
+}
+
+fn journals_dir() -> PathBuf {
+    lifelog_dir().join("journals")
+}
+
+fn lifelog_dir() -> PathBuf {
+    let strategy = choose_base_strategy().expect("failed to find config directory");
+    strategy.data_dir().join("lifelog")
+}
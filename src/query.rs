@@ -0,0 +1,340 @@
+use std::io;
+
+use chrono::NaiveDate;
+
+use super::calendar;
+use super::journal::Journal;
+use super::month_log::{Entry, MetricValue};
+
+/// A filter over the entries, parsed from a query like
+/// `rating>=1 tag:work after:2022-06-01 text:"deploy"`.
+///
+/// A query is a list of terms separated by spaces, and an entry matches when
+/// it matches all of them:
+///
+/// - `rating>=1`: the rating compared with `=` (or `:`), `!=`, `<`, `<=`,
+///   `>` or `>=`
+/// - `tag:work`: the text contains `#work`
+/// - `habit:read`: the habit was done
+/// - `after:2022-06-01`, `before:2022-06-30`: the day is on or after (on or
+///   before) the date
+/// - `text:deploy`, or just `deploy`: the text contains the words, ignoring
+///   the case
+/// - `sleep>=7`, `mood:happy`, `workout:yes`: the value of a custom metric,
+///   compared the same way as the rating
+///
+/// Values with spaces are put in quotes (`text:"went hiking"`), and so is
+/// text that would be taken for a comparison (`"10:30"`, `"a<b"`). A term
+/// starting with `-` is negated (`-tag:work`). Days without an entry never
+/// match.
+pub struct Query {
+    terms: Vec<(bool, Term)>,
+}
+
+enum Term {
+    Rating(Comparison, i8),
+    Tag(String),
+    Habit(String),
+    After(NaiveDate),
+    Before(NaiveDate),
+    Text(String),
+    Metric(String, Comparison, String),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl Query {
+    /// Parse `query`, return a description of the problem if it's invalid.
+    pub fn parse(query: &str) -> Result<Self, String> {
+        let mut terms = Vec::new();
+        for word in split(query)? {
+            let (negated, word) = match word.strip_prefix('-') {
+                Some(rest) if !rest.is_empty() => (true, rest.to_string()),
+                _ => (false, word),
+            };
+            terms.push((negated, Term::parse(&word)?));
+        }
+        Ok(Query { terms })
+    }
+
+    /// Return the days in `from..=to` whose entry matches the query, with
+    /// their entries.
+    pub fn find(
+        &self,
+        journal: &mut Journal,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> io::Result<Vec<(NaiveDate, Entry)>> {
+        Ok(journal
+            .days(from, to)?
+            .filter(|(date, entry)| self.matches(*date, entry))
+            .map(|(date, entry)| (date, entry.clone()))
+            .collect())
+    }
+
+    /// Check if the query has no terms, in which case every entry matches.
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    /// Check if the entry of `date` matches the query.
+    pub fn matches(&self, date: NaiveDate, entry: &Entry) -> bool {
+        !entry.is_default()
+            && self
+                .terms
+                .iter()
+                .all(|(negated, term)| term.matches(date, entry) != *negated)
+    }
+}
+
+impl Term {
+    fn parse(word: &str) -> Result<Self, String> {
+        let (name, comparison, value) = match Comparison::find(word) {
+            Some((name, comparison, value)) => (name, comparison, unquote(value)),
+            None => return Ok(Term::Text(unquote(word).to_lowercase())),
+        };
+        if value.is_empty() {
+            return Err(format!("missing value in '{}'", word));
+        }
+        let only_equal = |term: Term| {
+            if comparison == Comparison::Equal {
+                Ok(term)
+            } else {
+                Err(format!("'{}' can only be used with ':'", name))
+            }
+        };
+
+        match name {
+            "rating" => match value.parse() {
+                Ok(rating @ -2..=2) => Ok(Term::Rating(comparison, rating)),
+                _ => Err(format!(
+                    "invalid rating '{}', expected a number from -2 to 2",
+                    value
+                )),
+            },
+            "tag" => only_equal(Term::Tag(value.trim_start_matches('#').to_lowercase())),
+            "habit" => only_equal(Term::Habit(value.to_string())),
+            "after" => only_equal(Term::After(calendar::parse_date(&value)?)),
+            "before" => only_equal(Term::Before(calendar::parse_date(&value)?)),
+            "text" => only_equal(Term::Text(value.to_lowercase())),
+            "" => Err(format!("missing name in '{}'", word)),
+            _ => Ok(Term::Metric(
+                name.to_string(),
+                comparison,
+                value.to_string(),
+            )),
+        }
+    }
+
+    fn matches(&self, date: NaiveDate, entry: &Entry) -> bool {
+        match self {
+            Term::Rating(comparison, rating) => comparison.holds(entry.get_rating(), *rating),
+            Term::Tag(tag) => entry.tags().contains(tag),
+            Term::Habit(habit) => entry.has_done(habit),
+            Term::After(after) => date >= *after,
+            Term::Before(before) => date <= *before,
+            Term::Text(text) => entry.get_text().to_lowercase().contains(text),
+            Term::Metric(name, comparison, value) => match entry.get_metrics().get(name) {
+                Some(MetricValue::Number(number)) => value
+                    .parse::<f64>()
+                    .is_ok_and(|value| comparison.holds(*number, value)),
+                Some(MetricValue::Boolean(done)) => {
+                    let value = matches!(value.as_str(), "yes" | "true");
+                    match comparison {
+                        Comparison::Equal => *done == value,
+                        Comparison::NotEqual => *done != value,
+                        _ => false,
+                    }
+                }
+                Some(MetricValue::Choice(choice)) => match comparison {
+                    Comparison::Equal => choice.eq_ignore_ascii_case(value),
+                    Comparison::NotEqual => !choice.eq_ignore_ascii_case(value),
+                    _ => false,
+                },
+                None => false,
+            },
+        }
+    }
+}
+
+impl Comparison {
+    /// Split a term like `rating>=1` into its name, comparison and value.
+    ///
+    /// The comparison is only looked for before the first quote, so quoted
+    /// text like `"10:30"` is never split.
+    fn find(word: &str) -> Option<(&str, Self, &str)> {
+        let unquoted = &word[..word.find('"').unwrap_or(word.len())];
+        let start = unquoted.find([':', '=', '!', '<', '>'])?;
+        let (name, rest) = word.split_at(start);
+        let (comparison, length) = if rest.starts_with(">=") {
+            (Comparison::GreaterOrEqual, 2)
+        } else if rest.starts_with("<=") {
+            (Comparison::LessOrEqual, 2)
+        } else if rest.starts_with("!=") {
+            (Comparison::NotEqual, 2)
+        } else if rest.starts_with('>') {
+            (Comparison::Greater, 1)
+        } else if rest.starts_with('<') {
+            (Comparison::Less, 1)
+        } else if rest.starts_with('!') {
+            // a `!` that isn't part of `!=` is just text
+            return None;
+        } else {
+            (Comparison::Equal, 1)
+        };
+        Some((name, comparison, &rest[length..]))
+    }
+
+    fn holds<T: PartialOrd>(self, left: T, right: T) -> bool {
+        match self {
+            Comparison::Equal => left == right,
+            Comparison::NotEqual => left != right,
+            Comparison::Less => left < right,
+            Comparison::LessOrEqual => left <= right,
+            Comparison::Greater => left > right,
+            Comparison::GreaterOrEqual => left >= right,
+        }
+    }
+}
+
+/// Split a query into its terms, at the spaces that are not in quotes.
+///
+/// The quotes are kept, so the terms can tell quoted text apart, see
+/// `unquote`.
+fn split(query: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut quoted = false;
+    for c in query.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                word.push(c);
+            }
+            c if c.is_whitespace() && !quoted => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+            }
+            c => word.push(c),
+        }
+    }
+    if quoted {
+        return Err("missing closing quote".to_string());
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+/// Remove the quotes of a term or value (`"went hiking"` is `went hiking`).
+fn unquote(text: &str) -> String {
+    text.replace('"', "")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(query: &str, entry: &Entry) -> bool {
+        let date = NaiveDate::from_ymd(2022, 6, 15);
+        Query::parse(query).unwrap().matches(date, entry)
+    }
+
+    #[test]
+    fn find_splits_comparisons() {
+        assert!(matches!(
+            Comparison::find("rating>=1"),
+            Some(("rating", Comparison::GreaterOrEqual, "1"))
+        ));
+        assert!(matches!(
+            Comparison::find("sleep<7"),
+            Some(("sleep", Comparison::Less, "7"))
+        ));
+        assert!(matches!(
+            Comparison::find("mood!=sad"),
+            Some(("mood", Comparison::NotEqual, "sad"))
+        ));
+        assert!(matches!(
+            Comparison::find("tag:work"),
+            Some(("tag", Comparison::Equal, "work"))
+        ));
+        assert!(Comparison::find("deploy").is_none());
+        assert!(Comparison::find("wow!").is_none());
+    }
+
+    #[test]
+    fn find_ignores_quoted_text() {
+        assert!(Comparison::find("\"10:30\"").is_none());
+        assert!(Comparison::find("\"a<b\"").is_none());
+        assert!(matches!(
+            Comparison::find("text:\"at 10:30\""),
+            Some(("text", Comparison::Equal, "\"at 10:30\""))
+        ));
+    }
+
+    #[test]
+    fn split_keeps_quoted_spaces() {
+        assert_eq!(
+            split("tag:work  text:\"went hiking\" -x").unwrap(),
+            ["tag:work", "text:\"went hiking\"", "-x"]
+        );
+        assert!(split("\"unclosed").is_err());
+    }
+
+    #[test]
+    fn parse_rejects_invalid_terms() {
+        assert!(Query::parse("rating>=3").is_err());
+        assert!(Query::parse("rating:").is_err());
+        assert!(Query::parse(":work").is_err());
+        assert!(Query::parse("tag>work").is_err());
+        assert!(Query::parse("after:yesterday").is_err());
+        assert!(Query::parse("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn matches_rating_tags_and_text() {
+        let entry = Entry::new(1, "Meeting at 10:30 #Work, see https://a.b");
+        assert!(matches("rating>=1 rating<2", &entry));
+        assert!(!matches("rating=2", &entry));
+        assert!(matches("tag:work tag:#WORK", &entry));
+        assert!(!matches("-tag:work", &entry));
+        assert!(matches("meeting \"at 10:30\"", &entry));
+        assert!(matches("text:\"see https://a.b\"", &entry));
+        assert!(!matches("holiday", &entry));
+        assert!(matches("after:2022-06-15 before:2022-06-15", &entry));
+        assert!(!matches("after:2022-06-16", &entry));
+    }
+
+    #[test]
+    fn matches_habits_and_metrics() {
+        let entry = Entry::new(0, "ok").with_habits(&["read"]).with_metrics(&[
+            ("sleep", MetricValue::Number(7.5)),
+            ("workout", MetricValue::Boolean(true)),
+            ("mood", MetricValue::Choice("Happy".to_string())),
+        ]);
+        assert!(matches("habit:read -habit:meditate", &entry));
+        assert!(matches("sleep>=7 sleep<8", &entry));
+        assert!(!matches("sleep>8", &entry));
+        assert!(matches("workout:yes", &entry));
+        assert!(!matches("workout:no", &entry));
+        assert!(matches("mood:happy mood!=sad", &entry));
+        assert!(!matches("mood>happy", &entry));
+        assert!(!matches("caffeine:none", &entry));
+    }
+
+    #[test]
+    fn empty_days_never_match() {
+        assert!(!matches("", &Entry::default()));
+        assert!(!matches("-tag:work", &Entry::default()));
+    }
+}
@@ -0,0 +1,533 @@
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, Write};
+use std::thread::JoinHandle;
+
+use chrono::{Datelike, Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use super::calendar;
+use super::config::Config;
+use super::hooks::{self, Hook};
+use super::journal::Journal;
+use super::month_log::{DatedEntry, MetricValue};
+use super::query::Query;
+use super::statistics::Statistics;
+
+/// Codes of the errors defined by JSON-RPC.
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+/// Code of the errors of lifelog itself, eg a day that has no entry.
+const JOURNAL_ERROR: i64 = -32000;
+
+/// A JSON-RPC 2.0 server on stdin and stdout, for `lifelog rpc`.
+///
+/// Every request and response is a single line of JSON, so editor plugins
+/// can read the responses line by line. Requests are answered in order, and
+/// notifications (requests without an `id`) are run without an answer.
+/// Entries are read and saved through a `Journal`, like in the TUI.
+///
+/// The methods are:
+///
+/// - `get_entry {date, include_private?}`: the entry of the day (see
+///   `DatedEntry`) or `null`
+/// - `put_entry {date, rating, text, private?, habits?, metrics?}`: save the
+///   entry of the day, the fields left out are kept as they are
+/// - `delete_entry {date}`: delete the entry of the day
+/// - `list_days {from?, to?, include_private?}`: the days that have an
+///   entry, with their rating
+/// - `search {query, from?, to?, include_private?}`: the entries that match a
+///   query (see `Query`)
+/// - `stats {from?, to?}`: the statistics of the range (see `Statistics`)
+///
+/// Private entries are left out unless `include_private` is set, like on the
+/// command line. They still count in the statistics, which don't show text.
+/// `put_entry` and `delete_entry` return `null` and run the hook of the
+/// change in the background. If it fails, a `hook_failed` notification with
+/// the problem as `message` is written. Dates are `YYYY-MM-DD`, and ranges
+/// default to all the entries up to today like on the command line.
+pub struct Rpc {
+    journal: Journal,
+    config: Config,
+    /// Hooks that may still be running, waited for before exiting.
+    hooks: Vec<JoinHandle<()>>,
+}
+
+struct Error {
+    code: i64,
+    message: String,
+}
+
+impl Error {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Error {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+/// Month files that can't be read or saved are journal errors.
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::new(JOURNAL_ERROR, e.to_string())
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DateParams {
+    date: String,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GetParams {
+    date: String,
+    #[serde(default)]
+    include_private: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PutParams {
+    date: String,
+    rating: i8,
+    text: String,
+    private: Option<bool>,
+    habits: Option<Vec<String>>,
+    metrics: Option<BTreeMap<String, MetricValue>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RangeParams {
+    from: Option<String>,
+    to: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ListParams {
+    from: Option<String>,
+    to: Option<String>,
+    #[serde(default)]
+    include_private: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SearchParams {
+    query: String,
+    from: Option<String>,
+    to: Option<String>,
+    #[serde(default)]
+    include_private: bool,
+}
+
+#[derive(Serialize)]
+struct Day {
+    date: NaiveDate,
+    rating: i8,
+    private: bool,
+}
+
+impl Rpc {
+    pub fn new(config: Config) -> Self {
+        Rpc {
+            journal: Journal::new(),
+            config,
+            hooks: Vec::new(),
+        }
+    }
+
+    /// Answer the requests read from stdin until it is closed.
+    pub fn run(mut self) -> io::Result<()> {
+        let stdin = io::stdin();
+        let stdout = io::stdout();
+        for line in stdin.lock().lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            if let Some(response) = self.handle(&line) {
+                // the hooks write to stdout as well, so each line is written
+                // while holding the lock
+                let mut stdout = stdout.lock();
+                writeln!(stdout, "{}", response)?;
+                stdout.flush()?;
+            }
+        }
+
+        for hook in self.hooks {
+            let _ = hook.join();
+        }
+        Ok(())
+    }
+
+    /// Return the response to a line of input, `None` if there is nothing
+    /// to answer (eg only notifications).
+    fn handle(&mut self, line: &str) -> Option<Value> {
+        let message: Value = match serde_json::from_str(line) {
+            Ok(message) => message,
+            Err(e) => return Some(failure(Value::Null, Error::new(PARSE_ERROR, e.to_string()))),
+        };
+
+        match message {
+            Value::Array(batch) if batch.is_empty() => Some(failure(
+                Value::Null,
+                Error::new(INVALID_REQUEST, "empty batch"),
+            )),
+            Value::Array(batch) => {
+                let responses: Vec<Value> = batch
+                    .into_iter()
+                    .filter_map(|request| self.call(request))
+                    .collect();
+                (!responses.is_empty()).then_some(Value::Array(responses))
+            }
+            request => self.call(request),
+        }
+    }
+
+    /// Run a single request and return its response, `None` for a
+    /// notification.
+    fn call(&mut self, request: Value) -> Option<Value> {
+        let id = request.get("id").cloned();
+        let method = request.get("method").and_then(Value::as_str);
+        let (method, params) = match method {
+            Some(method) if request.get("jsonrpc") == Some(&json!("2.0")) => (
+                method.to_string(),
+                request.get("params").cloned().unwrap_or(Value::Null),
+            ),
+            _ => {
+                let error = Error::new(INVALID_REQUEST, "not a JSON-RPC 2.0 request");
+                return Some(failure(id.unwrap_or(Value::Null), error));
+            }
+        };
+
+        let result = self.dispatch(&method, params);
+        let id = id?;
+        Some(match result {
+            Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+            Err(error) => failure(id, error),
+        })
+    }
+
+    fn dispatch(&mut self, method: &str, params: Value) -> Result<Value, Error> {
+        match method {
+            "get_entry" => self.get_entry(parse_params(params)?),
+            "put_entry" => self.put_entry(parse_params(params)?),
+            "delete_entry" => self.delete_entry(parse_params(params)?),
+            "list_days" => self.list_days(parse_params(params)?),
+            "search" => self.search(parse_params(params)?),
+            "stats" => self.stats(parse_params(params)?),
+            _ => Err(Error::new(
+                METHOD_NOT_FOUND,
+                format!("unknown method '{}'", method),
+            )),
+        }
+    }
+
+    fn get_entry(&mut self, params: GetParams) -> Result<Value, Error> {
+        let date = calendar::parse_date(&params.date).map_err(invalid_params)?;
+        let entry = self
+            .journal
+            .month(&date.format("%B/%Y").to_string())?
+            .get_entry(date.day());
+        if entry.is_default() {
+            return Ok(Value::Null);
+        }
+        // an error rather than `null`, so the entry isn't mistaken for a
+        // missing one and overwritten
+        if entry.is_private() && !params.include_private {
+            return Err(Error::new(
+                JOURNAL_ERROR,
+                format!(
+                    "the entry of {} is private, set include_private to read it",
+                    date
+                ),
+            ));
+        }
+        Ok(serde_json::to_value(DatedEntry::new(date, entry)).unwrap())
+    }
+
+    fn put_entry(&mut self, params: PutParams) -> Result<Value, Error> {
+        let date = calendar::parse_date(&params.date).map_err(invalid_params)?;
+        if date > Local::today().naive_local() {
+            return Err(Error::new(JOURNAL_ERROR, "that day has not come yet"));
+        }
+        if !(-2..=2).contains(&params.rating) {
+            return Err(Error::new(
+                INVALID_PARAMS,
+                format!(
+                    "invalid rating {}, expected a number from -2 to 2",
+                    params.rating
+                ),
+            ));
+        }
+        if params.text.trim().is_empty() {
+            return Err(Error::new(INVALID_PARAMS, "the text is empty"));
+        }
+        if let Some(habits) = &params.habits {
+            if let Some(habit) = habits
+                .iter()
+                .find(|habit| !self.config.habits.contains(habit))
+            {
+                return Err(Error::new(
+                    INVALID_PARAMS,
+                    format!("unknown habit '{}'", habit),
+                ));
+            }
+        }
+        if let Some(metrics) = &params.metrics {
+            for (name, value) in metrics {
+                self.check_metric(name, value)
+                    .map_err(|e| Error::new(INVALID_PARAMS, e))?;
+            }
+        }
+
+        let month_year = date.format("%B/%Y").to_string();
+        let day = date.day();
+        self.journal.update(&month_year, |log| {
+            log.update_entry(day, params.rating, params.text);
+            if let Some(private) = params.private {
+                log.set_private(day, private);
+            }
+            if let Some(habits) = params.habits {
+                log.set_habits(day, habits);
+            }
+            if let Some(metrics) = params.metrics {
+                log.set_metrics(day, metrics);
+            }
+        })?;
+
+        let entry = self.journal.month(&month_year)?.get_entry(day);
+        let hook = Hook::new(
+            &self.config.hooks,
+            hooks::Event::PostSave,
+            Some((date, entry)),
+        );
+        self.run_hook(hook);
+        Ok(Value::Null)
+    }
+
+    fn delete_entry(&mut self, params: DateParams) -> Result<Value, Error> {
+        let date = calendar::parse_date(&params.date).map_err(invalid_params)?;
+        let month_year = date.format("%B/%Y").to_string();
+        let deleted = self
+            .journal
+            .month(&month_year)?
+            .get_entry(date.day())
+            .clone();
+        if deleted.is_default() {
+            return Err(Error::new(
+                JOURNAL_ERROR,
+                format!("there is no entry on {}", date),
+            ));
+        }
+
+        self.journal
+            .update(&month_year, |log| log.delete_entry(date.day()))?
+            .map_err(|e| {
+                Error::new(
+                    JOURNAL_ERROR,
+                    format!("failed to delete the attachments: {}", e),
+                )
+            })?;
+
+        let hook = Hook::new(
+            &self.config.hooks,
+            hooks::Event::PostDelete,
+            Some((date, &deleted)),
+        );
+        self.run_hook(hook);
+        Ok(Value::Null)
+    }
+
+    fn list_days(&mut self, params: ListParams) -> Result<Value, Error> {
+        let (from, to) = date_range(params.from, params.to)?;
+        let days: Vec<Day> = self
+            .journal
+            .days(from, to)?
+            .filter(|(_, entry)| !entry.is_default())
+            .filter(|(_, entry)| params.include_private || !entry.is_private())
+            .map(|(date, entry)| Day {
+                date,
+                rating: entry.get_rating(),
+                private: entry.is_private(),
+            })
+            .collect();
+        Ok(serde_json::to_value(days).unwrap())
+    }
+
+    fn search(&mut self, params: SearchParams) -> Result<Value, Error> {
+        let query = Query::parse(&params.query)
+            .map_err(|e| Error::new(INVALID_PARAMS, format!("invalid query: {}", e)))?;
+        let (from, to) = date_range(params.from, params.to)?;
+
+        let found = query.find(&mut self.journal, from, to)?;
+        let entries: Vec<DatedEntry> = found
+            .iter()
+            .filter(|(_, entry)| params.include_private || !entry.is_private())
+            .map(|(date, entry)| DatedEntry::new(*date, entry))
+            .collect();
+        Ok(serde_json::to_value(entries).unwrap())
+    }
+
+    fn stats(&mut self, params: RangeParams) -> Result<Value, Error> {
+        let (from, to) = date_range(params.from, params.to)?;
+        let statistics = Statistics::for_range(&mut self.journal, from, to)?;
+        Ok(serde_json::to_value(statistics).unwrap())
+    }
+
+    /// Run the hook of a change that was saved on another thread, so the next
+    /// requests don't wait for it.
+    ///
+    /// If it fails, a `hook_failed` notification is written to stdout.
+    fn run_hook(&mut self, hook: Option<Hook>) {
+        self.hooks.retain(|hook| !hook.is_finished());
+        if let Some(hook) = hook {
+            self.hooks.push(hook.spawn(|e| {
+                let notification = json!({
+                    "jsonrpc": "2.0",
+                    "method": "hook_failed",
+                    "params": {"message": e},
+                });
+                let mut stdout = io::stdout().lock();
+                // nothing to do if the editor has gone away
+                let _ = writeln!(stdout, "{}", notification).and_then(|_| stdout.flush());
+            }));
+        }
+    }
+
+    /// Check that `value` fits the metric called `name` in the config.
+    fn check_metric(&self, name: &str, value: &MetricValue) -> Result<(), String> {
+        self.config
+            .metrics
+            .iter()
+            .find(|metric| metric.name == name)
+            .ok_or_else(|| format!("unknown metric '{}'", name))?
+            .check(value)
+    }
+}
+
+fn failure(id: Value, error: Error) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": {"code": error.code, "message": error.message},
+    })
+}
+
+/// Read the params of a method, a missing `params` is like an empty object.
+fn parse_params<T: for<'de> Deserialize<'de>>(params: Value) -> Result<T, Error> {
+    let params = match params {
+        Value::Null => json!({}),
+        params => params,
+    };
+    serde_json::from_value(params)
+        .map_err(|e| Error::new(INVALID_PARAMS, format!("invalid params: {}", e)))
+}
+
+/// Dates and ranges that don't make sense are invalid params.
+fn invalid_params(message: String) -> Error {
+    Error::new(INVALID_PARAMS, message)
+}
+
+/// Return the range from `from` (or the earliest entry) to `to` (or today).
+fn date_range(from: Option<String>, to: Option<String>) -> Result<(NaiveDate, NaiveDate), Error> {
+    let parse = |date: Option<String>| date.as_deref().map(calendar::parse_date).transpose();
+    let from = parse(from).map_err(invalid_params)?;
+    let to = parse(to).map_err(invalid_params)?;
+    let (from, to) = calendar::date_range(from, to);
+    if from > to {
+        return Err(invalid_params(format!(
+            "from ({}) is after to ({})",
+            from, to
+        )));
+    }
+    Ok((from, to))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Return the response to `line`, none of the requests sent here get
+    /// far enough to read the journal.
+    fn handle(line: &str) -> Option<Value> {
+        Rpc::new(Config::default()).handle(line)
+    }
+
+    fn error_code(response: &Value) -> i64 {
+        response["error"]["code"].as_i64().unwrap()
+    }
+
+    #[test]
+    fn invalid_json_is_a_parse_error() {
+        let response = handle("{\"jsonrpc\": \"2.0\",").unwrap();
+        assert_eq!(error_code(&response), PARSE_ERROR);
+        assert_eq!(response["id"], Value::Null);
+    }
+
+    #[test]
+    fn malformed_requests_are_invalid() {
+        let response = handle(r#"{"jsonrpc": "1.0", "id": 1, "method": "stats"}"#).unwrap();
+        assert_eq!(error_code(&response), INVALID_REQUEST);
+        assert_eq!(response["id"], 1);
+
+        let response = handle(r#"{"jsonrpc": "2.0", "id": 2}"#).unwrap();
+        assert_eq!(error_code(&response), INVALID_REQUEST);
+
+        let response = handle("[]").unwrap();
+        assert_eq!(error_code(&response), INVALID_REQUEST);
+    }
+
+    #[test]
+    fn unknown_methods_are_not_found() {
+        let response = handle(r#"{"jsonrpc": "2.0", "id": "a", "method": "drop"}"#).unwrap();
+        assert_eq!(error_code(&response), METHOD_NOT_FOUND);
+        assert_eq!(response["id"], "a");
+    }
+
+    #[test]
+    fn bad_params_are_invalid() {
+        for request in [
+            r#"{"jsonrpc": "2.0", "id": 1, "method": "get_entry"}"#,
+            r#"{"jsonrpc": "2.0", "id": 1, "method": "get_entry", "params": {"date": "2022-13-01"}}"#,
+            r#"{"jsonrpc": "2.0", "id": 1, "method": "get_entry", "params": {"date": "2022-06-01", "day": 1}}"#,
+            r#"{"jsonrpc": "2.0", "id": 1, "method": "put_entry", "params": {"date": "2022-06-01", "rating": 3, "text": "hi"}}"#,
+            r#"{"jsonrpc": "2.0", "id": 1, "method": "put_entry", "params": {"date": "2022-06-01", "rating": 1, "text": " "}}"#,
+            r#"{"jsonrpc": "2.0", "id": 1, "method": "stats", "params": {"from": "2022-06-02", "to": "2022-06-01"}}"#,
+        ] {
+            let response = handle(request).unwrap();
+            assert_eq!(error_code(&response), INVALID_PARAMS, "{}", request);
+        }
+    }
+
+    #[test]
+    fn future_days_are_journal_errors() {
+        let response = handle(
+            r#"{"jsonrpc": "2.0", "id": 1, "method": "put_entry", "params": {"date": "9999-01-01", "rating": 1, "text": "hi"}}"#,
+        )
+        .unwrap();
+        assert_eq!(error_code(&response), JOURNAL_ERROR);
+    }
+
+    #[test]
+    fn notifications_get_no_response() {
+        assert_eq!(handle(r#"{"jsonrpc": "2.0", "method": "drop"}"#), None);
+
+        let batch = handle(
+            r#"[{"jsonrpc": "2.0", "method": "drop"}, {"jsonrpc": "2.0", "id": 7, "method": "drop"}]"#,
+        )
+        .unwrap();
+        let responses = batch.as_array().unwrap();
+        assert_eq!(responses.len(), 1);
+        assert_eq!(responses[0]["id"], 7);
+
+        assert_eq!(handle(r#"[{"jsonrpc": "2.0", "method": "drop"}]"#), None);
+    }
+}
@@ -0,0 +1,336 @@
+use std::fmt;
+use std::io;
+
+use chrono::NaiveDate;
+use serde::Serialize;
+
+use super::journal::Journal;
+use super::statistics;
+
+/// Words that tell how a day went, with a score from `-3` (very bad) to `3`
+/// (very good).
+///
+/// Small on purpose: it only has to get the tone of a diary entry right,
+/// and everything stays offline.
+const LEXICON: &[(&str, i8)] = &[
+    // very good
+    ("amazing", 3),
+    ("awesome", 3),
+    ("best", 3),
+    ("blessed", 3),
+    ("brilliant", 3),
+    ("ecstatic", 3),
+    ("excellent", 3),
+    ("fantastic", 3),
+    ("incredible", 3),
+    ("love", 3),
+    ("loved", 3),
+    ("magical", 3),
+    ("outstanding", 3),
+    ("perfect", 3),
+    ("thrilled", 3),
+    ("wonderful", 3),
+    // good
+    ("beautiful", 2),
+    ("calm", 2),
+    ("celebrate", 2),
+    ("celebrated", 2),
+    ("cheerful", 2),
+    ("delicious", 2),
+    ("enjoy", 2),
+    ("enjoyed", 2),
+    ("excited", 2),
+    ("fun", 2),
+    ("glad", 2),
+    ("good", 2),
+    ("grateful", 2),
+    ("great", 2),
+    ("happy", 2),
+    ("laughed", 2),
+    ("lovely", 2),
+    ("nice", 2),
+    ("peaceful", 2),
+    ("productive", 2),
+    ("proud", 2),
+    ("relaxed", 2),
+    ("relaxing", 2),
+    ("success", 2),
+    ("successful", 2),
+    ("thankful", 2),
+    ("win", 2),
+    ("won", 2),
+    // a bit good
+    ("better", 1),
+    ("cozy", 1),
+    ("easy", 1),
+    ("fine", 1),
+    ("finished", 1),
+    ("friends", 1),
+    ("hope", 1),
+    ("interesting", 1),
+    ("like", 1),
+    ("liked", 1),
+    ("pleasant", 1),
+    ("rested", 1),
+    ("smile", 1),
+    ("smiled", 1),
+    ("sunny", 1),
+    ("well", 1),
+    // a bit bad
+    ("annoyed", -1),
+    ("annoying", -1),
+    ("boring", -1),
+    ("busy", -1),
+    ("cold", -1),
+    ("late", -1),
+    ("meh", -1),
+    ("missed", -1),
+    ("rain", -1),
+    ("rainy", -1),
+    ("slow", -1),
+    ("tired", -1),
+    ("worried", -1),
+    ("worse", -1),
+    // bad
+    ("angry", -2),
+    ("anxious", -2),
+    ("argued", -2),
+    ("argument", -2),
+    ("bad", -2),
+    ("broke", -2),
+    ("broken", -2),
+    ("cried", -2),
+    ("disappointed", -2),
+    ("exhausted", -2),
+    ("fail", -2),
+    ("failed", -2),
+    ("fight", -2),
+    ("frustrated", -2),
+    ("headache", -2),
+    ("hurt", -2),
+    ("lonely", -2),
+    ("lost", -2),
+    ("sad", -2),
+    ("sick", -2),
+    ("stress", -2),
+    ("stressed", -2),
+    ("stressful", -2),
+    ("ugly", -2),
+    ("upset", -2),
+    // very bad
+    ("awful", -3),
+    ("depressed", -3),
+    ("disaster", -3),
+    ("furious", -3),
+    ("hate", -3),
+    ("hated", -3),
+    ("horrible", -3),
+    ("miserable", -3),
+    ("panic", -3),
+    ("terrible", -3),
+    ("worst", -3),
+];
+
+/// Words that turn the meaning of the next few words around.
+const NEGATIONS: &[&str] = &["not", "no", "never", "without", "hardly", "nothing"];
+
+/// Words that make the next word stronger.
+const INTENSIFIERS: &[&str] = &["very", "really", "so", "extremely", "super", "incredibly"];
+
+/// Number of words after a negation that are negated.
+const NEGATION_REACH: usize = 3;
+
+/// Return the tone of `text`, from `-3` (very bad) to `3` (very good).
+///
+/// This is the average score of the words of the text that are in the
+/// lexicon, after negations and intensifiers are applied. `None` if the text
+/// doesn't have any of them.
+pub fn score(text: &str) -> Option<f64> {
+    let text = text.to_lowercase();
+    let words = text
+        .split(|c: char| !c.is_alphanumeric() && c != '\'')
+        .filter(|word| !word.is_empty());
+
+    let (mut sum, mut count) = (0.0, 0);
+    let mut negated_for = 0;
+    let mut intensified = false;
+
+    for word in words {
+        let is_negation = NEGATIONS.contains(&word) || word.ends_with("n't");
+
+        if let Some((_, score)) = LEXICON.iter().find(|(known, _)| *known == word) {
+            let mut score = f64::from(*score);
+            if intensified {
+                score *= 1.5;
+            }
+            if negated_for > 0 {
+                // "not good" is not as bad as "bad"
+                score *= -0.5;
+            }
+            sum += score;
+            count += 1;
+        }
+
+        intensified = INTENSIFIERS.contains(&word);
+        negated_for = if is_negation {
+            NEGATION_REACH
+        } else {
+            negated_for.saturating_sub(1)
+        };
+    }
+
+    if count == 0 {
+        None
+    } else {
+        Some((sum / f64::from(count)).clamp(-3.0, 3.0))
+    }
+}
+
+/// Return the rating that fits the tone of `text`, see `score`.
+pub fn suggest_rating(text: &str) -> Option<i8> {
+    let score = score(text)?;
+    let rating = if score >= 2.25 {
+        2
+    } else if score >= 0.75 {
+        1
+    } else if score > -0.75 {
+        0
+    } else if score > -2.25 {
+        -1
+    } else {
+        -2
+    };
+    Some(rating)
+}
+
+/// Comparison of the ratings given to days with the rating their text
+/// suggests, over a range of days.
+#[derive(Serialize)]
+pub struct Comparison {
+    /// First day of the range.
+    pub from: NaiveDate,
+    /// Last day of the range (inclusive).
+    pub to: NaiveDate,
+    /// Number of rated days with a text that suggests a rating.
+    pub days: u32,
+    /// Average rating given to those days.
+    pub average_rating: Option<f64>,
+    /// Average rating suggested by their text.
+    pub average_suggestion: Option<f64>,
+    /// Share of the days on which both are the same.
+    pub agreement: Option<f64>,
+    /// Share of the days on which they are at most one apart.
+    pub within_one: Option<f64>,
+}
+
+/// Header of the text output, matching the `Display` of `Comparison`.
+pub const HEADER: &str = "from        to          days  rating  text   same  within 1";
+
+impl Comparison {
+    /// Compare the ratings with the text of the days in `from..=to`.
+    pub fn for_range(journal: &mut Journal, from: NaiveDate, to: NaiveDate) -> io::Result<Self> {
+        let pairs: Vec<(i8, i8)> = journal
+            .days(from, to)?
+            .filter(|(_, entry)| !entry.is_default() && (-2..=2).contains(&entry.get_rating()))
+            .filter_map(|(_, entry)| Some((entry.get_rating(), suggest_rating(entry.get_text())?)))
+            .collect();
+
+        let days = pairs.len() as u32;
+        let share = |matches: usize| {
+            if days == 0 {
+                None
+            } else {
+                Some(matches as f64 / f64::from(days))
+            }
+        };
+        let average = |sum: i32| {
+            if days == 0 {
+                None
+            } else {
+                Some(f64::from(sum) / f64::from(days))
+            }
+        };
+
+        Ok(Comparison {
+            from,
+            to,
+            days,
+            average_rating: average(pairs.iter().map(|(rating, _)| i32::from(*rating)).sum()),
+            average_suggestion: average(
+                pairs
+                    .iter()
+                    .map(|(_, suggestion)| i32::from(*suggestion))
+                    .sum(),
+            ),
+            agreement: share(pairs.iter().filter(|(a, b)| a == b).count()),
+            within_one: share(pairs.iter().filter(|(a, b)| (a - b).abs() <= 1).count()),
+        })
+    }
+
+    /// Compare the ratings with the text for every month that overlaps the
+    /// range `from..=to`.
+    pub fn monthly(journal: &mut Journal, from: NaiveDate, to: NaiveDate) -> io::Result<Vec<Self>> {
+        statistics::months(from, to)
+            .into_iter()
+            .map(|(from, to)| Self::for_range(journal, from, to))
+            .collect()
+    }
+}
+
+impl fmt::Display for Comparison {
+    /// Format the comparison as a row of the table started by `HEADER`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let number = |value: Option<f64>| match value {
+            Some(value) => format!("{:+.2}", value),
+            None => "-".to_string(),
+        };
+        let percent = |value: Option<f64>| match value {
+            Some(value) => format!("{:.0}%", value * 100.0),
+            None => "-".to_string(),
+        };
+
+        write!(
+            f,
+            "{}  {}  {:>4}  {:>6}  {:>5}  {:>4}  {:>8}",
+            self.from,
+            self.to,
+            self.days,
+            number(self.average_rating),
+            number(self.average_suggestion),
+            percent(self.agreement),
+            percent(self.within_one)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn score_averages_known_words() {
+        assert_eq!(score("Nothing happened."), None);
+        assert_eq!(score("A GOOD day"), Some(2.0));
+        assert_eq!(score("good food, bad weather"), Some(0.0));
+        assert_eq!(score("very good"), Some(3.0));
+        assert_eq!(score("so amazing"), Some(3.0));
+    }
+
+    #[test]
+    fn negations_reach_the_next_words() {
+        assert_eq!(score("not good"), Some(-1.0));
+        assert_eq!(score("I didn't feel happy"), Some(-1.0));
+        assert_eq!(score("not at all good"), Some(-1.0));
+        assert_eq!(score("not that it was good"), Some(2.0));
+    }
+
+    #[test]
+    fn suggest_rating_buckets_the_score() {
+        assert_eq!(suggest_rating("nothing to say"), None);
+        assert_eq!(suggest_rating("really happy"), Some(2));
+        assert_eq!(suggest_rating("good"), Some(1));
+        assert_eq!(suggest_rating("fine but tired"), Some(0));
+        assert_eq!(suggest_rating("not good"), Some(-1));
+        assert_eq!(suggest_rating("a terrible day"), Some(-2));
+    }
+}
@@ -0,0 +1,742 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{Cursor, Read};
+
+use chrono::{Datelike, Local, NaiveDate};
+use password_hash::rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use tiny_http::{Header, Method, Request, Response};
+
+use super::config::Config;
+use super::hooks::{self, Hook};
+use super::journal::Journal;
+use super::month_log::{DatedEntry, Entry};
+use super::statistics::Statistics;
+use super::{lock, markdown, profile, site};
+
+type HttpResponse = Response<Cursor<Vec<u8>>>;
+
+/// Name of the cookie that keeps the session of a browser.
+const SESSION_COOKIE: &str = "lifelog_session";
+
+/// Largest request body read, a day's entry is far smaller.
+const MAX_BODY: u64 = 1 << 20;
+
+/// A small web interface to the journal in use, for `lifelog serve`.
+///
+/// It only listens on 127.0.0.1. A browser has to log in with the token
+/// printed on startup (and the passphrase if the journal has one), it then
+/// gets a session cookie. Entries are read and saved through a `Journal`,
+/// like in the TUI, so both can be used at the same time.
+pub struct Server {
+    http: tiny_http::Server,
+    port: u16,
+    journal: Journal,
+    config: Config,
+    token: String,
+    /// Sessions of the browsers that logged in.
+    sessions: HashSet<String>,
+}
+
+/// An entry sent by the editor.
+#[derive(Deserialize)]
+struct EntryUpdate {
+    rating: i8,
+    text: String,
+    private: bool,
+}
+
+#[derive(Serialize)]
+struct MonthView {
+    journal: String,
+    /// The month as `YYYY-MM`.
+    month: String,
+    title: String,
+    /// Weekday of the first day of the month, from 0 for Monday.
+    first_weekday: u32,
+    days: Vec<DayView>,
+    statistics: Statistics,
+    /// Ratings from `+2` to `-2` with their names.
+    ratings: Vec<(i8, String)>,
+}
+
+#[derive(Serialize)]
+struct DayView {
+    day: u32,
+    /// `None` if the day has no entry.
+    rating: Option<i8>,
+    private: bool,
+}
+
+#[derive(Serialize)]
+struct EntryView<'a> {
+    title: String,
+    /// `None` if the day has no entry, or if it is private and was not
+    /// revealed.
+    entry: Option<DatedEntry<'a>>,
+    /// Whether the entry is private, so the browser can offer to reveal it.
+    private: bool,
+    html: String,
+    written: Option<String>,
+}
+
+impl<'a> EntryView<'a> {
+    /// The view of the entry on `date`, leaving out everything but the
+    /// title if it is private and not `reveal`ed.
+    fn new(date: NaiveDate, entry: &'a Entry, reveal: bool) -> Self {
+        let title = date.format("%A, %-d %B %Y").to_string();
+        if entry.is_private() && !reveal {
+            return EntryView {
+                title,
+                entry: None,
+                private: true,
+                html: String::new(),
+                written: None,
+            };
+        }
+
+        EntryView {
+            title,
+            entry: (!entry.is_default()).then(|| DatedEntry::new(date, entry)),
+            private: entry.is_private(),
+            html: markdown::to_xhtml(entry.get_text()),
+            written: entry.written_note(),
+        }
+    }
+}
+
+impl Server {
+    /// Start listening on `port` (`0` for any free port), with `token` or a
+    /// random one.
+    pub fn bind(port: u16, token: Option<String>, config: Config) -> Result<Self, String> {
+        let http = tiny_http::Server::http(("127.0.0.1", port))
+            .map_err(|e| format!("failed to listen on port {}: {}", port, e))?;
+        let port = http
+            .server_addr()
+            .to_ip()
+            .map(|address| address.port())
+            .unwrap_or(port);
+
+        Ok(Server {
+            http,
+            port,
+            journal: Journal::new(),
+            config,
+            token: token.unwrap_or_else(random_token),
+            sessions: HashSet::new(),
+        })
+    }
+
+    /// Return the address to open in a browser to log in.
+    pub fn login_url(&self) -> String {
+        format!("http://127.0.0.1:{}/login?token={}", self.port, self.token)
+    }
+
+    /// Answer requests until the process is stopped.
+    ///
+    /// Requests are handled one at a time, they only take a few
+    /// milliseconds.
+    pub fn run(mut self) {
+        while let Ok(mut request) = self.http.recv() {
+            let response = self.handle(&mut request);
+            // the browser may have gone away, there is nothing to do then
+            let _ = request.respond(response);
+        }
+    }
+
+    fn handle(&mut self, request: &mut Request) -> HttpResponse {
+        let (path, params) = split_url(request.url());
+        let method = request.method().clone();
+
+        let result = match (&method, path.as_str()) {
+            (Method::Get, "/app.js") => Ok(asset(APP_JS, "text/javascript")),
+            (Method::Get, "/style.css") => {
+                let css = site::stylesheet() + STYLE;
+                Ok(asset(&css, "text/css"))
+            }
+            (Method::Get, "/login") => Ok(self.login(&params)),
+            (Method::Post, "/login") => {
+                let form = parse_query(&read_body(request));
+                Ok(self.login(&form))
+            }
+            _ if !self.is_logged_in(request) => Ok(page(
+                401,
+                "<p>open the link printed by <code>lifelog serve</code> to log in.</p>",
+            )),
+            (Method::Get, "/") => Ok(asset(INDEX_HTML, "text/html; charset=utf-8")),
+            (Method::Get, "/api/month") => self.month(&params),
+            (Method::Get, "/api/entry") => self.entry(&params),
+            (Method::Put, "/api/entry") => {
+                let body = read_body(request);
+                self.save_entry(&params, &body)
+            }
+            (Method::Delete, "/api/entry") => self.delete_entry(&params),
+            _ => Ok(page(404, "<p>there is nothing here.</p>")),
+        };
+
+        result.unwrap_or_else(|e| json(&HashMap::from([("error", e)])).with_status_code(400))
+    }
+
+    /// Log in with the token (and the passphrase of the journal, if it has
+    /// one), from the link or the login form.
+    fn login(&mut self, params: &HashMap<String, String>) -> HttpResponse {
+        let token = params.get("token").map(String::as_str).unwrap_or("");
+        if !same(token, &self.token) {
+            return page(
+                401,
+                "<p>invalid token, open the link printed by <code>lifelog serve</code>.</p>",
+            );
+        }
+
+        if lock::is_set() {
+            let passphrase = params.get("passphrase");
+            if !passphrase.is_some_and(|passphrase| lock::verify(passphrase)) {
+                let error = if passphrase.is_some() {
+                    "<p>wrong passphrase.</p>\n"
+                } else {
+                    ""
+                };
+                return page(
+                    401,
+                    &format!(
+                        "<h1>this journal is locked</h1>\n{}\
+                        <form method=\"post\" action=\"/login\">\n\
+                        <input type=\"hidden\" name=\"token\" value=\"{}\">\n\
+                        <input type=\"password\" name=\"passphrase\" placeholder=\"passphrase\" autofocus>\n\
+                        <button type=\"submit\">Unlock</button>\n\
+                        </form>",
+                        error,
+                        site::escape(token)
+                    ),
+                );
+            }
+        }
+
+        let session = random_token();
+        self.sessions.insert(session.clone());
+        Response::from_string("")
+            .with_status_code(303)
+            .with_header(header("Location", "/"))
+            .with_header(header(
+                "Set-Cookie",
+                &format!(
+                    "{}={}; Path=/; HttpOnly; SameSite=Strict",
+                    SESSION_COOKIE, session
+                ),
+            ))
+    }
+
+    fn is_logged_in(&self, request: &Request) -> bool {
+        request
+            .headers()
+            .iter()
+            .filter(|header| header.field.equiv("Cookie"))
+            .flat_map(|header| header.value.as_str().split(';'))
+            .filter_map(|cookie| {
+                cookie
+                    .trim()
+                    .strip_prefix(SESSION_COOKIE)?
+                    .strip_prefix('=')
+            })
+            .any(|session| self.sessions.contains(session))
+    }
+
+    fn month(&mut self, params: &HashMap<String, String>) -> Result<HttpResponse, String> {
+        let first = match params.get("month") {
+            Some(month) => NaiveDate::parse_from_str(&format!("{}-01", month), "%Y-%m-%d")
+                .map_err(|_| format!("invalid month '{}', expected YYYY-MM", month))?,
+            None => Local::today().naive_local().with_day(1).unwrap(),
+        };
+
+        let log = self
+            .journal
+            .month(&first.format("%B/%Y").to_string())
+            .map_err(|e| e.to_string())?;
+        let days = log
+            .entries()
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| DayView {
+                day: i as u32 + 1,
+                rating: (!entry.is_default()).then(|| entry.get_rating()),
+                private: entry.is_private(),
+            })
+            .collect();
+        let statistics = Statistics::for_month_log(log);
+
+        Ok(json(&MonthView {
+            journal: profile::current(),
+            month: first.format("%Y-%m").to_string(),
+            title: first.format("%B %Y").to_string(),
+            first_weekday: first.weekday().num_days_from_monday(),
+            days,
+            statistics,
+            ratings: (-2..=2)
+                .rev()
+                .map(|rating| (rating, self.config.rating_label(rating)))
+                .collect(),
+        }))
+    }
+
+    fn entry(&mut self, params: &HashMap<String, String>) -> Result<HttpResponse, String> {
+        let date = date_param(params)?;
+        let entry = self
+            .journal
+            .month(&date.format("%B/%Y").to_string())
+            .map_err(|e| e.to_string())?
+            .get_entry(date.day());
+
+        // private entries only leave the server when asked for
+        let reveal = params.get("reveal").is_some_and(|reveal| reveal == "1");
+        Ok(json(&EntryView::new(date, entry, reveal)))
+    }
+
+    fn save_entry(
+        &mut self,
+        params: &HashMap<String, String>,
+        body: &str,
+    ) -> Result<HttpResponse, String> {
+        let date = date_param(params)?;
+        let update: EntryUpdate =
+            serde_json::from_str(body).map_err(|e| format!("invalid entry: {}", e))?;
+        if date > Local::today().naive_local() {
+            return Err("that day has not come yet.".to_string());
+        }
+        if !(-2..=2).contains(&update.rating) {
+            return Err(format!("invalid rating {}", update.rating));
+        }
+        if update.text.trim().is_empty() {
+            return Err("the entry is empty.".to_string());
+        }
+
+        let month_year = date.format("%B/%Y").to_string();
+        self.journal
+            .update(&month_year, |log| {
+                log.update_entry(date.day(), update.rating, update.text);
+                log.set_private(date.day(), update.private);
+            })
+            .map_err(|e| e.to_string())?;
+
+        let entry = self
+            .journal
+            .month(&month_year)
+            .map_err(|e| e.to_string())?
+            .get_entry(date.day());
+        run_hook(Hook::new(
+            &self.config.hooks,
+            hooks::Event::PostSave,
+            Some((date, entry)),
+        ));
+        Ok(json(&HashMap::<String, String>::new()))
+    }
+
+    fn delete_entry(&mut self, params: &HashMap<String, String>) -> Result<HttpResponse, String> {
+        let date = date_param(params)?;
+        let month_year = date.format("%B/%Y").to_string();
+        let deleted = self
+            .journal
+            .month(&month_year)
+            .map_err(|e| e.to_string())?
+            .get_entry(date.day())
+            .clone();
+        if deleted.is_default() {
+            return Err("there is no entry on that day.".to_string());
+        }
+
+        self.journal
+            .update(&month_year, |log| log.delete_entry(date.day()))
+            .map_err(|e| e.to_string())?
+            .map_err(|e| format!("failed to delete the attachments: {}", e))?;
+
+        run_hook(Hook::new(
+            &self.config.hooks,
+            hooks::Event::PostDelete,
+            Some((date, &deleted)),
+        ));
+        Ok(json(&HashMap::<String, String>::new()))
+    }
+}
+
+/// Run the hook of a change that was saved on another thread, so the next
+/// requests don't wait for it. Its problems are printed where `lifelog serve`
+/// was started.
+fn run_hook(hook: Option<Hook>) {
+    if let Some(hook) = hook {
+        hook.spawn(|e| eprintln!("{}", e));
+    }
+}
+
+fn date_param(params: &HashMap<String, String>) -> Result<NaiveDate, String> {
+    let date = params.get("date").ok_or("missing date")?;
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|_| format!("invalid date '{}', expected YYYY-MM-DD", date))
+}
+
+fn read_body(request: &mut Request) -> String {
+    let mut body = String::new();
+    // an unreadable body is handled like an empty one
+    let _ = request.as_reader().take(MAX_BODY).read_to_string(&mut body);
+    body
+}
+
+fn header(name: &str, value: &str) -> Header {
+    Header::from_bytes(name.as_bytes(), value.as_bytes()).expect("invalid header")
+}
+
+fn json<T: Serialize>(value: &T) -> HttpResponse {
+    Response::from_string(serde_json::to_string(value).unwrap())
+        .with_header(header("Content-Type", "application/json"))
+        .with_header(header("Cache-Control", "no-store"))
+}
+
+fn asset(content: &str, content_type: &str) -> HttpResponse {
+    Response::from_string(content)
+        .with_header(header("Content-Type", content_type))
+        .with_header(header("Content-Security-Policy", "default-src 'self'"))
+}
+
+/// Return a page with a message, eg to log in.
+fn page(status: u16, body: &str) -> HttpResponse {
+    let html = format!(
+        "<!DOCTYPE html>\n\
+        <html>\n\
+        <head>\n\
+        <meta charset=\"utf-8\">\n\
+        <title>lifelog</title>\n\
+        <link rel=\"stylesheet\" href=\"/style.css\">\n\
+        </head>\n\
+        <body>\n<main>\n{}\n</main>\n</body>\n\
+        </html>\n",
+        body
+    );
+    asset(&html, "text/html; charset=utf-8").with_status_code(status)
+}
+
+/// Split a request URL into its path and its query parameters.
+fn split_url(url: &str) -> (String, HashMap<String, String>) {
+    match url.split_once('?') {
+        Some((path, query)) => (path.to_string(), parse_query(query)),
+        None => (url.to_string(), HashMap::new()),
+    }
+}
+
+/// Parse `name=value&...` pairs, as found in URLs and sent by forms.
+fn parse_query(query: &str) -> HashMap<String, String> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(name, value)| (decode(name), decode(value)))
+        .collect()
+}
+
+/// Decode the `%XX` escapes and `+` of a URL component.
+fn decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], escaped) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (b'+', _) => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Return 32 random bytes in hexadecimal, for tokens and sessions.
+fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Compare two secrets in a time that doesn't depend on where they differ.
+fn same(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+const INDEX_HTML: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>lifelog</title>
+<link rel="stylesheet" href="/style.css">
+<script src="/app.js" defer></script>
+</head>
+<body>
+<nav id="journal">lifelog</nav>
+<main class="app">
+<section>
+<div class="month">
+<button id="previous" type="button">&lsaquo;</button>
+<h2 id="title"></h2>
+<button id="next" type="button">&rsaquo;</button>
+</div>
+<table id="calendar" class="calendar"></table>
+<table id="statistics" class="statistics"></table>
+</section>
+<section class="day">
+<h2 id="day"></h2>
+<div id="preview"></div>
+<form id="editor">
+<textarea id="text" rows="12" placeholder="how was your day?"></textarea>
+<div class="controls">
+<select id="rating"></select>
+<label><input id="private" type="checkbox"> private</label>
+<button type="submit">Save</button>
+<button id="delete" type="button">Delete</button>
+</div>
+</form>
+<p id="message"></p>
+</section>
+</main>
+</body>
+</html>
+"#;
+
+/// Additions to the style of the static site for the web interface.
+const STYLE: &str = "\
+main.app { max-width: 64em; display: flex; flex-wrap: wrap; gap: 2em; }
+main.app section.day { flex: 1; min-width: 20em; }
+.month { display: flex; align-items: center; justify-content: space-between; }
+.calendar td { cursor: pointer; padding: 0.3em; }
+.calendar td.empty { cursor: default; }
+.calendar td.selected { outline: 2px solid; }
+#text { width: 100%; box-sizing: border-box; font: inherit; }
+.controls { display: flex; gap: 1em; align-items: center; margin-top: 0.5em; }
+#message { font-style: italic; }
+";
+
+const APP_JS: &str = r#"// the web interface of `lifelog serve`, on top of its JSON API
+const $ = (id) => document.getElementById(id);
+let month = null;
+let selected = null;
+let revealed = null;
+
+async function api(method, path, body) {
+    const response = await fetch(path, {
+        method,
+        headers: body ? { "Content-Type": "application/json" } : {},
+        body: body ? JSON.stringify(body) : undefined,
+    });
+    const json = await response.json();
+    if (!response.ok) {
+        throw new Error(json.error);
+    }
+    return json;
+}
+
+function today() {
+    const now = new Date();
+    const pad = (n) => String(n).padStart(2, "0");
+    return `${now.getFullYear()}-${pad(now.getMonth() + 1)}-${pad(now.getDate())}`;
+}
+
+function cell(row, text, className) {
+    const td = document.createElement(row.parentElement ? "td" : "th");
+    td.textContent = text;
+    if (className) {
+        td.className = className;
+    }
+    row.appendChild(td);
+    return td;
+}
+
+async function loadMonth(value) {
+    const data = await api("GET", "/api/month" + (value ? `?month=${value}` : ""));
+    month = data.month;
+    $("journal").textContent = data.journal === "default" ? "lifelog" : `lifelog - ${data.journal}`;
+    $("title").textContent = data.title;
+
+    const calendar = $("calendar");
+    calendar.innerHTML = "<tr><th>Mo</th><th>Tu</th><th>We</th><th>Th</th><th>Fr</th><th>Sa</th><th>Su</th></tr>";
+    let row = calendar.insertRow();
+    for (let i = 0; i < data.first_weekday; i++) {
+        cell(row, "", "empty");
+    }
+    for (const day of data.days) {
+        if (row.cells.length === 7) {
+            row = calendar.insertRow();
+        }
+        const date = `${month}-${String(day.day).padStart(2, "0")}`;
+        const td = cell(row, day.day, day.rating === null ? "" : `rating-${day.rating + 2}`);
+        if (date === selected) {
+            td.classList.add("selected");
+        }
+        td.addEventListener("click", () => selectDay(date));
+    }
+
+    const rating = $("rating");
+    rating.innerHTML = "";
+    for (const [value, label] of data.ratings) {
+        rating.add(new Option(label, value));
+    }
+
+    const statistics = $("statistics");
+    statistics.innerHTML = "";
+    const counts = ["awesome", "good", "okay", "bad", "horrible"];
+    data.ratings.forEach(([value, label], i) => {
+        const row = statistics.insertRow();
+        cell(row, label, `rating-${value + 2}`);
+        cell(row, data.statistics[counts[i]]);
+    });
+    const missed = statistics.insertRow();
+    cell(missed, "no entry");
+    cell(missed, data.statistics.missed);
+    if (data.statistics.average !== null) {
+        const average = statistics.insertRow();
+        cell(average, "average");
+        cell(average, data.statistics.average.toFixed(2));
+    }
+}
+
+async function selectDay(date) {
+    selected = date;
+    $("message").textContent = "";
+    if (date.slice(0, 7) !== month) {
+        await loadMonth(date.slice(0, 7));
+    } else {
+        for (const td of document.querySelectorAll(".calendar td.selected")) {
+            td.classList.remove("selected");
+        }
+        await loadMonth(month);
+    }
+
+    const reveal = revealed === date ? "&reveal=1" : "";
+    const view = await api("GET", `/api/entry?date=${date}${reveal}`);
+    $("day").textContent = view.title;
+    const preview = $("preview");
+    const entry = view.entry;
+    const hidden = view.private && !entry;
+    if (hidden) {
+        preview.innerHTML = "<p>this entry is private.</p>";
+        const show = document.createElement("button");
+        show.textContent = "Show";
+        show.addEventListener("click", () => {
+            revealed = date;
+            selectDay(date);
+        });
+        preview.appendChild(show);
+    } else if (!entry) {
+        preview.textContent = "no entry yet.";
+    } else {
+        preview.innerHTML = view.html;
+        if (view.written) {
+            const note = document.createElement("p");
+            note.className = "note";
+            note.textContent = view.written;
+            preview.prepend(note);
+        }
+    }
+
+    $("editor").hidden = hidden;
+    $("text").value = entry ? entry.text : "";
+    $("rating").value = entry ? entry.rating : 0;
+    $("private").checked = entry ? entry.private : false;
+    $("delete").hidden = !entry && !hidden;
+}
+
+async function change(method, body, done) {
+    try {
+        await api(method, `/api/entry?date=${selected}`, body);
+        await selectDay(selected);
+        $("message").textContent = done;
+    } catch (error) {
+        $("message").textContent = error.message;
+    }
+}
+
+$("editor").addEventListener("submit", (event) => {
+    event.preventDefault();
+    change("PUT", {
+        rating: Number($("rating").value),
+        text: $("text").value,
+        private: $("private").checked,
+    }, "entry saved!");
+});
+$("delete").addEventListener("click", () => {
+    if (confirm("delete this entry?")) {
+        change("DELETE", null, "entry deleted.");
+    }
+});
+
+function shiftMonth(delta) {
+    const [year, number] = month.split("-").map(Number);
+    const date = new Date(year, number - 1 + delta, 1);
+    loadMonth(`${date.getFullYear()}-${String(date.getMonth() + 1).padStart(2, "0")}`);
+}
+$("previous").addEventListener("click", () => shiftMonth(-1));
+$("next").addEventListener("click", () => shiftMonth(1));
+
+selectDay(today());
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_escapes_and_spaces() {
+        assert_eq!(decode("sun+set%231.png"), "sun set#1.png");
+        assert_eq!(decode("caf%C3%A9%20ok"), "café ok");
+        assert_eq!(decode("100%"), "100%");
+        assert_eq!(decode("%zz%4"), "%zz%4");
+    }
+
+    #[test]
+    fn split_url_decodes_the_query() {
+        let (path, query) = split_url("/entry?date=2022-06-15&text=a%26b+c&flag");
+        assert_eq!(path, "/entry");
+        assert_eq!(query.len(), 2);
+        assert_eq!(query["date"], "2022-06-15");
+        assert_eq!(query["text"], "a&b c");
+
+        let (path, query) = split_url("/");
+        assert_eq!(path, "/");
+        assert!(query.is_empty());
+    }
+
+    #[test]
+    fn same_compares_whole_secrets() {
+        assert!(same("secret", "secret"));
+        assert!(same("", ""));
+        assert!(!same("secret", "secreT"));
+        assert!(!same("secret", "secret2"));
+        assert!(!same("secret", ""));
+    }
+
+    #[test]
+    fn private_entries_are_sent_only_when_revealed() {
+        let date = NaiveDate::from_ymd(2022, 6, 15);
+        let entry = Entry::new(1, "a secret").with_private(true);
+
+        let hidden = serde_json::to_string(&EntryView::new(date, &entry, false)).unwrap();
+        assert!(!hidden.contains("secret"));
+        assert!(hidden.contains(r#""entry":null,"private":true"#));
+
+        let revealed = serde_json::to_string(&EntryView::new(date, &entry, true)).unwrap();
+        assert!(revealed.contains(r#""text":"a secret""#));
+        assert!(revealed.contains("<p>a secret</p>"));
+
+        let entry = entry.with_private(false);
+        let public = serde_json::to_string(&EntryView::new(date, &entry, false)).unwrap();
+        assert!(public.contains(r#""text":"a secret""#));
+    }
+}
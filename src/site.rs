@@ -0,0 +1,585 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use chrono::{Datelike, NaiveDate, Weekday};
+use cursive::theme::{BaseColor, Color, PaletteColor};
+use serde::Serialize;
+
+use super::config::Config;
+use super::journal::Journal;
+use super::month_log::{Entry, MonthLog};
+use super::statistics::Statistics;
+use super::{attachment, markdown, statistics, theme};
+
+/// A static website of the journal, made to be browsed offline.
+///
+/// ```text
+/// ./site
+/// ├── index.html             (years)
+/// ├── search.html
+/// ├── search.js
+/// ├── search-index.js
+/// ├── style.css              (colors of the user's theme)
+/// ├── tags
+/// │   ├── index.html
+/// │   └── hiking.html        (days tagged #hiking)
+/// └── 2022
+///     ├── index.html         (months of the year)
+///     ├── 08.html            (calendar of August)
+///     └── 08
+///         ├── 05.html        (entry of 5 August)
+///         └── 05
+///             └── sunset.jpg (attachments)
+/// ```
+pub struct Site {
+    config: Config,
+    /// Whether the text of private entries is exported.
+    include_private: bool,
+    /// Days of every tag, by tag name.
+    tags: BTreeMap<String, Vec<NaiveDate>>,
+    search_index: Vec<SearchItem>,
+    /// Number of day pages written.
+    days: u32,
+}
+
+/// A month of the site, with the statistics of its days that are exported.
+struct Month {
+    log: MonthLog,
+    statistics: Statistics,
+}
+
+/// An entry as the search page knows it.
+#[derive(Serialize)]
+struct SearchItem {
+    date: String,
+    url: String,
+    rating: i8,
+    text: String,
+    tags: Vec<String>,
+}
+
+/// Colors used for the ratings, from `-2` to `+2`.
+pub const RATING_COLORS: [&str; 5] = ["#c62828", "#ef8a80", "#bdbdbd", "#8fd18f", "#2e7d32"];
+
+const SEARCH_JS: &str = r#"// search the entries listed in search-index.js
+const input = document.getElementById("query");
+const results = document.getElementById("results");
+
+function search() {
+    const words = input.value.toLowerCase().split(/\s+/).filter(w => w);
+    results.innerHTML = "";
+    if (words.length === 0) {
+        return;
+    }
+
+    const matches = SEARCH_INDEX.filter(item => {
+        const haystack = (item.text + " " + item.tags.join(" ")).toLowerCase();
+        return words.every(word => haystack.includes(word));
+    });
+    for (const item of matches) {
+        const li = document.createElement("li");
+        const link = document.createElement("a");
+        link.href = item.url;
+        link.textContent = item.date;
+        link.className = "rating-" + (item.rating + 2);
+        li.appendChild(link);
+        li.appendChild(document.createTextNode(" " + item.text.slice(0, 160)));
+        results.appendChild(li);
+    }
+    if (matches.length === 0) {
+        results.innerHTML = "<li>no entries found.</li>";
+    }
+}
+
+input.addEventListener("input", search);
+"#;
+
+impl Site {
+    pub fn new(config: Config, include_private: bool) -> Self {
+        Site {
+            config,
+            include_private,
+            tags: BTreeMap::new(),
+            search_index: Vec::new(),
+            days: 0,
+        }
+    }
+
+    /// Write the pages of the days in `from..=to` to `dir`.
+    ///
+    /// Return the number of days that have a page.
+    pub fn export(
+        mut self,
+        journal: &mut Journal,
+        dir: &Path,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> io::Result<u32> {
+        fs::create_dir_all(dir)?;
+
+        let mut years: BTreeMap<i32, Vec<Month>> = BTreeMap::new();
+        for (first, last) in statistics::months(from, to) {
+            let log = journal.month(&first.format("%B/%Y").to_string())?.clone();
+            let statistics = Statistics::for_range(journal, first, last)?;
+            years
+                .entry(first.year())
+                .or_default()
+                .push(Month { log, statistics });
+        }
+
+        for (year, months) in &years {
+            fs::create_dir_all(dir.join(year.to_string()))?;
+            for month in months {
+                self.write_month(dir, month, from, to)?;
+            }
+            fs::write(
+                dir.join(year.to_string()).join("index.html"),
+                self.year_page(*year, months, from, to),
+            )?;
+        }
+
+        fs::write(dir.join("index.html"), self.index_page(&years))?;
+        fs::write(dir.join("style.css"), stylesheet())?;
+        fs::write(dir.join("search.html"), search_page())?;
+        fs::write(dir.join("search.js"), SEARCH_JS)?;
+        fs::write(
+            dir.join("search-index.js"),
+            format!(
+                "const SEARCH_INDEX = {};\n",
+                serde_json::to_string(&self.search_index).unwrap()
+            ),
+        )?;
+
+        fs::create_dir_all(dir.join("tags"))?;
+        for (tag, days) in &self.tags {
+            fs::write(
+                dir.join("tags").join(format!("{}.html", tag)),
+                tag_page(tag, days),
+            )?;
+        }
+        fs::write(dir.join("tags").join("index.html"), self.tags_page())?;
+
+        Ok(self.days)
+    }
+
+    /// Write the month page and the pages of the days of `month` that are in
+    /// `from..=to`.
+    fn write_month(
+        &mut self,
+        dir: &Path,
+        month: &Month,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> io::Result<()> {
+        let log = &month.log;
+        let first = log.first_day();
+        let month_dir = dir.join(first.format("%Y/%m").to_string());
+
+        for (i, entry) in log.entries().iter().enumerate() {
+            let date = first + chrono::Duration::days(i as i64);
+            if entry.is_default() || date < from || date > to {
+                continue;
+            }
+            fs::create_dir_all(&month_dir)?;
+
+            let hidden = entry.is_private() && !self.include_private;
+            if !hidden {
+                self.copy_attachments(&month_dir, log, date, entry)?;
+                let tags = entry.tags();
+                for tag in &tags {
+                    self.tags.entry(tag.clone()).or_default().push(date);
+                }
+                self.search_index.push(SearchItem {
+                    date: date.to_string(),
+                    url: day_url(date),
+                    rating: entry.get_rating(),
+                    text: entry.get_text().to_string(),
+                    tags,
+                });
+            }
+
+            fs::write(
+                month_dir.join(format!("{:02}.html", date.day())),
+                self.day_page(date, entry, hidden),
+            )?;
+            self.days += 1;
+        }
+
+        fs::write(
+            dir.join(first.format("%Y/%m.html").to_string()),
+            self.month_page(month, from, to),
+        )
+    }
+
+    fn copy_attachments(
+        &self,
+        month_dir: &Path,
+        log: &MonthLog,
+        date: NaiveDate,
+        entry: &Entry,
+    ) -> io::Result<()> {
+        // a missing attachment is only left out of the site
+        attachment::copy_day(
+            &log.month_year(),
+            date.day(),
+            entry.get_attachments(),
+            &month_dir.join(format!("{:02}", date.day())),
+        )?;
+        Ok(())
+    }
+
+    fn index_page(&self, years: &BTreeMap<i32, Vec<Month>>) -> String {
+        let mut body = String::from("<h1>lifelog</h1>\n<ul class=\"years\">\n");
+        for (year, months) in years.iter().rev() {
+            let written: u32 = months.iter().map(|month| month.statistics.written).sum();
+            writeln!(
+                body,
+                "<li><a href=\"{0}/index.html\">{0}</a> - {1} entries</li>",
+                year, written
+            )
+            .unwrap();
+        }
+        body.push_str("</ul>\n");
+        page("lifelog", "", &body)
+    }
+
+    fn year_page(&self, year: i32, months: &[Month], from: NaiveDate, to: NaiveDate) -> String {
+        let mut body = format!("<h1>{}</h1>\n<div class=\"months\">\n", year);
+        for month in months {
+            let first = month.log.first_day();
+            write!(
+                body,
+                "<section>\n<h2><a href=\"{}\">{}</a></h2>\n{}</section>\n",
+                first.format("%m.html"),
+                first.format("%B"),
+                calendar(&month.log, &first.format("%m/").to_string(), from, to)
+            )
+            .unwrap();
+        }
+        body.push_str("</div>\n");
+        page(&year.to_string(), "../", &body)
+    }
+
+    fn month_page(&self, month: &Month, from: NaiveDate, to: NaiveDate) -> String {
+        let first = month.log.first_day();
+        let statistics = &month.statistics;
+
+        let mut body = format!(
+            "<h1>{}</h1>\n{}",
+            first.format("%B %Y"),
+            calendar(&month.log, &first.format("%m/").to_string(), from, to)
+        );
+        body.push_str("<table class=\"statistics\">\n");
+        for (rating, count) in (-2..=2).rev().zip([
+            statistics.awesome,
+            statistics.good,
+            statistics.okay,
+            statistics.bad,
+            statistics.horrible,
+        ]) {
+            writeln!(
+                body,
+                "<tr><td class=\"rating-{}\">{}</td><td>{}</td></tr>",
+                rating + 2,
+                escape(&self.config.rating_label(rating)),
+                count
+            )
+            .unwrap();
+        }
+        writeln!(
+            body,
+            "<tr><td>no entry</td><td>{}</td></tr>\n</table>",
+            statistics.missed
+        )
+        .unwrap();
+        if let Some(average) = statistics.average {
+            writeln!(body, "<p>average rating: {:+.2}</p>", average).unwrap();
+        }
+
+        page(&first.format("%B %Y").to_string(), "../", &body)
+    }
+
+    fn day_page(&self, date: NaiveDate, entry: &Entry, hidden: bool) -> String {
+        let rating = entry.get_rating();
+        let mut body = format!(
+            "<p><a href=\"../{}\">{}</a></p>\n<h1>{}</h1>\n",
+            date.format("%m.html"),
+            date.format("%B %Y"),
+            date.format("%A, %-d %B %Y")
+        );
+        writeln!(
+            body,
+            "<p class=\"rating rating-{}\">{}</p>",
+            rating + 2,
+            escape(&self.config.rating_label(rating))
+        )
+        .unwrap();
+
+        if hidden {
+            body.push_str("<p><em>this entry is private.</em></p>\n");
+            return page(&date.to_string(), "../../", &body);
+        }
+
+        if let Some(written) = entry.written_note() {
+            writeln!(body, "<p class=\"note\">{}</p>", escape(&written)).unwrap();
+        }
+        writeln!(
+            body,
+            "<article>\n{}</article>",
+            markdown::to_html(entry.get_text())
+        )
+        .unwrap();
+
+        if !entry.get_habits().is_empty() {
+            writeln!(
+                body,
+                "<p>habits done: {}</p>",
+                escape(&entry.get_habits().join(", "))
+            )
+            .unwrap();
+        }
+        if !entry.get_metrics().is_empty() {
+            body.push_str("<ul class=\"metrics\">\n");
+            for (name, value) in entry.get_metrics() {
+                writeln!(
+                    body,
+                    "<li>{}: {}</li>",
+                    escape(name),
+                    escape(&value.to_string())
+                )
+                .unwrap();
+            }
+            body.push_str("</ul>\n");
+        }
+        if !entry.get_attachments().is_empty() {
+            body.push_str("<ul class=\"attachments\">\n");
+            for name in entry.get_attachments() {
+                writeln!(
+                    body,
+                    "<li><a href=\"{:02}/{}\">{}</a></li>",
+                    date.day(),
+                    url_encode(name),
+                    escape(name)
+                )
+                .unwrap();
+            }
+            body.push_str("</ul>\n");
+        }
+
+        let tags = entry.tags();
+        if !tags.is_empty() {
+            body.push_str("<p class=\"tags\">");
+            for tag in tags {
+                write!(body, "<a href=\"../../tags/{0}.html\">#{0}</a> ", tag).unwrap();
+            }
+            body.push_str("</p>\n");
+        }
+
+        page(&date.to_string(), "../../", &body)
+    }
+
+    fn tags_page(&self) -> String {
+        let mut body = String::from("<h1>tags</h1>\n<ul>\n");
+        for (tag, days) in &self.tags {
+            writeln!(
+                body,
+                "<li><a href=\"{0}.html\">#{0}</a> - {1} days</li>",
+                tag,
+                days.len()
+            )
+            .unwrap();
+        }
+        body.push_str("</ul>\n");
+        if self.tags.is_empty() {
+            body.push_str("<p>no tags yet, add some to your entries like #this.</p>\n");
+        }
+        page("tags", "../", &body)
+    }
+}
+
+fn tag_page(tag: &str, days: &[NaiveDate]) -> String {
+    let mut body = format!("<h1>#{}</h1>\n<ul>\n", tag);
+    for date in days {
+        writeln!(
+            body,
+            "<li><a href=\"../{}\">{}</a></li>",
+            day_url(*date),
+            date.format("%A, %-d %B %Y")
+        )
+        .unwrap();
+    }
+    body.push_str("</ul>\n");
+    page(&format!("#{}", tag), "../", &body)
+}
+
+fn search_page() -> String {
+    let body = "<h1>search</h1>\n\
+        <input id=\"query\" type=\"search\" placeholder=\"words to look for\" autofocus>\n\
+        <ul id=\"results\"></ul>\n\
+        <script src=\"search-index.js\"></script>\n\
+        <script src=\"search.js\"></script>\n";
+    page("search", "", body)
+}
+
+/// Return a page with the navigation links, `root` is the relative path to
+/// the top of the site (eg `../` for a year page).
+fn page(title: &str, root: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n\
+        <html>\n\
+        <head>\n\
+        <meta charset=\"utf-8\">\n\
+        <title>{title}</title>\n\
+        <link rel=\"stylesheet\" href=\"{root}style.css\">\n\
+        </head>\n\
+        <body>\n\
+        <nav><a href=\"{root}index.html\">years</a> \
+        <a href=\"{root}tags/index.html\">tags</a> \
+        <a href=\"{root}search.html\">search</a></nav>\n\
+        <main>\n{body}</main>\n\
+        </body>\n\
+        </html>\n",
+        title = escape(title),
+        root = root,
+        body = body
+    )
+}
+
+/// Return a calendar of the month with the days colored by rating, each day
+/// with an entry links to its page (`prefix` is the path to the day pages).
+///
+/// Only the days in `from..=to` have a page, the others are left plain.
+fn calendar(log: &MonthLog, prefix: &str, from: NaiveDate, to: NaiveDate) -> String {
+    let mut html = String::from(
+        "<table class=\"calendar\">\n<tr><th>Mo</th><th>Tu</th><th>We</th>\
+        <th>Th</th><th>Fr</th><th>Sa</th><th>Su</th></tr>\n<tr>",
+    );
+
+    let first = log.first_day();
+    let offset = first.weekday().num_days_from_monday();
+    html.push_str(&"<td></td>".repeat(offset as usize));
+    for (i, entry) in log.entries().iter().enumerate() {
+        let day = i as u32 + 1;
+        let date = first.with_day(day).unwrap();
+        if day > 1 && date.weekday() == Weekday::Mon {
+            html.push_str("</tr>\n<tr>");
+        }
+        if entry.is_default() || date < from || date > to {
+            write!(html, "<td>{}</td>", day).unwrap();
+        } else {
+            write!(
+                html,
+                "<td class=\"rating-{}\"><a href=\"{}{:02}.html\">{}</a></td>",
+                entry.get_rating() + 2,
+                prefix,
+                day,
+                day
+            )
+            .unwrap();
+        }
+    }
+    html.push_str("</tr>\n</table>\n");
+    html
+}
+
+/// Return the style of the site, with the colors of the selected theme.
+pub fn stylesheet() -> String {
+    let palette = theme::load(&theme::selected()).unwrap_or_default().palette;
+    let color = |name: PaletteColor, default: &str| css_color(palette[name], default);
+
+    let mut css = format!(
+        "body {{ background: {}; color: {}; font-family: sans-serif; margin: 0; }}\n\
+        nav {{ background: {}; padding: 0.5em 1em; }}\n\
+        nav a {{ color: {}; margin-right: 1em; }}\n\
+        main {{ background: {}; max-width: 48em; margin: 1em auto; padding: 1em 2em; }}\n\
+        a {{ color: {}; }}\n\
+        h1, h2 {{ color: {}; }}\n\
+        .note {{ color: {}; font-style: italic; }}\n\
+        .months {{ display: flex; flex-wrap: wrap; gap: 1em; }}\n\
+        .calendar td, .calendar th {{ width: 2em; text-align: center; }}\n\
+        .calendar td a {{ color: inherit; }}\n\
+        .rating {{ display: inline-block; padding: 0.2em 0.6em; }}\n\
+        #query {{ width: 100%; font-size: 1.2em; }}\n",
+        color(PaletteColor::Background, "#e0e0e0"),
+        color(PaletteColor::Primary, "#000000"),
+        color(PaletteColor::Highlight, "#5f5fd7"),
+        color(PaletteColor::HighlightText, "#ffffff"),
+        color(PaletteColor::View, "#ffffff"),
+        color(PaletteColor::Secondary, "#0000ee"),
+        color(PaletteColor::TitlePrimary, "#cd0000"),
+        color(PaletteColor::Tertiary, "#7f7f7f"),
+    );
+    for (i, background) in RATING_COLORS.iter().enumerate() {
+        writeln!(
+            css,
+            ".rating-{} {{ background: {}; color: #000000; }}",
+            i, background
+        )
+        .unwrap();
+    }
+    css
+}
+
+/// Return the CSS value of a terminal color, `default` for the terminal's
+/// own color.
+fn css_color(color: Color, default: &str) -> String {
+    let base = |base: BaseColor, light: bool| -> &str {
+        match (base, light) {
+            (BaseColor::Black, false) => "#000000",
+            (BaseColor::Red, false) => "#cd0000",
+            (BaseColor::Green, false) => "#00cd00",
+            (BaseColor::Yellow, false) => "#cdcd00",
+            (BaseColor::Blue, false) => "#0000ee",
+            (BaseColor::Magenta, false) => "#cd00cd",
+            (BaseColor::Cyan, false) => "#00cdcd",
+            (BaseColor::White, false) => "#e5e5e5",
+            (BaseColor::Black, true) => "#7f7f7f",
+            (BaseColor::Red, true) => "#ff0000",
+            (BaseColor::Green, true) => "#00ff00",
+            (BaseColor::Yellow, true) => "#ffff00",
+            (BaseColor::Blue, true) => "#5c5cff",
+            (BaseColor::Magenta, true) => "#ff00ff",
+            (BaseColor::Cyan, true) => "#00ffff",
+            (BaseColor::White, true) => "#ffffff",
+        }
+    };
+
+    match color {
+        Color::Rgb(r, g, b) => format!("#{:02x}{:02x}{:02x}", r, g, b),
+        // each component goes from 0 to 5
+        Color::RgbLowRes(r, g, b) => format!("#{:02x}{:02x}{:02x}", r * 51, g * 51, b * 51),
+        Color::Dark(color) => base(color, false).to_string(),
+        Color::Light(color) => base(color, true).to_string(),
+        Color::TerminalDefault => default.to_string(),
+    }
+}
+
+/// Return the path of the page of `date` from the top of the site.
+fn day_url(date: NaiveDate) -> String {
+    date.format("%Y/%m/%d.html").to_string()
+}
+
+/// Escape the characters that have a meaning in HTML.
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Percent-encode a file name to use it in a link, eg `a b#1.jpg` as
+/// `a%20b%231.jpg`.
+pub fn url_encode(name: &str) -> String {
+    let mut encoded = String::new();
+    for byte in name.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
@@ -0,0 +1,276 @@
+use std::io;
+
+use chrono::{Datelike, Local, NaiveDate};
+use serde::Serialize;
+
+use super::config::Config;
+use super::journal::Journal;
+use super::month_log::{Entry, MonthLog};
+
+/// Statistics of the entries written over a range of days.
+#[derive(Serialize)]
+pub struct Statistics {
+    /// First day of the range.
+    pub from: NaiveDate,
+    /// Last day of the range (inclusive).
+    pub to: NaiveDate,
+    /// Number of days rated `+2`.
+    pub awesome: u32,
+    /// Number of days rated `+1`.
+    pub good: u32,
+    /// Number of days rated `0`.
+    pub okay: u32,
+    /// Number of days rated `-1`.
+    pub bad: u32,
+    /// Number of days rated `-2`.
+    pub horrible: u32,
+    /// Number of days that have an entry.
+    pub written: u32,
+    /// Number of days up to and including today that have no entry.
+    pub missed: u32,
+    /// Number of days after today (these can't have an entry yet).
+    pub future: u32,
+    /// Average rating of the days that have an entry.
+    pub average: Option<f64>,
+}
+
+/// Header of the CSV output, matching `Statistics::csv_row`.
+pub const CSV_HEADER: &str = "from,to,awesome,good,okay,bad,horrible,written,missed,future,average";
+
+impl Statistics {
+    /// Construct empty statistics for the range `from..=to`.
+    fn new(from: NaiveDate, to: NaiveDate) -> Self {
+        Statistics {
+            from,
+            to,
+            awesome: 0,
+            good: 0,
+            okay: 0,
+            bad: 0,
+            horrible: 0,
+            written: 0,
+            missed: 0,
+            future: 0,
+            average: None,
+        }
+    }
+
+    /// Compute statistics for all the days of a `MonthLog`.
+    pub fn for_month_log(log: &MonthLog) -> Self {
+        let from = log.first_day();
+        let to = from + chrono::Duration::days(log.days() as i64 - 1);
+        let today = Local::today().naive_local();
+
+        let mut statistics = Self::new(from, to);
+        for day in 1..=log.days() {
+            let date = from.with_day(day).unwrap();
+            statistics.count(date, log.get_entry(day), today);
+        }
+        statistics.finish();
+        statistics
+    }
+
+    /// Compute statistics for the range `from..=to`.
+    pub fn for_range(journal: &mut Journal, from: NaiveDate, to: NaiveDate) -> io::Result<Self> {
+        let today = Local::today().naive_local();
+        let mut statistics = Self::new(from, to);
+        for (date, entry) in journal.days(from, to)? {
+            statistics.count(date, entry, today);
+        }
+        statistics.finish();
+        Ok(statistics)
+    }
+
+    /// Compute statistics for every month that overlaps the range `from..=to`.
+    ///
+    /// The first and last month are cut to the range.
+    pub fn monthly(journal: &mut Journal, from: NaiveDate, to: NaiveDate) -> io::Result<Vec<Self>> {
+        months(from, to)
+            .into_iter()
+            .map(|(from, to)| Self::for_range(journal, from, to))
+            .collect()
+    }
+
+    /// Count a single day in the statistics.
+    fn count(&mut self, date: NaiveDate, entry: &Entry, today: NaiveDate) {
+        if entry.is_default() {
+            if date > today {
+                self.future += 1;
+            } else {
+                self.missed += 1;
+            }
+            return;
+        }
+
+        self.written += 1;
+        match entry.get_rating() {
+            2 => self.awesome += 1,
+            1 => self.good += 1,
+            0 => self.okay += 1,
+            -1 => self.bad += 1,
+            -2 => self.horrible += 1,
+            _ => (),
+        }
+    }
+
+    /// Compute the values that depend on all the counted days.
+    fn finish(&mut self) {
+        let rated = self.awesome + self.good + self.okay + self.bad + self.horrible;
+        if rated > 0 {
+            let sum = 2 * self.awesome as i64 + self.good as i64
+                - self.bad as i64
+                - 2 * self.horrible as i64;
+            self.average = Some(sum as f64 / rated as f64);
+        }
+    }
+
+    /// Return the statistics as a CSV row (see `CSV_HEADER` for the columns).
+    pub fn csv_row(&self) -> String {
+        format!(
+            "{},{},{},{},{},{},{},{},{},{},{}",
+            self.from,
+            self.to,
+            self.awesome,
+            self.good,
+            self.okay,
+            self.bad,
+            self.horrible,
+            self.written,
+            self.missed,
+            self.future,
+            self.average
+                .map(|average| format!("{:.2}", average))
+                .unwrap_or_default()
+        )
+    }
+
+    /// Format the statistics the way they are shown in the `statistics` view,
+    /// with the names of the ratings from `config`.
+    pub fn summary(&self, config: &Config) -> String {
+        let mut summary = format!(
+            "{} - {}\n\
+            {} - {}\n\
+            {} - {}\n\
+            {} - {}\n\
+            {} - {}\n\n\
+            no entry - {}",
+            config.rating_label(2),
+            self.awesome,
+            config.rating_label(1),
+            self.good,
+            config.rating_label(0),
+            self.okay,
+            config.rating_label(-1),
+            self.bad,
+            config.rating_label(-2),
+            self.horrible,
+            self.missed
+        );
+        if self.future > 0 {
+            summary.push_str(&format!("\nupcoming - {}", self.future));
+        }
+        summary
+    }
+}
+
+/// Split the range `from..=to` into one range per month.
+///
+/// The first and last month are cut to the range.
+pub fn months(from: NaiveDate, to: NaiveDate) -> Vec<(NaiveDate, NaiveDate)> {
+    let mut months = Vec::new();
+
+    let mut start = from;
+    while start <= to {
+        let next_month = match start.month() {
+            12 => NaiveDate::from_ymd(start.year() + 1, 1, 1),
+            month => NaiveDate::from_ymd(start.year(), month + 1, 1),
+        };
+        months.push((start, to.min(next_month.pred())));
+        start = next_month;
+    }
+    months
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::month_log::tests::TempDir;
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd(2022, 6, day)
+    }
+
+    #[test]
+    fn empty_days_are_missed_until_today() {
+        let mut statistics = Statistics::new(date(1), date(30));
+        for day in 1..=30 {
+            let entry = match day {
+                1 | 2 => Entry::new(2, "great"),
+                3 => Entry::new(-1, "meh"),
+                // an entry written ahead of time is counted as written
+                25 => Entry::new(0, "planned"),
+                _ => Entry::default(),
+            };
+            statistics.count(date(day), &entry, date(10));
+        }
+        statistics.finish();
+
+        assert_eq!(
+            (statistics.awesome, statistics.okay, statistics.bad),
+            (2, 1, 1)
+        );
+        assert_eq!(statistics.written, 4);
+        // days 4 to 10, today included
+        assert_eq!(statistics.missed, 7);
+        // days 11 to 30 but the 25th
+        assert_eq!(statistics.future, 19);
+        assert_eq!(statistics.average, Some(0.75));
+        assert_eq!(
+            statistics.csv_row(),
+            "2022-06-01,2022-06-30,2,0,1,1,0,4,7,19,0.75"
+        );
+    }
+
+    #[test]
+    fn ranges_without_entries() {
+        let _temp = TempDir::create();
+        let mut journal = Journal::new();
+
+        let past = Statistics::for_range(&mut journal, date(1), date(30)).unwrap();
+        assert_eq!((past.written, past.missed, past.future), (0, 30, 0));
+        assert_eq!(past.average, None);
+        assert_eq!(past.csv_row(), "2022-06-01,2022-06-30,0,0,0,0,0,0,30,0,");
+
+        let (from, to) = (
+            NaiveDate::from_ymd(2999, 1, 1),
+            NaiveDate::from_ymd(2999, 1, 31),
+        );
+        let future = Statistics::for_range(&mut journal, from, to).unwrap();
+        assert_eq!((future.missed, future.future), (0, 31));
+    }
+
+    #[test]
+    fn months_are_cut_to_the_range() {
+        assert_eq!(
+            months(
+                NaiveDate::from_ymd(2021, 12, 15),
+                NaiveDate::from_ymd(2022, 2, 3)
+            ),
+            [
+                (
+                    NaiveDate::from_ymd(2021, 12, 15),
+                    NaiveDate::from_ymd(2021, 12, 31)
+                ),
+                (
+                    NaiveDate::from_ymd(2022, 1, 1),
+                    NaiveDate::from_ymd(2022, 1, 31)
+                ),
+                (
+                    NaiveDate::from_ymd(2022, 2, 1),
+                    NaiveDate::from_ymd(2022, 2, 3)
+                ),
+            ]
+        );
+        assert!(months(date(2), date(1)).is_empty());
+    }
+}
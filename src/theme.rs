@@ -0,0 +1,159 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use cursive::theme::{self, Theme};
+
+use super::month_log;
+
+/// Name of the user's own theme, read from `theme.toml`.
+pub const CUSTOM: &str = "custom";
+
+/// Name of the theme used when nothing else is chosen or can be loaded.
+pub const DEFAULT: &str = "default";
+
+/// Themes that come with lifelog, by name, in the `theme.toml` format.
+const PRESETS: &[(&str, &str)] = &[
+    (DEFAULT, ""),
+    (
+        "dark",
+        r##"
+shadow = false
+borders = "simple"
+
+[colors]
+background = "black"
+shadow = "black"
+view = "#1c1c1c"
+primary = "#d0d0d0"
+secondary = "light blue"
+tertiary = "#8a8a8a"
+title_primary = "light cyan"
+title_secondary = "light yellow"
+highlight = "#005f87"
+highlight_inactive = "#3a3a3a"
+highlight_text = "white"
+"##,
+    ),
+    (
+        "light",
+        r##"
+shadow = true
+borders = "simple"
+
+[colors]
+background = "#e4e4e4"
+shadow = "#a8a8a8"
+view = "#ffffff"
+primary = "#262626"
+secondary = "#005faf"
+tertiary = "#808080"
+title_primary = "#870000"
+title_secondary = "#875f00"
+highlight = "#5f87d7"
+highlight_inactive = "#bcbcbc"
+highlight_text = "#ffffff"
+"##,
+    ),
+    (
+        "high contrast",
+        r##"
+shadow = false
+borders = "outset"
+
+[colors]
+background = "black"
+shadow = "black"
+view = "black"
+primary = "white"
+secondary = "light yellow"
+tertiary = "light white"
+title_primary = "light yellow"
+title_secondary = "light cyan"
+highlight = "light yellow"
+highlight_inactive = "white"
+highlight_text = "black"
+"##,
+    ),
+    (
+        "solarized",
+        r##"
+shadow = true
+borders = "simple"
+
+[colors]
+background = "#002b36"
+shadow = "#00212b"
+view = "#073642"
+primary = "#93a1a1"
+secondary = "#2aa198"
+tertiary = "#586e75"
+title_primary = "#b58900"
+title_secondary = "#cb4b16"
+highlight = "#268bd2"
+highlight_inactive = "#586e75"
+highlight_text = "#fdf6e3"
+"##,
+    ),
+];
+
+/// Return the names of the themes to choose from: the built-in ones and,
+/// if there is a `theme.toml` file, the custom one.
+pub fn names() -> Vec<&'static str> {
+    let mut names: Vec<&str> = PRESETS.iter().map(|(name, _)| *name).collect();
+    if custom_path().exists() {
+        names.push(CUSTOM);
+    }
+    names
+}
+
+/// Load the theme called `name`.
+///
+/// Return a description of the problem if it is the custom theme and its
+/// file is invalid, or if there is no theme with that name.
+pub fn load(name: &str) -> Result<Theme, String> {
+    if name == CUSTOM {
+        return theme::load_theme_file(custom_path()).map_err(|e| match e {
+            theme::Error::Io(e) => format!("failed to read theme.toml file ({})", e),
+            theme::Error::Parse(e) => format!("invalid theme.toml file ({})", e),
+        });
+    }
+
+    match PRESETS.iter().find(|(preset, _)| *preset == name) {
+        Some((_, toml)) => Ok(theme::load_toml(toml).expect("invalid built-in theme")),
+        None => Err(format!("unknown theme '{}'", name)),
+    }
+}
+
+/// Return the name of the chosen theme.
+///
+/// Without a saved choice, the custom theme is used if there is one (this is
+/// how lifelog always behaved), otherwise the default one.
+pub fn selected() -> String {
+    match fs::read_to_string(selected_path()) {
+        Ok(name) => name.trim().to_string(),
+        Err(_) if custom_path().exists() => CUSTOM.to_string(),
+        Err(_) => DEFAULT.to_string(),
+    }
+}
+
+/// Remember `name` as the chosen theme.
+pub fn save_selected(name: &str) -> io::Result<()> {
+    fs::create_dir_all(theme_dir())?;
+    fs::write(selected_path(), name)
+}
+
+/// Return the location of the directory with the theme files.
+pub fn theme_dir() -> PathBuf {
+    month_log::data_dir().parent().unwrap().join("theme")
+}
+
+/// Return the location of the user's own theme file.
+pub fn custom_path() -> PathBuf {
+    theme_dir().join("theme.toml")
+}
+
+/// Return the location of the file with the name of the chosen theme.
+fn selected_path() -> PathBuf {
+    theme_dir().join("selected")
+}